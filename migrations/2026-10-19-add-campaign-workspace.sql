@@ -0,0 +1,11 @@
+-- Campaign presets belong to a workspace; NULL holds the presets of links without one
+ALTER TABLE campaigns ADD COLUMN IF NOT EXISTS workspace_id INTEGER REFERENCES workspaces(id) ON DELETE CASCADE;
+ALTER TABLE campaigns DROP CONSTRAINT IF EXISTS campaigns_name_key;
+CREATE UNIQUE INDEX IF NOT EXISTS campaigns_workspace_name_idx ON campaigns (COALESCE(workspace_id, 0), name);
+
+-- Presets saved while they were shared by every workspace are copied into each of them
+INSERT INTO campaigns (workspace_id, name, utm, created_at)
+SELECT workspaces.id, campaigns.name, campaigns.utm, campaigns.created_at
+FROM campaigns CROSS JOIN workspaces
+WHERE campaigns.workspace_id IS NULL
+ON CONFLICT DO NOTHING;
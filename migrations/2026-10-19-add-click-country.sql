@@ -0,0 +1,2 @@
+-- Country of the visitor, resolved from the client address with the GeoIP database
+ALTER TABLE clicks ADD COLUMN IF NOT EXISTS country VARCHAR(2);
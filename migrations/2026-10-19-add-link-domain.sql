@@ -0,0 +1,8 @@
+-- Domain a link belongs to; NULL is the default domain of BASE_URL
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS domain_id INTEGER REFERENCES domains (id);
+-- Short codes are unique per domain, so a.co/x and b.co/x can be different links
+ALTER TABLE shortlink DROP CONSTRAINT IF EXISTS shortlink_short_code_key;
+CREATE UNIQUE INDEX IF NOT EXISTS shortlink_domain_short_code_idx ON shortlink (COALESCE(domain_id, 0), short_code);
+
+ALTER TABLE clicks ADD COLUMN IF NOT EXISTS domain_id INTEGER;
+ALTER TABLE health_checks ADD COLUMN IF NOT EXISTS domain_id INTEGER;
@@ -0,0 +1,4 @@
+-- Imported short codes may be longer than the generated 8 characters
+ALTER TABLE shortlink ALTER COLUMN short_code TYPE VARCHAR(64);
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS tags TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,21 @@
+-- Destination used while the primary one is failing health checks
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS fallback_url TEXT;
+-- Outcome of the latest health check; NULL until the destination has been checked
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS healthy BOOLEAN;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS health_checked_at TIMESTAMPTZ;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_tables WHERE tablename = 'health_checks') THEN
+        CREATE TABLE health_checks (
+            id BIGSERIAL PRIMARY KEY,
+            short_code VARCHAR(64) NOT NULL,
+            checked_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            healthy BOOLEAN NOT NULL,
+            status_code INTEGER,
+            latency_ms INTEGER NOT NULL,
+            error TEXT
+        );
+        CREATE INDEX health_checks_short_code_checked_at_idx ON health_checks (short_code, checked_at);
+    END IF;
+END $$;
@@ -0,0 +1,4 @@
+-- Show the preview page to every visitor instead of redirecting straight away
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS interstitial BOOLEAN NOT NULL DEFAULT FALSE;
+-- The same for every link on a branded domain
+ALTER TABLE domains ADD COLUMN IF NOT EXISTS interstitial BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,15 @@
+-- Titles, notes and folders to organize links, and a search vector over them and the destination
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS title TEXT;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS notes TEXT;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS folder TEXT;
+
+-- Destinations are split on punctuation so that hosts and path segments are words of their own
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', COALESCE(title, '')), 'A')
+    || setweight(to_tsvector('simple', COALESCE(notes, '')), 'B')
+    || setweight(to_tsvector('simple', regexp_replace(original_url, '[^[:alnum:]]+', ' ', 'g')), 'C')
+) STORED;
+
+CREATE INDEX IF NOT EXISTS shortlink_search_vector_idx ON shortlink USING GIN (search_vector);
+CREATE INDEX IF NOT EXISTS shortlink_tags_idx ON shortlink USING GIN (tags);
+CREATE INDEX IF NOT EXISTS shortlink_workspace_folder_idx ON shortlink (workspace_id, folder);
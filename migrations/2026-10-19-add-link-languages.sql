@@ -0,0 +1,5 @@
+-- Languages a link is available in, negotiated with the visitor's Accept-Language header
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS language_rules JSONB NOT NULL DEFAULT '[]'::jsonb;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS default_language TEXT;
+-- Language each visitor was sent to
+ALTER TABLE clicks ADD COLUMN IF NOT EXISTS language TEXT;
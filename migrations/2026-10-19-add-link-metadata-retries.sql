@@ -0,0 +1,3 @@
+-- Failed attempts at fetching the destination page since it last changed, and when to try again
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS metadata_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS metadata_retry_at TIMESTAMPTZ;
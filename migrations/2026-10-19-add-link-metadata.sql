@@ -0,0 +1,10 @@
+-- Details of the destination page, fetched in the background for listings and previews
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS page_title TEXT;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS page_description TEXT;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS favicon_url TEXT;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS image_url TEXT;
+-- NULL until the destination has been fetched, and again after it changes
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS metadata_fetched_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS shortlink_metadata_pending_idx ON shortlink (id)
+    WHERE metadata_fetched_at IS NULL AND deleted_at IS NULL;
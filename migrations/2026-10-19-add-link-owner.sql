@@ -0,0 +1,8 @@
+-- Links created with an API key belong to it; anonymous links have no owner
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS owner_key_id INTEGER REFERENCES api_keys(id);
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS normalized_url TEXT;
+UPDATE shortlink SET normalized_url = original_url WHERE normalized_url IS NULL;
+CREATE INDEX IF NOT EXISTS shortlink_owner_normalized_url_idx ON shortlink (owner_key_id, normalized_url);
+
+-- Owners that opt in get their existing link back when shortening the same URL again
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS reuse_existing BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,2 @@
+-- What a link forwards from the visited URL to its destination: none, query, path or both
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS passthrough TEXT NOT NULL DEFAULT 'none';
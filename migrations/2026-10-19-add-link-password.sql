@@ -0,0 +1,2 @@
+-- Argon2 hash of the password protecting a link; NULL for public links
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS password_hash TEXT;
@@ -0,0 +1,14 @@
+-- Links resolve only between active_from and active_until; expires_at becomes active_until
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM information_schema.columns
+               WHERE table_name = 'shortlink' AND column_name = 'expires_at') THEN
+        ALTER TABLE shortlink RENAME COLUMN expires_at TO active_until;
+    END IF;
+END $$;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS active_until TIMESTAMPTZ;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS active_from TIMESTAMPTZ;
+
+-- Where to send visitors before the window opens and after it closes, instead of showing a page
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS upcoming_url TEXT;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS ended_url TEXT;
@@ -0,0 +1,3 @@
+-- Deleted links stay in the trash, keeping their short code, until they are purged
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+CREATE INDEX IF NOT EXISTS shortlink_deleted_at_idx ON shortlink (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,2 @@
+-- Rules sending visitors elsewhere based on their device, as a JSON array evaluated in order
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS targeting_rules JSONB NOT NULL DEFAULT '[]'::jsonb;
@@ -0,0 +1,4 @@
+-- UTM parameters added to a link's destinations, as a JSON object of utm_source, utm_medium, ...
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS utm JSONB NOT NULL DEFAULT '{}'::jsonb;
+-- Campaign preset the parameters came from
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS campaign TEXT;
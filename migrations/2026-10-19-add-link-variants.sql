@@ -0,0 +1,5 @@
+-- Weighted destinations splitting a link's traffic, as a JSON array of {name, url, weight}
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS variants JSONB NOT NULL DEFAULT '[]'::jsonb;
+-- Variant each visitor was sent to
+ALTER TABLE clicks ADD COLUMN IF NOT EXISTS variant TEXT;
+CREATE INDEX IF NOT EXISTS clicks_short_code_variant_idx ON clicks (short_code, variant) WHERE variant IS NOT NULL;
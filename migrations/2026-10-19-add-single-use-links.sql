@@ -0,0 +1,3 @@
+-- Single-use links stop resolving once consumed_at is set
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS single_use BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS consumed_at TIMESTAMPTZ;
@@ -0,0 +1,18 @@
+-- API keys are issued per workspace, optionally to one of its members, with a set of scopes
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS workspace_id INTEGER REFERENCES workspaces(id) ON DELETE CASCADE;
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS member_id INTEGER REFERENCES workspace_members(id) ON DELETE SET NULL;
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS scopes TEXT[] NOT NULL
+    DEFAULT ARRAY['read_links', 'write_links', 'manage_members', 'manage_keys', 'manage_workspace'];
+
+-- Links belong to the workspace of the key that created them
+ALTER TABLE shortlink ADD COLUMN IF NOT EXISTS workspace_id INTEGER REFERENCES workspaces(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS shortlink_workspace_idx ON shortlink (workspace_id);
+
+-- Keys created before workspaces existed, and their links, move to a "default" workspace
+INSERT INTO workspaces (name)
+SELECT 'default' WHERE EXISTS (SELECT 1 FROM api_keys WHERE workspace_id IS NULL)
+ON CONFLICT (name) DO NOTHING;
+UPDATE api_keys SET workspace_id = (SELECT id FROM workspaces WHERE name = 'default') WHERE workspace_id IS NULL;
+UPDATE shortlink SET workspace_id = api_keys.workspace_id
+FROM api_keys WHERE shortlink.owner_key_id = api_keys.id AND shortlink.workspace_id IS NULL;
+ALTER TABLE api_keys ALTER COLUMN workspace_id SET NOT NULL;
@@ -0,0 +1,13 @@
+DO $$ 
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_tables WHERE tablename = 'api_keys') THEN
+        CREATE TABLE api_keys (
+            id SERIAL PRIMARY KEY,
+            name TEXT NOT NULL,
+            key_prefix VARCHAR(16) NOT NULL,
+            key_hash CHAR(64) NOT NULL UNIQUE,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            revoked_at TIMESTAMP
+        );
+    END IF;
+END $$;
@@ -0,0 +1,32 @@
+-- Who changed what and when; rows are never updated or deleted, and outlive what they describe
+CREATE TABLE IF NOT EXISTS audit_events (
+    id BIGSERIAL PRIMARY KEY,
+    workspace_id INTEGER,
+    actor TEXT NOT NULL,
+    actor_key_id INTEGER,
+    actor_name TEXT,
+    ip INET,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    diff JSONB NOT NULL DEFAULT '{}'::jsonb,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS audit_events_workspace_idx ON audit_events (workspace_id, id);
+CREATE INDEX IF NOT EXISTS audit_events_target_idx ON audit_events (target, id);
+
+CREATE OR REPLACE FUNCTION audit_events_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS audit_events_append_only ON audit_events;
+CREATE TRIGGER audit_events_append_only BEFORE UPDATE OR DELETE ON audit_events
+    FOR EACH ROW EXECUTE FUNCTION audit_events_append_only();
+
+-- Keys that can manage other keys may also read the audit log
+ALTER TABLE api_keys ALTER COLUMN scopes
+    SET DEFAULT ARRAY['read_links', 'write_links', 'manage_members', 'manage_keys', 'manage_workspace', 'read_audit'];
+UPDATE api_keys SET scopes = array_append(scopes, 'read_audit')
+WHERE 'manage_keys' = ANY(scopes) AND NOT 'read_audit' = ANY(scopes);
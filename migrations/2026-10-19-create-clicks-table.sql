@@ -0,0 +1,13 @@
+DO $$ 
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_tables WHERE tablename = 'clicks') THEN
+        CREATE TABLE clicks (
+            id BIGSERIAL PRIMARY KEY,
+            short_code VARCHAR(64) NOT NULL,
+            clicked_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            referer TEXT,
+            user_agent TEXT
+        );
+        CREATE INDEX clicks_short_code_clicked_at_idx ON clicks (short_code, clicked_at);
+    END IF;
+END $$;
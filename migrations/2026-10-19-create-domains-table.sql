@@ -0,0 +1,10 @@
+-- Branded short domains served by this deployment, matched against the Host header
+CREATE TABLE IF NOT EXISTS domains (
+    id SERIAL PRIMARY KEY,
+    host TEXT NOT NULL UNIQUE,
+    -- Where `/` redirects on this domain; NULL shows the index page
+    root_url TEXT,
+    -- Where unknown short codes redirect on this domain; NULL shows the 404 page
+    not_found_url TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
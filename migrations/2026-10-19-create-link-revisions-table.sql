@@ -0,0 +1,57 @@
+-- Numbered versions of the destination, rules and schedule of each link, recorded whenever they change
+CREATE TABLE IF NOT EXISTS link_revisions (
+    id BIGSERIAL PRIMARY KEY,
+    link_id INTEGER NOT NULL REFERENCES shortlink(id) ON DELETE CASCADE,
+    revision INTEGER NOT NULL,
+    original_url TEXT NOT NULL,
+    active_from TIMESTAMPTZ,
+    active_until TIMESTAMPTZ,
+    upcoming_url TEXT,
+    ended_url TEXT,
+    fallback_url TEXT,
+    targeting_rules JSONB NOT NULL DEFAULT '[]'::jsonb,
+    language_rules JSONB NOT NULL DEFAULT '[]'::jsonb,
+    default_language TEXT,
+    variants JSONB NOT NULL DEFAULT '[]'::jsonb,
+    passthrough TEXT NOT NULL DEFAULT 'none',
+    utm JSONB NOT NULL DEFAULT '{}'::jsonb,
+    campaign TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (link_id, revision)
+);
+
+-- Writers of a link hold its row lock, so numbering by MAX + 1 cannot race
+CREATE OR REPLACE FUNCTION record_link_revision() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' AND (
+        OLD.original_url, OLD.active_from, OLD.active_until, OLD.upcoming_url, OLD.ended_url, OLD.fallback_url,
+        OLD.targeting_rules, OLD.language_rules, OLD.default_language, OLD.variants, OLD.passthrough, OLD.utm,
+        OLD.campaign
+    ) IS NOT DISTINCT FROM (
+        NEW.original_url, NEW.active_from, NEW.active_until, NEW.upcoming_url, NEW.ended_url, NEW.fallback_url,
+        NEW.targeting_rules, NEW.language_rules, NEW.default_language, NEW.variants, NEW.passthrough, NEW.utm,
+        NEW.campaign
+    ) THEN
+        RETURN NEW;
+    END IF;
+    INSERT INTO link_revisions (link_id, revision, original_url, active_from, active_until, upcoming_url, ended_url,
+        fallback_url, targeting_rules, language_rules, default_language, variants, passthrough, utm, campaign)
+    SELECT NEW.id, COALESCE(MAX(revision), 0) + 1, NEW.original_url, NEW.active_from, NEW.active_until,
+        NEW.upcoming_url, NEW.ended_url, NEW.fallback_url, NEW.targeting_rules, NEW.language_rules,
+        NEW.default_language, NEW.variants, NEW.passthrough, NEW.utm, NEW.campaign
+    FROM link_revisions WHERE link_id = NEW.id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS record_link_revision ON shortlink;
+CREATE TRIGGER record_link_revision AFTER INSERT OR UPDATE ON shortlink
+    FOR EACH ROW EXECUTE FUNCTION record_link_revision();
+
+-- Existing links start their history with what they are now
+INSERT INTO link_revisions (link_id, revision, original_url, active_from, active_until, upcoming_url, ended_url,
+    fallback_url, targeting_rules, language_rules, default_language, variants, passthrough, utm, campaign, created_at)
+SELECT id, 1, original_url, active_from, active_until, upcoming_url, ended_url, fallback_url, targeting_rules,
+    language_rules, default_language, variants, passthrough, utm, campaign, COALESCE(created_at, LOCALTIMESTAMP)
+FROM shortlink
+WHERE NOT EXISTS (SELECT 1 FROM link_revisions WHERE link_revisions.link_id = shortlink.id);
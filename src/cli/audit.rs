@@ -0,0 +1,103 @@
+use super::links::parse_time;
+use super::output::{print_json, print_table, OutputFormat};
+use super::workspaces::workspace_id;
+use super::CliError;
+use crate::config::audit::list_audit_events;
+use crate::config::db::DbError;
+use crate::models::{AuditAction, AuditEvent, AuditQuery};
+use crate::utils::audit::{audit_limit, DEFAULT_AUDIT_LIMIT};
+use chrono::{DateTime, Utc};
+use clap::Subcommand;
+use deadpool_postgres::Pool;
+use serde_json::Value;
+
+#[derive(Debug, Subcommand)]
+pub enum AuditCommand {
+    /// List audit events, newest first
+    List {
+        /// Only events of this workspace
+        #[arg(long)]
+        workspace: Option<String>,
+        #[arg(long, value_enum)]
+        action: Option<AuditAction>,
+        /// What changed, e.g. `link:abc123`, `link:go.example.com/abc123`, `key:4` or `member:2`
+        #[arg(long)]
+        target: Option<String>,
+        /// Only changes made with this API key
+        #[arg(long)]
+        actor_key: Option<i32>,
+        /// Only events at or after this time (RFC 3339)
+        #[arg(long, value_parser = parse_time)]
+        since: Option<DateTime<Utc>>,
+        /// Only events before this time (RFC 3339)
+        #[arg(long, value_parser = parse_time)]
+        until: Option<DateTime<Utc>>,
+        /// Only events older than this ID, to page through the log
+        #[arg(long)]
+        before: Option<i64>,
+        #[arg(long, default_value_t = DEFAULT_AUDIT_LIMIT)]
+        limit: i64,
+    },
+}
+
+pub async fn run(command: AuditCommand, db_pool: Pool, output: OutputFormat) -> Result<(), CliError> {
+    let client = db_pool.get().await.map_err(|_| DbError::DatabaseError)?;
+
+    match command {
+        AuditCommand::List { workspace, action, target, actor_key, since, until, before, limit } => {
+            let workspace_id = match workspace {
+                Some(name) => Some(workspace_id(&client, &name).await?),
+                None => None,
+            };
+            let query = AuditQuery {
+                action,
+                target,
+                actor_key_id: actor_key,
+                since,
+                until,
+                before,
+                limit: Some(limit),
+            };
+            let events = list_audit_events(&client, workspace_id, &query, audit_limit(query.limit)).await?;
+            print_events(&events, output);
+        }
+    }
+    Ok(())
+}
+
+fn print_events(events: &[AuditEvent], output: OutputFormat) {
+    match output {
+        OutputFormat::Json => print_json(events),
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = events
+                .iter()
+                .map(|event| {
+                    let actor = match (&event.actor.name, event.actor.key_id) {
+                        (Some(name), Some(id)) => format!("{} {} (key {})", event.actor.kind, name, id),
+                        (Some(name), None) => format!("{} {}", event.actor.kind, name),
+                        (None, _) => event.actor.kind.clone(),
+                    };
+                    vec![
+                        event.id.to_string(),
+                        event.workspace_id.map(|id| id.to_string()).unwrap_or_else(|| "-".to_string()),
+                        event.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                        actor,
+                        event.actor.ip.map(|ip| ip.to_string()).unwrap_or_else(|| "-".to_string()),
+                        event.action.clone(),
+                        event.target.clone(),
+                        changed_fields(&event.diff),
+                    ]
+                })
+                .collect();
+            print_table(&["ID", "WORKSPACE", "TIME", "ACTOR", "IP", "ACTION", "TARGET", "CHANGED"], &rows);
+        }
+    }
+}
+
+/// The names of the fields a diff covers; the values are shown with `--output json`.
+fn changed_fields(diff: &Value) -> String {
+    match diff.as_object() {
+        Some(fields) if !fields.is_empty() => fields.keys().cloned().collect::<Vec<_>>().join(", "),
+        _ => "-".to_string(),
+    }
+}
@@ -0,0 +1,41 @@
+use super::domains::domain_id;
+use super::CliError;
+use crate::config::db::{configure_db, DbError};
+use crate::config::redis::{invalidate_link, purge_links};
+use clap::Subcommand;
+use deadpool_redis::Pool as RedisPool;
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommand {
+    /// Drop the cached destination of a short code, or of every code on every domain with `all`
+    Purge {
+        target: String,
+        /// Branded domain of the short code; the default domain if omitted
+        #[arg(long)]
+        domain: Option<String>,
+    },
+}
+
+pub async fn run(command: CacheCommand, redis_pool: RedisPool) -> Result<(), CliError> {
+    let mut conn = redis_pool.get().await.map_err(|e| CliError::Redis(e.to_string()))?;
+
+    match command {
+        CacheCommand::Purge { target, .. } if target == "all" => {
+            let purged = purge_links(&mut conn).await.map_err(|e| CliError::Redis(e.to_string()))?;
+            println!("Purged {} cached links", purged);
+        }
+        CacheCommand::Purge { target, domain: None } => {
+            invalidate_link(&mut conn, None, &target).await.map_err(|e| CliError::Redis(e.to_string()))?;
+            println!("Purged {}", target);
+        }
+        CacheCommand::Purge { target, domain: Some(host) } => {
+            // Only a branded domain needs the database, to find its ID
+            let db_pool = configure_db().await;
+            let client = db_pool.get().await.map_err(|_| DbError::DatabaseError)?;
+            let domain_id = domain_id(&client, Some(&host)).await?;
+            invalidate_link(&mut conn, domain_id, &target).await.map_err(|e| CliError::Redis(e.to_string()))?;
+            println!("Purged {} on {}", target, host);
+        }
+    }
+    Ok(())
+}
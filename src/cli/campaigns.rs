@@ -0,0 +1,109 @@
+use super::output::{format_time, print_json, print_table, OutputFormat};
+use super::workspaces::optional_workspace_id;
+use super::CliError;
+use crate::config::campaigns::{delete_campaign, list_campaigns, save_campaign};
+use crate::config::db::DbError;
+use crate::models::{Campaign, UtmParams};
+use crate::utils::validate::{validate_campaign_name, validate_utm};
+use clap::{Args, Subcommand};
+use deadpool_postgres::Pool;
+
+#[derive(Debug, Subcommand)]
+pub enum CampaignsCommand {
+    /// Create a campaign preset, or replace the parameters of an existing one
+    Save {
+        name: String,
+        #[command(flatten)]
+        utm: UtmArgs,
+        /// Workspace the preset belongs to; the presets of links without a workspace if omitted
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// List campaign presets
+    List {
+        /// List the presets of this workspace instead of those of links without a workspace
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Delete a campaign preset; links created with it keep their parameters
+    Delete {
+        name: String,
+        /// Workspace the preset belongs to; the presets of links without a workspace if omitted
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+}
+
+/// UTM parameters given on the command line.
+#[derive(Debug, Args)]
+pub struct UtmArgs {
+    /// Where the traffic comes from, e.g. `newsletter`
+    #[arg(long)]
+    utm_source: Option<String>,
+    /// Marketing medium, e.g. `email`
+    #[arg(long)]
+    utm_medium: Option<String>,
+    /// Campaign name, e.g. `spring_sale`
+    #[arg(long)]
+    utm_campaign: Option<String>,
+    /// Paid search keyword
+    #[arg(long)]
+    utm_term: Option<String>,
+    /// What was clicked, to tell links in the same message apart
+    #[arg(long)]
+    utm_content: Option<String>,
+}
+
+impl From<UtmArgs> for UtmParams {
+    fn from(args: UtmArgs) -> Self {
+        UtmParams {
+            utm_source: args.utm_source,
+            utm_medium: args.utm_medium,
+            utm_campaign: args.utm_campaign,
+            utm_term: args.utm_term,
+            utm_content: args.utm_content,
+        }
+    }
+}
+
+pub async fn run(command: CampaignsCommand, db_pool: Pool, output: OutputFormat) -> Result<(), CliError> {
+    let client = db_pool.get().await.map_err(|_| DbError::DatabaseError)?;
+
+    match command {
+        CampaignsCommand::Save { name, utm, workspace } => {
+            validate_campaign_name(&name)?;
+            let utm = validate_utm(utm.into())?;
+            let workspace_id = optional_workspace_id(&client, workspace).await?;
+            let campaign = save_campaign(&client, workspace_id, &name, &utm).await?;
+            print_campaigns(std::slice::from_ref(&campaign), output);
+        }
+        CampaignsCommand::List { workspace } => {
+            let workspace_id = optional_workspace_id(&client, workspace).await?;
+            print_campaigns(&list_campaigns(&client, workspace_id).await?, output);
+        }
+        CampaignsCommand::Delete { name, workspace } => {
+            let workspace_id = optional_workspace_id(&client, workspace).await?;
+            delete_campaign(&client, workspace_id, &name)
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Campaign {}", name)))?;
+            println!("Deleted {}", name);
+        }
+    }
+    Ok(())
+}
+
+fn print_campaigns(campaigns: &[Campaign], output: OutputFormat) {
+    match output {
+        OutputFormat::Json => print_json(campaigns),
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = campaigns
+                .iter()
+                .map(|campaign| {
+                    let utm: Vec<String> = campaign.utm.pairs().map(|(name, value)| format!("{}={}", name, value)).collect();
+                    vec![campaign.name.clone(), utm.join(" "), format_time(campaign.created_at)]
+                })
+                .collect();
+            print_table(&["NAME", "PARAMETERS", "CREATED"], &rows);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use super::output::{format_time, print_json, print_table, OutputFormat};
+use super::CliError;
+use crate::config::db::DbError;
+use crate::config::domains::{delete_domain, get_domain, list_domains, save_domain};
+use crate::models::Domain;
+use crate::utils::domain::{is_valid_host, normalize_host};
+use crate::utils::validate::validate_link;
+use clap::Subcommand;
+use deadpool_postgres::Pool;
+use tokio_postgres::Client;
+
+#[derive(Debug, Subcommand)]
+pub enum DomainsCommand {
+    /// Add a branded domain, or replace the settings of an existing one
+    Save {
+        /// Host name, with the port if it is not 80 or 443
+        host: String,
+        /// Where `/` on this domain redirects; shows the index page if omitted
+        #[arg(long)]
+        root_url: Option<String>,
+        /// Where unknown short codes on this domain redirect; shows the 404 page if omitted
+        #[arg(long)]
+        not_found_url: Option<String>,
+        /// Show visitors the preview page before sending them on, for every link on this domain
+        #[arg(long)]
+        interstitial: bool,
+    },
+    /// List branded domains
+    List,
+    /// Remove a branded domain; domains that still have links cannot be removed
+    Remove { host: String },
+}
+
+pub async fn run(command: DomainsCommand, db_pool: Pool, output: OutputFormat) -> Result<(), CliError> {
+    let client = db_pool.get().await.map_err(|_| DbError::DatabaseError)?;
+
+    match command {
+        DomainsCommand::Save { host, root_url, not_found_url, interstitial } => {
+            let host = normalize_host(&host);
+            if !is_valid_host(&host) {
+                return Err(CliError::InvalidDomain(host));
+            }
+            let root_url = root_url.map(validate_url).transpose()?;
+            let not_found_url = not_found_url.map(validate_url).transpose()?;
+            let domain = save_domain(&client, &host, root_url.as_deref(), not_found_url.as_deref(), interstitial).await?;
+            print_domains(std::slice::from_ref(&domain), output);
+        }
+        DomainsCommand::List => print_domains(&list_domains(&client).await?, output),
+        DomainsCommand::Remove { host } => {
+            delete_domain(&client, &normalize_host(&host))
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Domain {}", host)))?;
+            println!("Removed {}", host);
+        }
+    }
+    Ok(())
+}
+
+/// The ID of the domain with this host, `None` for the default domain.
+pub(super) async fn domain_id(client: &Client, host: Option<&str>) -> Result<Option<i32>, CliError> {
+    let Some(host) = host else {
+        return Ok(None);
+    };
+    let domain = get_domain(client, &normalize_host(host))
+        .await?
+        .ok_or_else(|| CliError::NotFound(format!("Domain {}", host)))?;
+    Ok(Some(domain.id))
+}
+
+fn validate_url(url: String) -> Result<String, CliError> {
+    validate_link(url.clone()).map_err(|_| CliError::InvalidLink(url))
+}
+
+fn print_domains(domains: &[Domain], output: OutputFormat) {
+    match output {
+        OutputFormat::Json => print_json(domains),
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = domains
+                .iter()
+                .map(|domain| {
+                    vec![
+                        domain.host.clone(),
+                        domain.root_url.clone().unwrap_or_default(),
+                        domain.not_found_url.clone().unwrap_or_default(),
+                        if domain.interstitial { "yes" } else { "no" }.to_string(),
+                        format_time(domain.created_at),
+                    ]
+                })
+                .collect();
+            print_table(&["HOST", "ROOT URL", "404 URL", "PREVIEW", "CREATED"], &rows);
+        }
+    }
+}
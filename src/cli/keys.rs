@@ -0,0 +1,120 @@
+use super::output::{format_time, print_json, print_table, OutputFormat};
+use super::workspaces::workspace_id;
+use super::CliError;
+use crate::config::api_keys::{create_api_key, get_api_key, list_api_keys, revoke_api_key, set_reuse_existing};
+use crate::config::db::DbError;
+use crate::models::{ApiKey, AuditAction, Scope};
+use crate::utils::audit::{cli_actor, key_event, record};
+use clap::Subcommand;
+use deadpool_postgres::Pool;
+
+#[derive(Debug, Subcommand)]
+pub enum KeysCommand {
+    /// Create an API key for a workspace; the secret is printed once
+    Create {
+        name: String,
+        /// Workspace the key belongs to
+        #[arg(long)]
+        workspace: String,
+        /// Permission granted to the key; repeat for several, all of them if omitted
+        #[arg(long = "scope", value_enum)]
+        scopes: Vec<Scope>,
+    },
+    /// List API keys
+    List {
+        /// Only list the keys of this workspace
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Revoke an API key
+    Revoke { id: i32 },
+    /// Return existing links when this key shortens a URL it already shortened
+    Reuse {
+        id: i32,
+        #[arg(action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+}
+
+pub async fn run(command: KeysCommand, db_pool: Pool, output: OutputFormat) -> Result<(), CliError> {
+    let mut client = db_pool.get().await.map_err(|_| DbError::DatabaseError)?;
+
+    match command {
+        KeysCommand::Create { name, workspace, scopes } => {
+            let workspace_id = workspace_id(&client, &workspace).await?;
+            let scopes = if scopes.is_empty() { Scope::ALL.to_vec() } else { scopes };
+            let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+            let (key, secret) = create_api_key(&tx, workspace_id, None, &name, &scopes).await?;
+            record(&tx, &cli_actor(), &[key_event(AuditAction::KeyCreate, None, Some(&key))]).await?;
+            tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+            match output {
+                OutputFormat::Json => print_json(&serde_json::json!({ "key": key, "secret": secret })),
+                OutputFormat::Table => {
+                    print_key(&key, output);
+                    println!("\nSecret (shown only once): {}", secret);
+                }
+            }
+        }
+        KeysCommand::List { workspace } => {
+            let workspace_id = match workspace {
+                Some(name) => Some(workspace_id(&client, &name).await?),
+                None => None,
+            };
+            print_keys(&list_api_keys(&client, workspace_id).await?, output);
+        }
+        KeysCommand::Revoke { id } => {
+            let before = get_api_key(&client, None, id).await?;
+            let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+            let key = revoke_api_key(&tx, None, id)
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Active API key {}", id)))?;
+            let event = key_event(AuditAction::KeyRevoke, before.as_ref(), Some(&key));
+            record(&tx, &cli_actor(), &[event]).await?;
+            tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+            print_key(&key, output);
+        }
+        KeysCommand::Reuse { id, enabled } => {
+            let before = get_api_key(&client, None, id).await?;
+            let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+            let key = set_reuse_existing(&tx, id, enabled)
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Active API key {}", id)))?;
+            let event = key_event(AuditAction::KeyUpdate, before.as_ref(), Some(&key));
+            record(&tx, &cli_actor(), &[event]).await?;
+            tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+            print_key(&key, output);
+        }
+    }
+    Ok(())
+}
+
+fn print_key(key: &ApiKey, output: OutputFormat) {
+    match output {
+        OutputFormat::Json => print_json(key),
+        OutputFormat::Table => print_keys(std::slice::from_ref(key), output),
+    }
+}
+
+fn print_keys(keys: &[ApiKey], output: OutputFormat) {
+    match output {
+        OutputFormat::Json => print_json(keys),
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = keys
+                .iter()
+                .map(|key| {
+                    vec![
+                        key.id.to_string(),
+                        key.workspace_id.to_string(),
+                        key.name.clone(),
+                        format!("{}…", key.key_prefix),
+                        key.scopes.iter().map(Scope::as_str).collect::<Vec<_>>().join(","),
+                        format_time(key.created_at),
+                        format_time(key.revoked_at),
+                        if key.reuse_existing { "yes" } else { "no" }.to_string(),
+                    ]
+                })
+                .collect();
+            print_table(&["ID", "WORKSPACE", "NAME", "PREFIX", "SCOPES", "CREATED", "REVOKED", "REUSE"], &rows);
+        }
+    }
+}
@@ -0,0 +1,495 @@
+use super::campaigns::UtmArgs;
+use super::domains::domain_id;
+use super::output::{format_time, print_json, print_table, OutputFormat};
+use super::workspaces::optional_workspace_id;
+use super::CliError;
+use crate::config::clicks::stream_clicks;
+use crate::config::db::{
+    delete_link, get_link, insert_shortlink, search_links, set_link_interstitial, stream_links, update_link_labels,
+    update_link_url, DbError, LinkFilter,
+};
+use crate::config::redis::invalidate_link;
+use crate::config::revisions::{get_link_revision, list_link_revisions, restore_link_revision};
+use crate::models::{
+    AuditAction, LabelsRequest, LanguageRule, Link, LinkLabels, LinkRevision, PassthroughMode, ShortenRequest,
+    TargetingRule, Variant,
+};
+use crate::utils::audit::{cli_actor, link_change_events, link_event, record};
+use crate::utils::export::{click_lines, link_lines};
+use crate::utils::import::{import_records, parse_timestamp, read_records, ConflictPolicy, ImportError, ImportSummary, TransferFormat};
+use crate::utils::search::{decode_cursor, link_limit, link_page, DEFAULT_LINK_LIMIT};
+use crate::utils::short_code::{generate_short_code, short_url};
+use crate::utils::validate::{prepare_link, validate_labels, validate_link};
+use chrono::{DateTime, Utc};
+use clap::{ArgGroup, Args, Subcommand};
+use deadpool_postgres::Pool;
+use deadpool_redis::Pool as RedisPool;
+use futures_util::{Stream, StreamExt};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::pin::Pin;
+
+#[derive(Debug, Subcommand)]
+pub enum LinksCommand {
+    /// Shorten a URL
+    Create(Box<CreateArgs>),
+    /// Show a link
+    Show {
+        code: String,
+        /// Branded domain of the link; the default domain if omitted
+        #[arg(long)]
+        domain: Option<String>,
+    },
+    /// List links newest first, or search them most relevant first
+    List(ListArgs),
+    /// Move a link to the trash
+    Delete {
+        code: String,
+        /// Branded domain of the link; the default domain if omitted
+        #[arg(long)]
+        domain: Option<String>,
+    },
+    /// Change the destination, labels or preview page of a link
+    #[command(group(ArgGroup::new("changes").required(true).multiple(true)
+        .args(["url", "title", "notes", "folder", "tags", "clear_tags", "interstitial"])))]
+    Update {
+        code: String,
+        #[arg(long)]
+        url: Option<String>,
+        /// New title; an empty string removes it
+        #[arg(long)]
+        title: Option<String>,
+        /// New notes; an empty string removes them
+        #[arg(long)]
+        notes: Option<String>,
+        /// New folder; an empty string removes the link from its folder
+        #[arg(long)]
+        folder: Option<String>,
+        /// Replace the tags; may be repeated
+        #[arg(long = "tag", conflicts_with = "clear_tags")]
+        tags: Vec<String>,
+        /// Remove every tag
+        #[arg(long)]
+        clear_tags: bool,
+        /// Whether visitors see the preview page before being sent on
+        #[arg(long)]
+        interstitial: Option<bool>,
+        /// Branded domain of the link; the default domain if omitted
+        #[arg(long)]
+        domain: Option<String>,
+    },
+    /// List the revisions of a link, newest first
+    Revisions {
+        code: String,
+        /// Branded domain of the link; the default domain if omitted
+        #[arg(long)]
+        domain: Option<String>,
+    },
+    /// Put a link back the way one of its revisions recorded it
+    Restore {
+        code: String,
+        revision: i32,
+        /// Branded domain of the link; the default domain if omitted
+        #[arg(long)]
+        domain: Option<String>,
+    },
+    /// Import links from a CSV or JSONL file, keeping their short codes
+    Import {
+        file: PathBuf,
+        /// Defaults to the file extension, or CSV
+        #[arg(long, value_enum)]
+        format: Option<TransferFormat>,
+        #[arg(long, value_enum, default_value_t = ConflictPolicy::Skip)]
+        on_conflict: ConflictPolicy,
+        /// Workspace the links belong to; only its links are overwritten
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+    /// Export every link to stdout
+    Export {
+        #[arg(long, value_enum, default_value_t = TransferFormat::Csv)]
+        format: TransferFormat,
+        /// Export the recorded clicks instead of the links
+        #[arg(long)]
+        clicks: bool,
+        /// Only export the links of this workspace
+        #[arg(long)]
+        workspace: Option<String>,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct ListArgs {
+    /// Words to look for in the title, notes and destination
+    #[arg(long)]
+    query: Option<String>,
+    /// Only links with this tag; may be repeated to require several
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    /// Only links in this folder or its subfolders
+    #[arg(long)]
+    folder: Option<String>,
+    /// Only links on this branded domain
+    #[arg(long)]
+    domain: Option<String>,
+    /// Only links created with this API key
+    #[arg(long)]
+    owner: Option<i32>,
+    /// Only links created at or after this time (RFC 3339)
+    #[arg(long, value_parser = parse_time)]
+    created_after: Option<DateTime<Utc>>,
+    /// Only links created before this time (RFC 3339)
+    #[arg(long, value_parser = parse_time)]
+    created_before: Option<DateTime<Utc>>,
+    /// Only links of this workspace
+    #[arg(long)]
+    workspace: Option<String>,
+    /// Cursor printed with the previous page
+    #[arg(long)]
+    cursor: Option<String>,
+    #[arg(long, default_value_t = DEFAULT_LINK_LIMIT)]
+    limit: i64,
+}
+
+#[derive(Debug, Args)]
+pub struct CreateArgs {
+    url: String,
+    /// Require visitors to enter this password
+    #[arg(long)]
+    password: Option<String>,
+    /// The link works once and is then used up
+    #[arg(long)]
+    single_use: bool,
+    /// Show visitors the preview page before sending them on
+    #[arg(long)]
+    interstitial: bool,
+    /// The link does not resolve before this time (RFC 3339)
+    #[arg(long, value_parser = parse_time)]
+    active_from: Option<DateTime<Utc>>,
+    /// The link stops resolving at this time (RFC 3339)
+    #[arg(long, value_parser = parse_time)]
+    active_until: Option<DateTime<Utc>>,
+    /// Where to send visitors before the link is active
+    #[arg(long)]
+    upcoming_url: Option<String>,
+    /// Where to send visitors after the link has ended
+    #[arg(long)]
+    ended_url: Option<String>,
+    /// Where to send visitors while the destination fails health checks
+    #[arg(long)]
+    fallback_url: Option<String>,
+    /// Send matching visitors elsewhere, e.g. `os=ios,device=mobile=>https://apps.apple.com/app/id1`;
+    /// may be repeated, the first matching rule wins
+    #[arg(long = "target", value_name = "CONDITIONS=>URL")]
+    targeting_rules: Vec<TargetingRule>,
+    /// Make the link available in a language, e.g. `fr` or `fr=>https://example.fr`; may be repeated
+    #[arg(long = "language", value_name = "LANG[=>URL]")]
+    language_rules: Vec<LanguageRule>,
+    /// Language used when none of the visitor's languages is available
+    #[arg(long)]
+    default_language: Option<String>,
+    /// Split the traffic, e.g. `a:70=>https://example.com/a`; may be repeated
+    #[arg(long = "variant", value_name = "NAME:WEIGHT=>URL")]
+    variants: Vec<Variant>,
+    /// Forward the query string and/or the path after the short code to the destination
+    #[arg(long, value_enum, default_value_t = PassthroughMode::None)]
+    passthrough: PassthroughMode,
+    #[command(flatten)]
+    utm: UtmArgs,
+    /// Campaign preset supplying the UTM parameters not given explicitly
+    #[arg(long)]
+    campaign: Option<String>,
+    /// Tag the link; may be repeated
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    #[arg(long)]
+    title: Option<String>,
+    #[arg(long)]
+    notes: Option<String>,
+    /// Folder to file the link in, e.g. `marketing/spring`
+    #[arg(long)]
+    folder: Option<String>,
+    /// Branded domain to create the link on; the default domain if omitted
+    #[arg(long)]
+    domain: Option<String>,
+    /// Workspace the link belongs to; no workspace manages it if omitted
+    #[arg(long)]
+    workspace: Option<String>,
+}
+
+impl From<CreateArgs> for ShortenRequest {
+    fn from(args: CreateArgs) -> Self {
+        ShortenRequest {
+            url: args.url,
+            password: args.password,
+            single_use: args.single_use,
+            interstitial: args.interstitial,
+            active_from: args.active_from,
+            active_until: args.active_until,
+            upcoming_url: args.upcoming_url,
+            ended_url: args.ended_url,
+            fallback_url: args.fallback_url,
+            targeting_rules: args.targeting_rules,
+            language_rules: args.language_rules,
+            default_language: args.default_language,
+            variants: args.variants,
+            passthrough: args.passthrough,
+            utm: args.utm.into(),
+            campaign: args.campaign,
+            tags: args.tags,
+            title: args.title,
+            notes: args.notes,
+            folder: args.folder,
+            domain: args.domain,
+        }
+    }
+}
+
+pub(super) fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_timestamp(value).ok_or_else(|| format!("invalid timestamp {:?}", value))
+}
+
+pub async fn run(command: LinksCommand, db_pool: Pool, redis_pool: RedisPool, output: OutputFormat) -> Result<(), CliError> {
+    let mut client = db_pool.get().await.map_err(|_| DbError::DatabaseError)?;
+
+    match command {
+        LinksCommand::Create(mut args) => {
+            let workspace_id = optional_workspace_id(&client, args.workspace.take()).await?;
+            let link = prepare_link((*args).into(), workspace_id, &client).await?;
+            let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+            let link = insert_shortlink(&tx, &generate_short_code(), &link, None, workspace_id).await?;
+            record(&tx, &cli_actor(), &[link_event(AuditAction::LinkCreate, None, Some(&link))]).await?;
+            tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+            print_link(&link, output);
+        }
+        LinksCommand::Show { code, domain } => {
+            let domain_id = domain_id(&client, domain.as_deref()).await?;
+            let link = get_link(&client, domain_id, &code)
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Link {}", code)))?;
+            print_link(&link, output);
+        }
+        LinksCommand::List(args) => {
+            let after = args
+                .cursor
+                .as_deref()
+                .map(|cursor| decode_cursor(cursor, args.query.is_some()).map_err(|_| CliError::InvalidCursor(cursor.to_string())))
+                .transpose()?;
+            let domain_id = domain_id(&client, args.domain.as_deref()).await?;
+            let workspace_id = optional_workspace_id(&client, args.workspace).await?;
+            let limit = link_limit(Some(args.limit));
+            let filter = LinkFilter {
+                query: args.query.as_deref(),
+                tags: &args.tags,
+                folder: args.folder.as_deref().map(|folder| folder.trim_matches('/')),
+                domain_id,
+                owner_key_id: args.owner,
+                created_after: args.created_after,
+                created_before: args.created_before,
+                after,
+            };
+            let links = search_links(&client, workspace_id, &filter, limit + 1).await?;
+            let page = link_page(links, limit);
+            match output {
+                OutputFormat::Json => print_json(&page),
+                OutputFormat::Table => {
+                    let links: Vec<Link> = page.links.into_iter().map(|listed| listed.link).collect();
+                    print_links(&links, output);
+                    if let Some(cursor) = page.next_cursor {
+                        println!("\nMore links: --cursor {}", cursor);
+                    }
+                }
+            }
+        }
+        LinksCommand::Delete { code, domain } => {
+            let domain_id = domain_id(&client, domain.as_deref()).await?;
+            let before = get_link(&client, domain_id, &code).await?;
+            let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+            let link = delete_link(&tx, domain_id, &code)
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Link {}", code)))?;
+            let event = link_event(AuditAction::LinkDelete, before.as_ref(), Some(&link));
+            record(&tx, &cli_actor(), &[event]).await?;
+            tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+            purge_cached(&redis_pool, domain_id, &code).await?;
+            println!("Moved {} to the trash", code);
+        }
+        LinksCommand::Update { code, url, title, notes, folder, tags, clear_tags, interstitial, domain } => {
+            let url = url
+                .map(|url| validate_link(url.clone()).map_err(|_| CliError::InvalidLink(url)))
+                .transpose()?;
+            let domain_id = domain_id(&client, domain.as_deref()).await?;
+            let before = get_link(&client, domain_id, &code)
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Link {}", code)))?;
+            let changes = LabelsRequest {
+                tags: (clear_tags || !tags.is_empty()).then_some(tags),
+                title,
+                notes,
+                folder,
+            };
+            let labels = validate_labels(changes.apply(LinkLabels::from(&before)))?;
+            let mut link = before.clone();
+            let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+            if let Some(url) = url {
+                link = update_link_url(&tx, domain_id, &code, &url)
+                    .await?
+                    .ok_or_else(|| CliError::NotFound(format!("Link {}", code)))?;
+            }
+            if labels != LinkLabels::from(&link) {
+                link = update_link_labels(&tx, domain_id, &code, &labels)
+                    .await?
+                    .ok_or_else(|| CliError::NotFound(format!("Link {}", code)))?;
+            }
+            if let Some(interstitial) = interstitial.filter(|&interstitial| interstitial != link.interstitial) {
+                link = set_link_interstitial(&tx, domain_id, &code, interstitial)
+                    .await?
+                    .ok_or_else(|| CliError::NotFound(format!("Link {}", code)))?;
+            }
+            let events = link_change_events(AuditAction::LinkUpdate, &before, &link);
+            record(&tx, &cli_actor(), &events).await?;
+            tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+            purge_cached(&redis_pool, domain_id, &code).await?;
+            print_link(&link, output);
+        }
+        LinksCommand::Revisions { code, domain } => {
+            let domain_id = domain_id(&client, domain.as_deref()).await?;
+            let link = get_link(&client, domain_id, &code)
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Link {}", code)))?;
+            print_revisions(&list_link_revisions(&client, link.id).await?, output);
+        }
+        LinksCommand::Restore { code, revision, domain } => {
+            let domain_id = domain_id(&client, domain.as_deref()).await?;
+            let before = get_link(&client, domain_id, &code)
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Link {}", code)))?;
+            let revision = get_link_revision(&client, before.id, revision)
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Revision {} of link {}", revision, code)))?;
+            let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+            let link = restore_link_revision(&tx, before.id, &revision)
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Link {}", code)))?;
+            let events = link_change_events(AuditAction::LinkRestore, &before, &link);
+            record(&tx, &cli_actor(), &events).await?;
+            tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+            purge_cached(&redis_pool, domain_id, &code).await?;
+            print_link(&link, output);
+        }
+        LinksCommand::Import { file, format, on_conflict, workspace } => {
+            let workspace_id = optional_workspace_id(&client, workspace).await?;
+            let format = format.unwrap_or_else(|| match file.extension().and_then(|e| e.to_str()) {
+                Some("jsonl") | Some("ndjson") => TransferFormat::Jsonl,
+                _ => TransferFormat::Csv,
+            });
+            let reader = File::open(&file).map_err(|e| CliError::Io(e.to_string()))?;
+            let summary = import_records(&mut client, read_records(reader, format), workspace_id, on_conflict, &cli_actor())
+                .await
+                .map_err(|e| match e {
+                    ImportError::Database(e) => CliError::Database(e),
+                    e @ ImportError::Conflict { .. } => CliError::Import(e.to_string()),
+                })?;
+            for (domain_id, code) in &summary.updated_codes {
+                purge_cached(&redis_pool, *domain_id, code).await?;
+            }
+            print_import_summary(&summary, output);
+        }
+        LinksCommand::Export { format, clicks, workspace } => {
+            let workspace_id = optional_workspace_id(&client, workspace).await?;
+            let mut lines: Pin<Box<dyn Stream<Item = Result<String, DbError>>>> = if clicks {
+                Box::pin(click_lines(stream_clicks(&client, workspace_id).await?, format))
+            } else {
+                Box::pin(link_lines(stream_links(&client, workspace_id).await?, format))
+            };
+            let mut out = BufWriter::new(io::stdout().lock());
+            while let Some(line) = lines.next().await {
+                out.write_all(line?.as_bytes()).map_err(|e| CliError::Io(e.to_string()))?;
+            }
+            out.flush().map_err(|e| CliError::Io(e.to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+fn print_import_summary(summary: &ImportSummary, output: OutputFormat) {
+    match output {
+        OutputFormat::Json => print_json(summary),
+        OutputFormat::Table => {
+            print_table(
+                &["INSERTED", "UPDATED", "SKIPPED", "FAILED"],
+                &[vec![
+                    summary.inserted.to_string(),
+                    summary.updated.to_string(),
+                    summary.skipped.to_string(),
+                    summary.failed.to_string(),
+                ]],
+            );
+            if !summary.errors.is_empty() {
+                println!();
+                let rows: Vec<Vec<String>> = summary
+                    .errors
+                    .iter()
+                    .map(|e| vec![e.line.to_string(), e.error.clone()])
+                    .collect();
+                print_table(&["LINE", "ERROR"], &rows);
+            }
+        }
+    }
+}
+
+/// Drop the cached destination so the change takes effect immediately.
+async fn purge_cached(redis_pool: &RedisPool, domain_id: Option<i32>, code: &str) -> Result<(), CliError> {
+    let mut conn = redis_pool.get().await.map_err(|e| CliError::Redis(e.to_string()))?;
+    invalidate_link(&mut conn, domain_id, code).await.map_err(|e| CliError::Redis(e.to_string()))
+}
+
+fn print_link(link: &Link, output: OutputFormat) {
+    match output {
+        OutputFormat::Json => print_json(link),
+        OutputFormat::Table => print_links(std::slice::from_ref(link), output),
+    }
+}
+
+fn print_links(links: &[Link], output: OutputFormat) {
+    match output {
+        OutputFormat::Json => print_json(links),
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = links
+                .iter()
+                .map(|link| {
+                    vec![
+                        link.short_code.clone(),
+                        short_url(link.domain.as_deref(), &link.short_code),
+                        link.original_url.clone(),
+                        link.title.clone().unwrap_or_else(|| "-".to_string()),
+                        if link.tags.is_empty() { "-".to_string() } else { link.tags.join(",") },
+                        format_time(link.created_at),
+                    ]
+                })
+                .collect();
+            print_table(&["CODE", "SHORT URL", "DESTINATION", "TITLE", "TAGS", "CREATED"], &rows);
+        }
+    }
+}
+
+fn print_revisions(revisions: &[LinkRevision], output: OutputFormat) {
+    match output {
+        OutputFormat::Json => print_json(revisions),
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = revisions
+                .iter()
+                .map(|revision| {
+                    vec![
+                        revision.revision.to_string(),
+                        revision.original_url.clone(),
+                        revision.active_until.map(|t| t.to_rfc3339()).unwrap_or_else(|| "-".to_string()),
+                        revision.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    ]
+                })
+                .collect();
+            print_table(&["REVISION", "DESTINATION", "ACTIVE UNTIL", "RECORDED"], &rows);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+pub mod audit;
+pub mod cache;
+pub mod campaigns;
+pub mod domains;
+pub mod keys;
+pub mod links;
+pub mod output;
+pub mod trash;
+pub mod workspaces;
+
+use crate::config::db::{configure_db, DbError};
+use crate::config::migrations::run_migrations;
+use crate::config::redis::configure_redis;
+use crate::utils::validate::LinkError;
+use crate::utils::workspace::WorkspaceError;
+use clap::{Args, Parser, Subcommand};
+use output::OutputFormat;
+use thiserror::Error;
+
+/// Command-line interface of the shortener.
+///
+/// Without a subcommand the server is started, so `time_to_rust --port 3030` keeps working.
+#[derive(Debug, Parser)]
+#[command(name = "time_to_rust", about = "URL shortener server and admin tool", args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub serve: ServeArgs,
+
+    /// Output format of admin commands
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    pub output: OutputFormat,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the HTTP server
+    Serve(ServeArgs),
+    /// Apply pending database migrations
+    Migrate,
+    /// Manage short links
+    Links {
+        #[command(subcommand)]
+        command: links::LinksCommand,
+    },
+    /// Manage the Redis cache
+    Cache {
+        #[command(subcommand)]
+        command: cache::CacheCommand,
+    },
+    /// Manage API keys
+    Keys {
+        #[command(subcommand)]
+        command: keys::KeysCommand,
+    },
+    /// Manage UTM campaign presets
+    Campaigns {
+        #[command(subcommand)]
+        command: campaigns::CampaignsCommand,
+    },
+    /// Manage branded short domains
+    Domains {
+        #[command(subcommand)]
+        command: domains::DomainsCommand,
+    },
+    /// Manage workspaces and their members
+    Workspaces {
+        #[command(subcommand)]
+        command: workspaces::WorkspacesCommand,
+    },
+    /// Manage deleted links
+    Trash {
+        #[command(subcommand)]
+        command: trash::TrashCommand,
+    },
+    /// Query the audit log
+    Audit {
+        #[command(subcommand)]
+        command: audit::AuditCommand,
+    },
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ServeArgs {
+    /// Port to listen on
+    #[arg(long, env = "PORT", default_value_t = 3030)]
+    pub port: u16,
+}
+
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("Database error")]
+    Database(#[from] DbError),
+    #[error("Redis error: {0}")]
+    Redis(String),
+    #[error("Invalid link: {0}")]
+    InvalidLink(String),
+    #[error("Invalid domain: {0}")]
+    InvalidDomain(String),
+    #[error(transparent)]
+    Link(#[from] LinkError),
+    #[error(transparent)]
+    Workspace(#[from] WorkspaceError),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("Invalid cursor: {0}")]
+    InvalidCursor(String),
+    #[error("I/O error: {0}")]
+    Io(String),
+    #[error("Import aborted: {0}")]
+    Import(String),
+}
+
+/// Run an admin command (everything except `serve`).
+pub async fn run(command: Command, output: OutputFormat) -> Result<(), CliError> {
+    match command {
+        Command::Serve(_) => unreachable!("serve is handled by main"),
+        Command::Migrate => {
+            let pool = configure_db().await;
+            let mut client = pool.get().await.map_err(|_| DbError::DatabaseError)?;
+            let applied = run_migrations(&mut client).await?;
+            if applied.is_empty() {
+                println!("Database is up to date.");
+            }
+            for name in applied {
+                println!("Applied {}", name);
+            }
+            Ok(())
+        }
+        Command::Links { command } => {
+            links::run(command, configure_db().await, configure_redis().await, output).await
+        }
+        Command::Cache { command } => cache::run(command, configure_redis().await).await,
+        Command::Keys { command } => keys::run(command, configure_db().await, output).await,
+        Command::Campaigns { command } => campaigns::run(command, configure_db().await, output).await,
+        Command::Domains { command } => domains::run(command, configure_db().await, output).await,
+        Command::Workspaces { command } => workspaces::run(command, configure_db().await, output).await,
+        Command::Trash { command } => {
+            trash::run(command, configure_db().await, configure_redis().await, output).await
+        }
+        Command::Audit { command } => audit::run(command, configure_db().await, output).await,
+    }
+}
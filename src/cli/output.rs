@@ -0,0 +1,44 @@
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// How command results are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+/// Print rows as a left-aligned table with a header line.
+pub fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+    print_row(&header, &widths);
+    for row in rows {
+        print_row(row, &widths);
+    }
+}
+
+fn print_row(cells: &[String], widths: &[usize]) {
+    let line: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+        .collect();
+    println!("{}", line.join("  ").trim_end());
+}
+
+/// Print a value as pretty JSON.
+pub fn print_json<T: Serialize + ?Sized>(value: &T) {
+    println!("{}", serde_json::to_string_pretty(value).expect("value serializes to JSON"));
+}
+
+/// Format an optional timestamp for table output.
+pub fn format_time(time: Option<chrono::NaiveDateTime>) -> String {
+    time.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "-".to_string())
+}
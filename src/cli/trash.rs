@@ -0,0 +1,97 @@
+use super::domains::domain_id;
+use super::output::{print_json, print_table, OutputFormat};
+use super::workspaces::workspace_id;
+use super::CliError;
+use crate::config::db::{get_deleted_link, list_deleted_links, undelete_link, DbError};
+use crate::config::redis::invalidate_link;
+use crate::models::{AuditAction, TrashedLink};
+use crate::utils::audit::{cli_actor, link_event, record};
+use crate::utils::trash::{purge_trash, trashed};
+use clap::Subcommand;
+use deadpool_postgres::Pool;
+use deadpool_redis::Pool as RedisPool;
+
+#[derive(Debug, Subcommand)]
+pub enum TrashCommand {
+    /// List deleted links, most recently deleted first
+    List {
+        /// Only list the links of this workspace
+        #[arg(long)]
+        workspace: Option<String>,
+        #[arg(long, default_value_t = 50)]
+        limit: i64,
+        #[arg(long, default_value_t = 0)]
+        offset: i64,
+    },
+    /// Take a deleted link back out of the trash
+    Restore {
+        code: String,
+        /// Branded domain of the link; the default domain if omitted
+        #[arg(long)]
+        domain: Option<String>,
+    },
+    /// Delete for good the links deleted more than `TRASH_RETENTION_DAYS` ago, releasing their short codes
+    Purge,
+}
+
+pub async fn run(command: TrashCommand, db_pool: Pool, redis_pool: RedisPool, output: OutputFormat) -> Result<(), CliError> {
+    let mut client = db_pool.get().await.map_err(|_| DbError::DatabaseError)?;
+
+    match command {
+        TrashCommand::List { workspace, limit, offset } => {
+            let workspace_id = match workspace {
+                Some(name) => Some(workspace_id(&client, &name).await?),
+                None => None,
+            };
+            let links = list_deleted_links(&client, workspace_id, limit, offset).await?;
+            print_trashed(&links.into_iter().map(trashed).collect::<Vec<_>>(), output);
+        }
+        TrashCommand::Restore { code, domain } => {
+            let domain_id = domain_id(&client, domain.as_deref()).await?;
+            let before = get_deleted_link(&client, domain_id, &code)
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Deleted link {}", code)))?;
+            let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+            let link = undelete_link(&tx, domain_id, &code)
+                .await?
+                .ok_or_else(|| CliError::NotFound(format!("Deleted link {}", code)))?;
+            record(&tx, &cli_actor(), &[link_event(AuditAction::LinkUndelete, Some(&before), Some(&link))]).await?;
+            tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+            let mut conn = redis_pool.get().await.map_err(|e| CliError::Redis(e.to_string()))?;
+            invalidate_link(&mut conn, domain_id, &code).await.map_err(|e| CliError::Redis(e.to_string()))?;
+            println!("Restored {}", code);
+        }
+        TrashCommand::Purge => {
+            let purged = purge_trash(&mut client, &redis_pool, &cli_actor()).await?;
+            match output {
+                OutputFormat::Json => print_json(&purged),
+                OutputFormat::Table => println!("Purged {} links", purged.len()),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_trashed(links: &[TrashedLink], output: OutputFormat) {
+    match output {
+        OutputFormat::Json => print_json(links),
+        OutputFormat::Table => {
+            let time = |time: Option<chrono::DateTime<chrono::Utc>>| {
+                time.map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "-".to_string())
+            };
+            let rows: Vec<Vec<String>> = links
+                .iter()
+                .map(|trashed| {
+                    vec![
+                        trashed.link.short_code.clone(),
+                        trashed.short_url.clone(),
+                        trashed.link.original_url.clone(),
+                        time(trashed.link.deleted_at),
+                        time(trashed.purge_at),
+                    ]
+                })
+                .collect();
+            print_table(&["CODE", "SHORT URL", "DESTINATION", "DELETED", "PURGED AFTER"], &rows);
+        }
+    }
+}
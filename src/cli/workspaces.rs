@@ -0,0 +1,181 @@
+use super::output::{format_time, print_json, print_table, OutputFormat};
+use super::CliError;
+use crate::config::api_keys::create_api_key;
+use crate::config::db::DbError;
+use crate::config::workspaces::{
+    create_invite, create_workspace, find_member, find_workspace, list_members, list_workspaces, remove_member,
+    set_member_role,
+};
+use crate::models::{AuditAction, Member, Role, Scope, Workspace};
+use crate::utils::audit::{cli_actor, invite_event, key_event, member_event, record, workspace_event};
+use crate::utils::workspace::{invite_ttl_days, invite_url, validate_email, validate_workspace_name, WorkspaceError};
+use clap::Subcommand;
+use deadpool_postgres::Pool;
+use tokio_postgres::Client;
+
+#[derive(Debug, Subcommand)]
+pub enum WorkspacesCommand {
+    /// Create a workspace with its owner, and print an owner API key once
+    Create {
+        name: String,
+        /// Email address of the owner
+        #[arg(long)]
+        owner: String,
+    },
+    /// List workspaces
+    List,
+    /// List the members of a workspace
+    Members { workspace: String },
+    /// Invite someone to a workspace and print the invite link
+    Invite {
+        workspace: String,
+        email: String,
+        #[arg(long, value_enum, default_value_t = Role::Editor)]
+        role: Role,
+    },
+    /// Change the role of a member
+    SetRole {
+        workspace: String,
+        email: String,
+        #[arg(value_enum)]
+        role: Role,
+    },
+    /// Remove a member and revoke the API keys issued to them
+    RemoveMember { workspace: String, email: String },
+}
+
+pub async fn run(command: WorkspacesCommand, db_pool: Pool, output: OutputFormat) -> Result<(), CliError> {
+    let mut client = db_pool.get().await.map_err(|_| DbError::DatabaseError)?;
+
+    match command {
+        WorkspacesCommand::Create { name, owner } => {
+            let name = validate_workspace_name(&name)?;
+            let owner = validate_email(&owner)?;
+            let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+            let (workspace, member) = create_workspace(&tx, &name, &owner)
+                .await?
+                .ok_or(WorkspaceError::NameTaken)?;
+            let (key, secret) = create_api_key(&tx, workspace.id, Some(member.id), &owner, Scope::ALL).await?;
+            let events = [
+                workspace_event(AuditAction::WorkspaceCreate, None, &workspace),
+                member_event(AuditAction::MemberJoin, None, Some(&member)),
+                key_event(AuditAction::KeyCreate, None, Some(&key)),
+            ];
+            record(&tx, &cli_actor(), &events).await?;
+            tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+            match output {
+                OutputFormat::Json => print_json(&serde_json::json!({
+                    "workspace": workspace,
+                    "member": member,
+                    "key": key,
+                    "secret": secret,
+                })),
+                OutputFormat::Table => {
+                    print_workspaces(std::slice::from_ref(&workspace), output);
+                    println!("\nOwner API key {} (shown only once): {}", key.id, secret);
+                }
+            }
+        }
+        WorkspacesCommand::List => print_workspaces(&list_workspaces(&client).await?, output),
+        WorkspacesCommand::Members { workspace } => {
+            let workspace_id = workspace_id(&client, &workspace).await?;
+            print_members(&list_members(&client, workspace_id).await?, output);
+        }
+        WorkspacesCommand::Invite { workspace, email, role } => {
+            let workspace_id = workspace_id(&client, &workspace).await?;
+            let email = validate_email(&email)?;
+            let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+            let (invite, token) = create_invite(&tx, workspace_id, &email, role, invite_ttl_days()).await?;
+            record(&tx, &cli_actor(), &[invite_event(&invite)]).await?;
+            tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+            let url = invite_url(&token);
+            match output {
+                OutputFormat::Json => print_json(&serde_json::json!({ "invite": invite, "invite_url": url })),
+                OutputFormat::Table => {
+                    println!("Invited {} as {} until {}", invite.email, invite.role.as_str(), invite.expires_at);
+                    println!("Invite link (shown only once): {}", url);
+                }
+            }
+        }
+        WorkspacesCommand::SetRole { workspace, email, role } => {
+            let (workspace_id, before) = member(&client, &workspace, &email).await?;
+            let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+            let member = set_member_role(&tx, workspace_id, before.id, role)
+                .await?
+                .ok_or(WorkspaceError::LastOwner)?;
+            let event = member_event(AuditAction::MemberUpdate, Some(&before), Some(&member));
+            record(&tx, &cli_actor(), &[event]).await?;
+            tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+            print_members(std::slice::from_ref(&member), output);
+        }
+        WorkspacesCommand::RemoveMember { workspace, email } => {
+            let (workspace_id, member) = member(&client, &workspace, &email).await?;
+            let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+            remove_member(&tx, workspace_id, member.id)
+                .await?
+                .ok_or(WorkspaceError::LastOwner)?;
+            record(&tx, &cli_actor(), &[member_event(AuditAction::MemberRemove, Some(&member), None)]).await?;
+            tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+            println!("Removed {} from {}", member.email, workspace);
+        }
+    }
+    Ok(())
+}
+
+/// The ID of the workspace with this name.
+pub(super) async fn workspace_id(client: &Client, name: &str) -> Result<i32, CliError> {
+    let workspace = find_workspace(client, name.trim())
+        .await?
+        .ok_or_else(|| CliError::NotFound(format!("Workspace {}", name)))?;
+    Ok(workspace.id)
+}
+
+/// The ID of the named workspace, if a name was given.
+pub(super) async fn optional_workspace_id(client: &Client, name: Option<String>) -> Result<Option<i32>, CliError> {
+    match name {
+        Some(name) => Ok(Some(workspace_id(client, &name).await?)),
+        None => Ok(None),
+    }
+}
+
+/// The member of the named workspace with this email, along with the workspace ID.
+async fn member(client: &Client, workspace: &str, email: &str) -> Result<(i32, Member), CliError> {
+    let workspace_id = workspace_id(client, workspace).await?;
+    let member = find_member(client, workspace_id, &validate_email(email)?)
+        .await?
+        .ok_or(WorkspaceError::MemberNotFound)?;
+    Ok((workspace_id, member))
+}
+
+fn print_workspaces(workspaces: &[Workspace], output: OutputFormat) {
+    match output {
+        OutputFormat::Json => print_json(workspaces),
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = workspaces
+                .iter()
+                .map(|workspace| vec![workspace.id.to_string(), workspace.name.clone(), format_time(workspace.created_at)])
+                .collect();
+            print_table(&["ID", "NAME", "CREATED"], &rows);
+        }
+    }
+}
+
+fn print_members(members: &[Member], output: OutputFormat) {
+    match output {
+        OutputFormat::Json => print_json(members),
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = members
+                .iter()
+                .map(|member| {
+                    vec![
+                        member.id.to_string(),
+                        member.email.clone(),
+                        member.role.as_str().to_string(),
+                        format_time(member.created_at),
+                    ]
+                })
+                .collect();
+            print_table(&["ID", "EMAIL", "ROLE", "JOINED"], &rows);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::config::db::DbError;
+use crate::models::{ApiKey, Scope};
+use sha2::{Digest, Sha256};
+use tokio_postgres::{Client, Transaction};
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Number of leading characters of a key kept in clear to identify it.
+const KEY_PREFIX_LEN: usize = 8;
+
+/// Hash an API key secret for storage and lookup.
+pub fn hash_key(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// Create an API key for a workspace, optionally issued to one of its members.
+/// Returns the stored key and the secret, which is not kept anywhere.
+#[instrument(name = "db.create_api_key", skip(tx))]
+pub async fn create_api_key(
+    tx: &Transaction<'_>,
+    workspace_id: i32,
+    member_id: Option<i32>,
+    name: &str,
+    scopes: &[Scope],
+) -> Result<(ApiKey, String), DbError> {
+    let secret = format!("lk_{}", Uuid::new_v4().simple());
+    let query = format!(
+        "INSERT INTO api_keys (workspace_id, member_id, name, scopes, key_prefix, key_hash) \
+         VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+        ApiKey::COLUMNS
+    );
+    let scopes: Vec<&str> = scopes.iter().map(Scope::as_str).collect();
+    let row = tx
+        .query_one(
+            &query,
+            &[&workspace_id, &member_id, &name, &scopes, &&secret[..KEY_PREFIX_LEN], &hash_key(&secret)],
+        )
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok((ApiKey::from_row(&row), secret))
+}
+
+/// List the API keys of a workspace, or of every workspace, including revoked ones.
+#[instrument(name = "db.list_api_keys", skip(client))]
+pub async fn list_api_keys(client: &Client, workspace_id: Option<i32>) -> Result<Vec<ApiKey>, DbError> {
+    let query = format!(
+        "SELECT {} FROM api_keys WHERE $1::integer IS NULL OR workspace_id = $1 ORDER BY id",
+        ApiKey::COLUMNS
+    );
+    let rows = client.query(&query, &[&workspace_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(ApiKey::from_row).collect())
+}
+
+/// Revoke an API key, limited to a workspace if one is given.
+/// Returns `None` if no active key has this ID.
+#[instrument(name = "db.revoke_api_key", skip(tx))]
+pub async fn revoke_api_key(tx: &Transaction<'_>, workspace_id: Option<i32>, id: i32) -> Result<Option<ApiKey>, DbError> {
+    let query = format!(
+        "UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP \
+         WHERE id = $1 AND revoked_at IS NULL AND ($2::integer IS NULL OR workspace_id = $2) RETURNING {}",
+        ApiKey::COLUMNS
+    );
+    let row = tx.query_opt(&query, &[&id, &workspace_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(ApiKey::from_row))
+}
+
+/// Turn reuse of existing links on or off for an API key. Returns `None` if no active key has this ID.
+#[instrument(name = "db.set_reuse_existing", skip(tx))]
+pub async fn set_reuse_existing(tx: &Transaction<'_>, id: i32, enabled: bool) -> Result<Option<ApiKey>, DbError> {
+    let query = format!(
+        "UPDATE api_keys SET reuse_existing = $2 WHERE id = $1 AND revoked_at IS NULL RETURNING {}",
+        ApiKey::COLUMNS
+    );
+    let row = tx.query_opt(&query, &[&id, &enabled]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(ApiKey::from_row))
+}
+
+/// Look up an active API key, limited to a workspace if one is given.
+#[instrument(name = "db.get_api_key", skip(client))]
+pub async fn get_api_key(client: &Client, workspace_id: Option<i32>, id: i32) -> Result<Option<ApiKey>, DbError> {
+    let query = format!(
+        "SELECT {} FROM api_keys WHERE id = $1 AND ($2::integer IS NULL OR workspace_id = $2) AND revoked_at IS NULL",
+        ApiKey::COLUMNS
+    );
+    let row = client.query_opt(&query, &[&id, &workspace_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(ApiKey::from_row))
+}
+
+/// Find the active API key matching a secret.
+#[instrument(name = "db.find_api_key", skip_all)]
+pub async fn find_api_key(client: &Client, secret: &str) -> Result<Option<ApiKey>, DbError> {
+    let query = format!(
+        "SELECT {} FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
+        ApiKey::COLUMNS
+    );
+    let row = client
+        .query_opt(&query, &[&hash_key(secret)])
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(ApiKey::from_row))
+}
@@ -0,0 +1,79 @@
+use crate::config::db::DbError;
+use crate::models::{Actor, AuditEvent, AuditQuery, NewAuditEvent};
+use serde::Serialize;
+use serde_json::Value;
+use tokio_postgres::{Client, Transaction};
+use tracing::instrument;
+
+/// A row handed to `jsonb_to_recordset` by `insert_audit_events`.
+#[derive(Serialize)]
+struct AuditEventRow<'a> {
+    workspace_id: Option<i32>,
+    action: &'static str,
+    target: &'a str,
+    diff: &'a Value,
+}
+
+/// Append events made by `actor` to the audit log, in the transaction making the changes they describe.
+#[instrument(name = "db.insert_audit_events", skip_all, fields(count = events.len()))]
+pub async fn insert_audit_events(tx: &Transaction<'_>, actor: &Actor, events: &[NewAuditEvent]) -> Result<(), DbError> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    let query = "INSERT INTO audit_events (workspace_id, actor, actor_key_id, actor_name, ip, action, target, diff) \
+                 SELECT workspace_id, $2, $3, $4, $5, action, target, diff \
+                 FROM jsonb_to_recordset($1::jsonb) AS t(workspace_id integer, action text, target text, diff jsonb)";
+    let rows: Vec<AuditEventRow> = events
+        .iter()
+        .map(|event| AuditEventRow {
+            workspace_id: event.workspace_id,
+            action: event.action.as_str(),
+            target: &event.target,
+            diff: &event.diff,
+        })
+        .collect();
+    let rows = serde_json::to_value(&rows).map_err(|_| DbError::DatabaseError)?;
+    tx.execute(query, &[&rows, &actor.kind, &actor.key_id, &actor.name, &actor.ip])
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(())
+}
+
+/// List audit events matching `query`, of a workspace or of every workspace, newest first.
+#[instrument(name = "db.list_audit_events", skip(client))]
+pub async fn list_audit_events(
+    client: &Client,
+    workspace_id: Option<i32>,
+    query: &AuditQuery,
+    limit: i64,
+) -> Result<Vec<AuditEvent>, DbError> {
+    let sql = format!(
+        "SELECT {} FROM audit_events \
+         WHERE ($1::integer IS NULL OR workspace_id = $1) \
+         AND ($2::text IS NULL OR action = $2) \
+         AND ($3::text IS NULL OR target = $3) \
+         AND ($4::integer IS NULL OR actor_key_id = $4) \
+         AND ($5::timestamptz IS NULL OR created_at >= $5) \
+         AND ($6::timestamptz IS NULL OR created_at < $6) \
+         AND ($7::bigint IS NULL OR id < $7) \
+         ORDER BY id DESC LIMIT $8",
+        AuditEvent::COLUMNS
+    );
+    let rows = client
+        .query(
+            &sql,
+            &[
+                &workspace_id,
+                &query.action.map(|action| action.as_str()),
+                &query.target,
+                &query.actor_key_id,
+                &query.since,
+                &query.until,
+                &query.before,
+                &limit,
+            ],
+        )
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(AuditEvent::from_row).collect())
+}
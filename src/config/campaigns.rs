@@ -0,0 +1,56 @@
+use crate::config::db::DbError;
+use crate::models::{Campaign, UtmParams};
+use tokio_postgres::types::Json;
+use tokio_postgres::Client;
+use tracing::instrument;
+
+/// Selects the campaign named `$1` of the workspace `$2`, `None` for the presets of links without a workspace.
+const CAMPAIGN_KEY: &str = "name = $1 AND COALESCE(workspace_id, 0) = COALESCE($2::integer, 0)";
+
+/// Create a campaign preset in a workspace, or replace the parameters of its preset with the same name.
+#[instrument(name = "db.save_campaign", skip(client, utm))]
+pub async fn save_campaign(
+    client: &Client,
+    workspace_id: Option<i32>,
+    name: &str,
+    utm: &UtmParams,
+) -> Result<Campaign, DbError> {
+    let query = format!(
+        "INSERT INTO campaigns (name, workspace_id, utm) VALUES ($1, $2, $3) \
+         ON CONFLICT ((COALESCE(workspace_id, 0)), name) DO UPDATE SET utm = EXCLUDED.utm RETURNING {}",
+        Campaign::COLUMNS
+    );
+    let row = client
+        .query_one(&query, &[&name, &workspace_id, &Json(utm)])
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(Campaign::from_row(&row))
+}
+
+/// Look up a campaign preset of a workspace by name.
+#[instrument(name = "db.get_campaign", skip(client))]
+pub async fn get_campaign(client: &Client, workspace_id: Option<i32>, name: &str) -> Result<Option<Campaign>, DbError> {
+    let query = format!("SELECT {} FROM campaigns WHERE {}", Campaign::COLUMNS, CAMPAIGN_KEY);
+    let row = client.query_opt(&query, &[&name, &workspace_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Campaign::from_row))
+}
+
+/// List the campaign presets of a workspace by name.
+#[instrument(name = "db.list_campaigns", skip(client))]
+pub async fn list_campaigns(client: &Client, workspace_id: Option<i32>) -> Result<Vec<Campaign>, DbError> {
+    let query = format!(
+        "SELECT {} FROM campaigns WHERE COALESCE(workspace_id, 0) = COALESCE($1::integer, 0) ORDER BY name",
+        Campaign::COLUMNS
+    );
+    let rows = client.query(&query, &[&workspace_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(Campaign::from_row).collect())
+}
+
+/// Delete a campaign preset of a workspace. Links created with it keep their parameters.
+/// Returns `None` if the workspace has no preset with this name.
+#[instrument(name = "db.delete_campaign", skip(client))]
+pub async fn delete_campaign(client: &Client, workspace_id: Option<i32>, name: &str) -> Result<Option<Campaign>, DbError> {
+    let query = format!("DELETE FROM campaigns WHERE {} RETURNING {}", CAMPAIGN_KEY, Campaign::COLUMNS);
+    let row = client.query_opt(&query, &[&name, &workspace_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Campaign::from_row))
+}
@@ -0,0 +1,69 @@
+use crate::config::db::DbError;
+use crate::models::Routing;
+use crate::utils::visitor::Visitor;
+use std::collections::HashMap;
+use tokio_postgres::{Client, RowStream};
+use tracing::instrument;
+
+/// Columns of the `clicks` table, in export order.
+pub const CLICK_COLUMNS: &str = "short_code, (SELECT host FROM domains WHERE domains.id = clicks.domain_id) AS domain, \
+                                 clicked_at, referer, user_agent, country, language, variant";
+
+/// Record a successful redirect.
+#[instrument(name = "db.insert_click", skip(client, visitor, routing))]
+pub async fn insert_click(
+    client: &Client,
+    domain_id: Option<i32>,
+    short_code: &str,
+    visitor: &Visitor,
+    routing: &Routing,
+) -> Result<(), DbError> {
+    let query = "INSERT INTO clicks (short_code, domain_id, referer, user_agent, country, language, variant) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7)";
+    client
+        .execute(
+            query,
+            &[
+                &short_code,
+                &domain_id,
+                &visitor.referer,
+                &visitor.user_agent,
+                &visitor.location.country,
+                &routing.language,
+                &routing.variant,
+            ],
+        )
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(())
+}
+
+/// Stream the clicks on the links of a workspace, or every recorded click, oldest first.
+#[instrument(name = "db.stream_clicks", skip(client))]
+pub async fn stream_clicks(client: &Client, workspace_id: Option<i32>) -> Result<RowStream, DbError> {
+    let query = format!(
+        "SELECT {} FROM clicks WHERE $1::integer IS NULL OR EXISTS ( \
+             SELECT 1 FROM shortlink WHERE shortlink.short_code = clicks.short_code \
+             AND COALESCE(shortlink.domain_id, 0) = COALESCE(clicks.domain_id, 0) AND shortlink.workspace_id = $1 \
+         ) ORDER BY id",
+        CLICK_COLUMNS
+    );
+    client
+        .query_raw(&query, [workspace_id])
+        .await
+        .map_err(|_| DbError::DatabaseError)
+}
+
+/// Number of clicks per variant of a link.
+#[instrument(name = "db.variant_click_counts", skip(client))]
+pub async fn variant_click_counts(
+    client: &Client,
+    domain_id: Option<i32>,
+    short_code: &str,
+) -> Result<HashMap<String, i64>, DbError> {
+    let query = "SELECT variant, COUNT(*) AS clicks FROM clicks \
+                 WHERE short_code = $1 AND COALESCE(domain_id, 0) = COALESCE($2::integer, 0) \
+                 AND variant IS NOT NULL GROUP BY variant";
+    let rows = client.query(query, &[&short_code, &domain_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(|row| (row.get("variant"), row.get("clicks"))).collect())
+}
@@ -1,8 +1,17 @@
 use thiserror::Error;
-use tokio_postgres::{Client, Config, NoTls};
+use tokio_postgres::types::Json;
+use tokio_postgres::{Client, Config, NoTls, RowStream, Transaction};
 use deadpool_postgres::{Manager, Pool};
 use std::env;
+use crate::models::{Destination, LanguageRule, Link, LinkLabels, NewLink, PassthroughMode, TargetingRule, UtmParams, Variant};
+use serde::Serialize;
+use chrono::{DateTime, Utc};
+use crate::utils::import::{ConflictPolicy, ImportRecord};
+use crate::utils::short_code::generate_short_code;
+use crate::utils::validate::normalize_url;
+use std::collections::{HashMap, HashSet};
 use log::info;
+use tracing::instrument;
 
 #[derive(Error, Debug)]
 pub enum DbError {
@@ -12,7 +21,24 @@ pub enum DbError {
 
 impl warp::reject::Reject for DbError {}
 
+/// Matches the link with short code `$1` on domain `$2` (`NULL` for the default domain),
+/// using the per-domain unique index.
+const LINK_KEY: &str = "short_code = $1 AND COALESCE(domain_id, 0) = COALESCE($2::integer, 0)";
+
+/// Days a deleted link stays in the trash before it is purged, unless `TRASH_RETENTION_DAYS` says otherwise.
+const DEFAULT_TRASH_RETENTION_DAYS: i32 = 30;
+
+/// Days a deleted link stays in the trash, from `TRASH_RETENTION_DAYS`.
+pub fn trash_retention_days() -> i32 {
+    env::var("TRASH_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&days| days >= 0)
+        .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS)
+}
+
 /// Configure the PostgreSQL database connection pool.
+#[instrument(name = "db.configure_db")]
 pub async fn configure_db() -> Pool {
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL is not set in .env");
     info!("Connecting to PostgreSQL at {}", database_url);
@@ -28,19 +54,585 @@ pub async fn configure_db() -> Pool {
     pool
 }
 
-/// Insert a shortlink into the database.
-pub async fn insert_shortlink(client: &Client, short_code: &str, original_url: &str) -> Result<(), DbError> {
-    let query = "INSERT INTO shortlink (short_code, original_url) VALUES ($1, $2)";
-    client
-        .execute(query, &[&short_code, &original_url])
+/// Insert a shortlink into the database and return it, owned by `owner_key_id` when created with an API key
+/// and belonging to `workspace_id` when created for a workspace.
+#[instrument(name = "db.insert_shortlink", skip(tx, link))]
+pub async fn insert_shortlink(
+    tx: &Transaction<'_>,
+    short_code: &str,
+    link: &NewLink,
+    owner_key_id: Option<i32>,
+    workspace_id: Option<i32>,
+) -> Result<Link, DbError> {
+    let query = format!(
+        "INSERT INTO shortlink (short_code, original_url, normalized_url, owner_key_id, password_hash, single_use, \
+         active_from, active_until, upcoming_url, ended_url, fallback_url, targeting_rules, \
+         language_rules, default_language, variants, passthrough, utm, campaign, domain_id, workspace_id, \
+         tags, title, notes, folder, interstitial) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, \
+         $21, $22, $23, $24, $25) \
+         RETURNING {}",
+        Link::COLUMNS
+    );
+    let row = tx
+        .query_one(
+            &query,
+            &[
+                &short_code,
+                &link.original_url,
+                &normalize_url(&link.original_url),
+                &owner_key_id,
+                &link.password_hash,
+                &link.single_use,
+                &link.active_from,
+                &link.active_until,
+                &link.upcoming_url,
+                &link.ended_url,
+                &link.fallback_url,
+                &Json(&link.targeting_rules),
+                &Json(&link.language_rules),
+                &link.default_language,
+                &Json(&link.variants),
+                &link.passthrough.as_str(),
+                &Json(&link.utm),
+                &link.campaign,
+                &link.domain_id(),
+                &workspace_id,
+                &link.labels.tags,
+                &link.labels.title,
+                &link.labels.notes,
+                &link.labels.folder,
+                &link.interstitial,
+            ],
+        )
         .await
         .map_err(|_| DbError::DatabaseError)?;
-    Ok(())
+    Ok(Link::from_row(&row))
 }
 
-/// Retrieve the original URL from the database using the short code.
-pub async fn get_original_url(client: &Client, short_code: &str) -> Result<Option<String>, DbError> {
-    let query = "SELECT original_url FROM shortlink WHERE short_code = $1";
-    let row = client.query_opt(query, &[&short_code]).await.map_err(|_| DbError::DatabaseError)?;
+/// Find the short code of an active, public, reusable link the owner already created for the same normalized URL.
+///
+/// The conditions mirror `NewLink::is_reusable`, so a plain request never gets a link that routes visitors elsewhere.
+#[instrument(name = "db.find_owned_link", skip(client, original_url))]
+pub async fn find_owned_link(
+    client: &Client,
+    owner_key_id: Option<i32>,
+    domain_id: Option<i32>,
+    original_url: &str,
+) -> Result<Option<String>, DbError> {
+    let query = "SELECT short_code FROM shortlink \
+                 WHERE owner_key_id IS NOT DISTINCT FROM $1 AND normalized_url = $2 \
+                 AND COALESCE(domain_id, 0) = COALESCE($3::integer, 0) \
+                 AND active_from IS NULL AND active_until IS NULL \
+                 AND password_hash IS NULL AND NOT single_use AND NOT interstitial AND passthrough = 'none' \
+                 AND variants = '[]'::jsonb AND targeting_rules = '[]'::jsonb \
+                 AND language_rules = '[]'::jsonb AND default_language IS NULL \
+                 AND fallback_url IS NULL AND utm = '{}'::jsonb AND deleted_at IS NULL \
+                 AND tags = '{}' AND title IS NULL AND notes IS NULL AND folder IS NULL \
+                 ORDER BY id LIMIT 1";
+    let row = client
+        .query_opt(query, &[&owner_key_id, &normalize_url(original_url), &domain_id])
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
     Ok(row.map(|r| r.get(0)))
 }
+
+/// Retrieve what is needed to redirect a short code on a domain.
+#[instrument(name = "db.get_destination", skip(client))]
+pub async fn get_destination(
+    client: &Client,
+    domain_id: Option<i32>,
+    short_code: &str,
+) -> Result<Option<Destination>, DbError> {
+    let query = format!("SELECT {} FROM shortlink WHERE {}", Destination::COLUMNS, LINK_KEY);
+    let row = client.query_opt(&query, &[&short_code, &domain_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Destination::from_row))
+}
+
+/// Retrieve the password hash of a short code on a domain, `None` if it has no password.
+///
+/// Kept out of `Destination` so that hashes are never cached in Redis.
+#[instrument(name = "db.get_password_hash", skip(client))]
+pub async fn get_password_hash(
+    client: &Client,
+    domain_id: Option<i32>,
+    short_code: &str,
+) -> Result<Option<String>, DbError> {
+    let query = format!("SELECT password_hash FROM shortlink WHERE {}", LINK_KEY);
+    let row = client.query_opt(&query, &[&short_code, &domain_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.and_then(|r| r.get(0)))
+}
+
+/// Use up a single-use link. Returns `None` if it was already consumed, so of any
+/// number of concurrent visitors exactly one gets the destination.
+#[instrument(name = "db.consume_link", skip(tx))]
+pub async fn consume_link(tx: &Transaction<'_>, domain_id: Option<i32>, short_code: &str) -> Result<Option<Link>, DbError> {
+    let query = format!(
+        "UPDATE shortlink SET consumed_at = CURRENT_TIMESTAMP \
+         WHERE {} AND single_use AND consumed_at IS NULL RETURNING {}",
+        LINK_KEY,
+        Link::COLUMNS
+    );
+    let row = tx.query_opt(&query, &[&short_code, &domain_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Link::from_row))
+}
+
+/// Retrieve a shortlink by its domain and short code, unless it is in the trash.
+#[instrument(name = "db.get_link", skip(client))]
+pub async fn get_link(client: &Client, domain_id: Option<i32>, short_code: &str) -> Result<Option<Link>, DbError> {
+    let query = format!("SELECT {} FROM shortlink WHERE {} AND deleted_at IS NULL", Link::COLUMNS, LINK_KEY);
+    let row = client.query_opt(&query, &[&short_code, &domain_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Link::from_row))
+}
+
+/// Filters of `search_links`.
+#[derive(Debug, Default)]
+pub struct LinkFilter<'a> {
+    /// Words to look for in the title, notes and destination, in `websearch_to_tsquery` syntax.
+    pub query: Option<&'a str>,
+    /// Tags the links must all have.
+    pub tags: &'a [String],
+    /// Folder the links are filed in, including its subfolders.
+    pub folder: Option<&'a str>,
+    pub domain_id: Option<i32>,
+    pub owner_key_id: Option<i32>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    /// Only links after this one in the listing order, to page through the results.
+    pub after: Option<LinkCursor>,
+}
+
+/// The position of a link in the listing order of `search_links`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkCursor {
+    /// Relevance of the link to the search; `None` when listing without a search.
+    pub rank: Option<f32>,
+    pub id: i32,
+}
+
+/// List the shortlinks of a workspace, or of every workspace, matching `filter`, with their positions.
+/// Links in the trash are left out.
+///
+/// A search lists the most relevant links first, matches in the title weighing more than in the notes and
+/// more than in the destination; links of equal relevance, and every link when not searching, are listed newest first.
+#[instrument(name = "db.search_links", skip(client))]
+pub async fn search_links(
+    client: &Client,
+    workspace_id: Option<i32>,
+    filter: &LinkFilter<'_>,
+    limit: i64,
+) -> Result<Vec<(Link, LinkCursor)>, DbError> {
+    let query = format!(
+        "SELECT * FROM ( \
+             SELECT {}, ts_rank(search_vector, websearch_to_tsquery('simple', $2)) AS rank \
+             FROM shortlink WHERE deleted_at IS NULL \
+         AND ($1::integer IS NULL OR workspace_id = $1) \
+         AND ($2::text IS NULL OR search_vector @@ websearch_to_tsquery('simple', $2)) \
+         AND tags @> $3::text[] \
+         AND ($4::text IS NULL OR folder = $4 OR left(folder, length($4) + 1) = $4 || '/') \
+         AND ($5::integer IS NULL OR domain_id = $5) \
+         AND ($6::integer IS NULL OR owner_key_id = $6) \
+         AND ($7::timestamptz IS NULL OR created_at >= $7) \
+         AND ($8::timestamptz IS NULL OR created_at < $8) \
+         ) AS matches \
+         WHERE $9::integer IS NULL OR (COALESCE(rank, 0), id) < (COALESCE($10::real, 0), $9) \
+         ORDER BY COALESCE(rank, 0) DESC, id DESC LIMIT $11",
+        Link::COLUMNS
+    );
+    let rows = client
+        .query(
+            &query,
+            &[
+                &workspace_id,
+                &filter.query,
+                &filter.tags,
+                &filter.folder,
+                &filter.domain_id,
+                &filter.owner_key_id,
+                &filter.created_after,
+                &filter.created_before,
+                &filter.after.map(|cursor| cursor.id),
+                &filter.after.and_then(|cursor| cursor.rank),
+                &limit,
+            ],
+        )
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let link = Link::from_row(row);
+            let cursor = LinkCursor { rank: row.get("rank"), id: link.id };
+            (link, cursor)
+        })
+        .collect())
+}
+
+/// Change the destination of a shortlink. Returns `None` if the short code does not exist or is in the trash.
+#[instrument(name = "db.update_link_url", skip(tx, original_url))]
+pub async fn update_link_url(
+    tx: &Transaction<'_>,
+    domain_id: Option<i32>,
+    short_code: &str,
+    original_url: &str,
+) -> Result<Option<Link>, DbError> {
+    let query = format!(
+        "UPDATE shortlink SET original_url = $3, normalized_url = $4, healthy = NULL, health_checked_at = NULL, \
+         metadata_fetched_at = NULL, metadata_attempts = 0, metadata_retry_at = NULL \
+         WHERE {} AND deleted_at IS NULL RETURNING {}",
+        LINK_KEY,
+        Link::COLUMNS
+    );
+    let row = tx
+        .query_opt(&query, &[&short_code, &domain_id, &original_url, &normalize_url(original_url)])
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Link::from_row))
+}
+
+/// Replace the labels of a shortlink. Returns `None` if the short code does not exist or is in the trash.
+#[instrument(name = "db.update_link_labels", skip(tx, labels))]
+pub async fn update_link_labels(
+    tx: &Transaction<'_>,
+    domain_id: Option<i32>,
+    short_code: &str,
+    labels: &LinkLabels,
+) -> Result<Option<Link>, DbError> {
+    let query = format!(
+        "UPDATE shortlink SET tags = $3, title = $4, notes = $5, folder = $6 \
+         WHERE {} AND deleted_at IS NULL RETURNING {}",
+        LINK_KEY,
+        Link::COLUMNS
+    );
+    let row = tx
+        .query_opt(
+            &query,
+            &[&short_code, &domain_id, &labels.tags, &labels.title, &labels.notes, &labels.folder],
+        )
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Link::from_row))
+}
+
+/// Turn the preview page shown to every visitor of a shortlink on or off.
+/// Returns `None` if the short code does not exist or is in the trash.
+#[instrument(name = "db.set_link_interstitial", skip(tx))]
+pub async fn set_link_interstitial(
+    tx: &Transaction<'_>,
+    domain_id: Option<i32>,
+    short_code: &str,
+    interstitial: bool,
+) -> Result<Option<Link>, DbError> {
+    let query = format!(
+        "UPDATE shortlink SET interstitial = $3 WHERE {} AND deleted_at IS NULL RETURNING {}",
+        LINK_KEY,
+        Link::COLUMNS
+    );
+    let row = tx
+        .query_opt(&query, &[&short_code, &domain_id, &interstitial])
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Link::from_row))
+}
+
+/// Move a shortlink to the trash, where it keeps its short code until purged.
+/// Returns `None` if the short code does not exist or is already in the trash.
+#[instrument(name = "db.delete_link", skip(tx))]
+pub async fn delete_link(tx: &Transaction<'_>, domain_id: Option<i32>, short_code: &str) -> Result<Option<Link>, DbError> {
+    let query = format!(
+        "UPDATE shortlink SET deleted_at = CURRENT_TIMESTAMP WHERE {} AND deleted_at IS NULL RETURNING {}",
+        LINK_KEY,
+        Link::COLUMNS
+    );
+    let row = tx.query_opt(&query, &[&short_code, &domain_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Link::from_row))
+}
+
+/// Retrieve a shortlink in the trash by its domain and short code.
+#[instrument(name = "db.get_deleted_link", skip(client))]
+pub async fn get_deleted_link(client: &Client, domain_id: Option<i32>, short_code: &str) -> Result<Option<Link>, DbError> {
+    let query = format!("SELECT {} FROM shortlink WHERE {} AND deleted_at IS NOT NULL", Link::COLUMNS, LINK_KEY);
+    let row = client.query_opt(&query, &[&short_code, &domain_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Link::from_row))
+}
+
+/// List the shortlinks in the trash of a workspace, or of every workspace, most recently deleted first.
+#[instrument(name = "db.list_deleted_links", skip(client))]
+pub async fn list_deleted_links(
+    client: &Client,
+    workspace_id: Option<i32>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<Link>, DbError> {
+    let query = format!(
+        "SELECT {} FROM shortlink WHERE deleted_at IS NOT NULL AND ($1::integer IS NULL OR workspace_id = $1) \
+         ORDER BY deleted_at DESC, id DESC LIMIT $2 OFFSET $3",
+        Link::COLUMNS
+    );
+    let rows = client
+        .query(&query, &[&workspace_id, &limit, &offset])
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(Link::from_row).collect())
+}
+
+/// Take a shortlink back out of the trash. Returns `None` if it is not in the trash.
+#[instrument(name = "db.undelete_link", skip(tx))]
+pub async fn undelete_link(tx: &Transaction<'_>, domain_id: Option<i32>, short_code: &str) -> Result<Option<Link>, DbError> {
+    let query = format!(
+        "UPDATE shortlink SET deleted_at = NULL WHERE {} AND deleted_at IS NOT NULL RETURNING {}",
+        LINK_KEY,
+        Link::COLUMNS
+    );
+    let row = tx.query_opt(&query, &[&short_code, &domain_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Link::from_row))
+}
+
+/// Delete for good the shortlinks that have been in the trash for more than `retention_days`,
+/// along with their clicks and health checks, so their short codes can be taken again.
+/// Returns what the purged links were.
+#[instrument(name = "db.purge_deleted_links", skip(tx))]
+pub async fn purge_deleted_links(tx: &Transaction<'_>, retention_days: i32) -> Result<Vec<Link>, DbError> {
+    let query = format!(
+        "DELETE FROM shortlink WHERE deleted_at < CURRENT_TIMESTAMP - make_interval(days => $1) RETURNING {}",
+        Link::COLUMNS
+    );
+    let rows = tx.query(&query, &[&retention_days]).await.map_err(|_| DbError::DatabaseError)?;
+    let purged: Vec<Link> = rows.iter().map(Link::from_row).collect();
+    for table in ["clicks", "health_checks"] {
+        let query = format!(
+            "DELETE FROM {} WHERE short_code = $1 AND COALESCE(domain_id, 0) = COALESCE($2::integer, 0)",
+            table
+        );
+        for link in &purged {
+            tx.execute(&query, &[&link.short_code, &link.domain_id])
+                .await
+                .map_err(|_| DbError::DatabaseError)?;
+        }
+    }
+    Ok(purged)
+}
+
+/// A row handed to `jsonb_to_recordset` by `insert_shortlinks`.
+#[derive(Serialize)]
+struct NewLinkRow<'a> {
+    short_code: String,
+    original_url: &'a str,
+    normalized_url: String,
+    password_hash: Option<&'a str>,
+    single_use: bool,
+    active_from: Option<DateTime<Utc>>,
+    active_until: Option<DateTime<Utc>>,
+    upcoming_url: Option<&'a str>,
+    ended_url: Option<&'a str>,
+    fallback_url: Option<&'a str>,
+    targeting_rules: &'a [TargetingRule],
+    language_rules: &'a [LanguageRule],
+    default_language: Option<&'a str>,
+    variants: &'a [Variant],
+    passthrough: PassthroughMode,
+    utm: &'a UtmParams,
+    campaign: Option<&'a str>,
+    domain_id: Option<i32>,
+    tags: &'a [String],
+    title: Option<&'a str>,
+    notes: Option<&'a str>,
+    folder: Option<&'a str>,
+    interstitial: bool,
+}
+
+/// Insert many shortlinks owned by `owner_key_id` and belonging to `workspace_id`, generating a short code
+/// for each link. Run in a transaction so either all of them are stored or none.
+///
+/// Returns the stored links in the same order as `links`; `None` marks a link for which
+/// no free short code could be found.
+#[instrument(name = "db.insert_shortlinks", skip_all, fields(count = links.len()))]
+pub async fn insert_shortlinks(
+    tx: &Transaction<'_>,
+    links: &[NewLink],
+    owner_key_id: Option<i32>,
+    workspace_id: Option<i32>,
+) -> Result<Vec<Option<Link>>, DbError> {
+    const MAX_ATTEMPTS: usize = 5;
+    let query = format!(
+        "INSERT INTO shortlink (short_code, original_url, normalized_url, password_hash, single_use, \
+                 active_from, active_until, upcoming_url, ended_url, fallback_url, targeting_rules, language_rules, \
+                 default_language, variants, passthrough, utm, campaign, domain_id, owner_key_id, workspace_id, \
+                 tags, title, notes, folder, interstitial) \
+                 SELECT short_code, original_url, normalized_url, password_hash, single_use, \
+                 active_from, active_until, upcoming_url, ended_url, fallback_url, targeting_rules, language_rules, \
+                 default_language, variants, passthrough, utm, campaign, domain_id, $2::integer, $3::integer, \
+                 tags, title, notes, folder, interstitial \
+                 FROM jsonb_to_recordset($1::jsonb) \
+                 AS t(short_code varchar, original_url text, normalized_url text, password_hash text, single_use boolean, \
+                 active_from timestamptz, active_until timestamptz, upcoming_url text, ended_url text, fallback_url text, \
+                 targeting_rules jsonb, language_rules jsonb, default_language text, variants jsonb, passthrough text, utm jsonb, campaign text, domain_id integer, \
+                 tags text[], title text, notes text, folder text, interstitial boolean) \
+                 ON CONFLICT ((COALESCE(domain_id, 0)), short_code) DO NOTHING RETURNING {}",
+        Link::COLUMNS
+    );
+
+    let mut stored: Vec<Option<Link>> = vec![None; links.len()];
+    for _ in 0..MAX_ATTEMPTS {
+        let pending: Vec<usize> = (0..links.len()).filter(|&i| stored[i].is_none()).collect();
+        if pending.is_empty() {
+            break;
+        }
+        // Candidates must be unique within the statement for the conflict check to be per-row
+        let mut unique = HashSet::new();
+        let rows: Vec<NewLinkRow> = pending
+            .iter()
+            .map(|&i| NewLinkRow {
+                short_code: loop {
+                    let code = generate_short_code();
+                    if unique.insert(code.clone()) {
+                        break code;
+                    }
+                },
+                original_url: &links[i].original_url,
+                normalized_url: normalize_url(&links[i].original_url),
+                password_hash: links[i].password_hash.as_deref(),
+                single_use: links[i].single_use,
+                active_from: links[i].active_from,
+                active_until: links[i].active_until,
+                upcoming_url: links[i].upcoming_url.as_deref(),
+                ended_url: links[i].ended_url.as_deref(),
+                fallback_url: links[i].fallback_url.as_deref(),
+                targeting_rules: &links[i].targeting_rules,
+                language_rules: &links[i].language_rules,
+                default_language: links[i].default_language.as_deref(),
+                variants: &links[i].variants,
+                passthrough: links[i].passthrough,
+                utm: &links[i].utm,
+                campaign: links[i].campaign.as_deref(),
+                domain_id: links[i].domain_id(),
+                tags: &links[i].labels.tags,
+                title: links[i].labels.title.as_deref(),
+                notes: links[i].labels.notes.as_deref(),
+                folder: links[i].labels.folder.as_deref(),
+                interstitial: links[i].interstitial,
+            })
+            .collect();
+        let rows_json = serde_json::to_value(&rows).map_err(|_| DbError::DatabaseError)?;
+        let mut inserted: HashMap<String, Link> = tx
+            .query(&query, &[&rows_json, &owner_key_id, &workspace_id])
+            .await
+            .map_err(|_| DbError::DatabaseError)?
+            .iter()
+            .map(|row| {
+                let link = Link::from_row(row);
+                (link.short_code.clone(), link)
+            })
+            .collect();
+        for (&i, row) in pending.iter().zip(rows) {
+            stored[i] = inserted.remove(&row.short_code);
+        }
+    }
+    Ok(stored)
+}
+
+/// What happened to an imported link.
+#[derive(Debug, Clone)]
+pub enum ImportOutcome {
+    Inserted(Link),
+    Updated { before: Box<Link>, after: Link },
+    /// The short code already exists and was left untouched.
+    Conflict,
+}
+
+/// Insert an imported link, keeping its short code.
+///
+/// With `ConflictPolicy::Overwrite` an existing link with the same code in the same workspace
+/// is replaced: what the record does not carry, such as a password, rules or labels, goes back
+/// to its default. Otherwise it is left untouched and `ImportOutcome::Conflict` is returned.
+#[instrument(name = "db.import_link", skip(tx, record))]
+pub async fn import_link(
+    tx: &Transaction<'_>,
+    record: &ImportRecord,
+    domain_id: Option<i32>,
+    workspace_id: Option<i32>,
+    policy: ConflictPolicy,
+) -> Result<ImportOutcome, DbError> {
+    let insert = "INSERT INTO shortlink (short_code, original_url, created_at, active_from, active_until, tags, normalized_url, \
+                  domain_id, workspace_id) \
+                  VALUES ($1, $2, COALESCE($3::timestamp, LOCALTIMESTAMP), $4, $5, $6, $7, $8, $9)";
+    let before = match policy {
+        ConflictPolicy::Overwrite => {
+            let query = format!("SELECT {} FROM shortlink WHERE {} FOR UPDATE", Link::COLUMNS, LINK_KEY);
+            let row = tx
+                .query_opt(&query, &[&record.short_code, &domain_id])
+                .await
+                .map_err(|_| DbError::DatabaseError)?;
+            row.as_ref().map(Link::from_row)
+        }
+        ConflictPolicy::Skip | ConflictPolicy::Fail => None,
+    };
+    // EXCLUDED holds the defaults of the columns the insert leaves out, which an overwrite resets
+    let query = match policy {
+        ConflictPolicy::Overwrite => format!(
+            "{} ON CONFLICT ((COALESCE(domain_id, 0)), short_code) DO UPDATE SET original_url = EXCLUDED.original_url, \
+             created_at = EXCLUDED.created_at, active_from = EXCLUDED.active_from, \
+             active_until = EXCLUDED.active_until, tags = EXCLUDED.tags, \
+             normalized_url = EXCLUDED.normalized_url, deleted_at = NULL, \
+             password_hash = EXCLUDED.password_hash, single_use = EXCLUDED.single_use, \
+             consumed_at = EXCLUDED.consumed_at, interstitial = EXCLUDED.interstitial, \
+             upcoming_url = EXCLUDED.upcoming_url, ended_url = EXCLUDED.ended_url, \
+             fallback_url = EXCLUDED.fallback_url, healthy = EXCLUDED.healthy, \
+             health_checked_at = EXCLUDED.health_checked_at, targeting_rules = EXCLUDED.targeting_rules, \
+             language_rules = EXCLUDED.language_rules, default_language = EXCLUDED.default_language, \
+             variants = EXCLUDED.variants, passthrough = EXCLUDED.passthrough, utm = EXCLUDED.utm, \
+             campaign = EXCLUDED.campaign, title = EXCLUDED.title, notes = EXCLUDED.notes, \
+             folder = EXCLUDED.folder, \
+             metadata_fetched_at = CASE WHEN shortlink.original_url = EXCLUDED.original_url \
+                 THEN shortlink.metadata_fetched_at END, \
+             metadata_attempts = CASE WHEN shortlink.original_url = EXCLUDED.original_url \
+                 THEN shortlink.metadata_attempts ELSE 0 END, \
+             metadata_retry_at = CASE WHEN shortlink.original_url = EXCLUDED.original_url \
+                 THEN shortlink.metadata_retry_at END \
+             WHERE shortlink.workspace_id IS NOT DISTINCT FROM EXCLUDED.workspace_id \
+             RETURNING {}",
+            insert,
+            Link::COLUMNS
+        ),
+        ConflictPolicy::Skip | ConflictPolicy::Fail => format!(
+            "{} ON CONFLICT ((COALESCE(domain_id, 0)), short_code) DO NOTHING RETURNING {}",
+            insert,
+            Link::COLUMNS
+        ),
+    };
+    let row = tx
+        .query_opt(
+            &query,
+            &[
+                &record.short_code,
+                &record.original_url,
+                &record.created_at,
+                &record.active_from,
+                &record.active_until,
+                &record.tags,
+                &normalize_url(&record.original_url),
+                &domain_id,
+                &workspace_id,
+            ],
+        )
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(match (row.as_ref().map(Link::from_row), before) {
+        (Some(after), Some(before)) => ImportOutcome::Updated { before: Box::new(before), after },
+        (Some(link), None) => ImportOutcome::Inserted(link),
+        (None, _) => ImportOutcome::Conflict,
+    })
+}
+
+/// Stream the shortlinks of a workspace, or every shortlink, oldest first.
+#[instrument(name = "db.stream_links", skip(client))]
+pub async fn stream_links(client: &Client, workspace_id: Option<i32>) -> Result<RowStream, DbError> {
+    let query = format!(
+        "SELECT {} FROM shortlink WHERE deleted_at IS NULL AND ($1::integer IS NULL OR workspace_id = $1) ORDER BY id",
+        Link::COLUMNS
+    );
+    client
+        .query_raw(&query, [workspace_id])
+        .await
+        .map_err(|_| DbError::DatabaseError)
+}
@@ -0,0 +1,51 @@
+use crate::config::db::DbError;
+use crate::models::Domain;
+use tokio_postgres::Client;
+use tracing::instrument;
+
+/// Add a domain, or replace the settings of the one with the same host.
+#[instrument(name = "db.save_domain", skip(client))]
+pub async fn save_domain(
+    client: &Client,
+    host: &str,
+    root_url: Option<&str>,
+    not_found_url: Option<&str>,
+    interstitial: bool,
+) -> Result<Domain, DbError> {
+    let query = format!(
+        "INSERT INTO domains (host, root_url, not_found_url, interstitial) VALUES ($1, $2, $3, $4) \
+         ON CONFLICT (host) DO UPDATE SET root_url = EXCLUDED.root_url, not_found_url = EXCLUDED.not_found_url, \
+         interstitial = EXCLUDED.interstitial \
+         RETURNING {}",
+        Domain::COLUMNS
+    );
+    let row = client
+        .query_one(&query, &[&host, &root_url, &not_found_url, &interstitial])
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(Domain::from_row(&row))
+}
+
+/// Look up a domain by host.
+#[instrument(name = "db.get_domain", skip(client))]
+pub async fn get_domain(client: &Client, host: &str) -> Result<Option<Domain>, DbError> {
+    let query = format!("SELECT {} FROM domains WHERE host = $1", Domain::COLUMNS);
+    let row = client.query_opt(&query, &[&host]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Domain::from_row))
+}
+
+/// List all domains by host.
+#[instrument(name = "db.list_domains", skip(client))]
+pub async fn list_domains(client: &Client) -> Result<Vec<Domain>, DbError> {
+    let query = format!("SELECT {} FROM domains ORDER BY host", Domain::COLUMNS);
+    let rows = client.query(&query, &[]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(Domain::from_row).collect())
+}
+
+/// Remove a domain that has no links. Returns `None` if no domain has this host.
+#[instrument(name = "db.delete_domain", skip(client))]
+pub async fn delete_domain(client: &Client, host: &str) -> Result<Option<Domain>, DbError> {
+    let query = format!("DELETE FROM domains WHERE host = $1 RETURNING {}", Domain::COLUMNS);
+    let row = client.query_opt(&query, &[&host]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Domain::from_row))
+}
@@ -0,0 +1,49 @@
+use crate::models::targeting::{Continent, GeoLocation};
+use log::{error, info};
+use maxminddb::{geoip2, Reader};
+use std::env;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+static GEOIP_READER: OnceLock<Option<Reader<Vec<u8>>>> = OnceLock::new();
+
+/// Load the MaxMind database named by `GEOIP_DATABASE_PATH`, if any.
+///
+/// The whole file is read into memory, so lookups never touch the disk or the network.
+/// Without the setting, or when the file cannot be read, visitors have no location.
+pub fn configure_geoip() {
+    GEOIP_READER.get_or_init(|| {
+        let path = env::var("GEOIP_DATABASE_PATH").ok().filter(|path| !path.is_empty())?;
+        match Reader::open_readfile(&path) {
+            Ok(reader) => {
+                info!("Loaded GeoIP database {} ({})", path, reader.metadata().database_type);
+                Some(reader)
+            }
+            Err(e) => {
+                error!("Failed to open GeoIP database {}: {:?}", path, e);
+                None
+            }
+        }
+    });
+}
+
+/// Where an IP address is located, according to the GeoIP database.
+///
+/// Works with both Country and City databases.
+pub fn geo_location(ip: IpAddr) -> GeoLocation {
+    let Some(Some(reader)) = GEOIP_READER.get() else {
+        return GeoLocation::default();
+    };
+    let record = match reader.lookup(ip).and_then(|result| result.decode::<geoip2::Country>()) {
+        Ok(Some(record)) => record,
+        Ok(None) => return GeoLocation::default(),
+        Err(e) => {
+            error!("GeoIP lookup of {} failed: {:?}", ip, e);
+            return GeoLocation::default();
+        }
+    };
+    GeoLocation {
+        country: record.country.iso_code.map(str::to_ascii_uppercase),
+        continent: record.continent.code.and_then(|code| code.parse::<Continent>().ok()),
+    }
+}
@@ -0,0 +1,77 @@
+use crate::config::db::DbError;
+use crate::models::HealthCheck;
+use tokio_postgres::Client;
+use tracing::instrument;
+
+/// Links whose destination is health checked: those with a fallback that are still in use.
+#[instrument(name = "db.links_to_check", skip(client))]
+pub async fn links_to_check(client: &Client) -> Result<Vec<(Option<i32>, String, String)>, DbError> {
+    let query = "SELECT domain_id, short_code, original_url FROM shortlink \
+                 WHERE fallback_url IS NOT NULL AND consumed_at IS NULL AND deleted_at IS NULL \
+                 AND (active_until IS NULL OR active_until > CURRENT_TIMESTAMP) \
+                 ORDER BY id";
+    let rows = client.query(query, &[]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1), row.get(2))).collect())
+}
+
+/// Store the outcome of a health check and update the link's current health.
+///
+/// Returns `true` if the link's health changed, so its cache entry must be dropped.
+#[instrument(name = "db.record_health_check", skip(client, check))]
+pub async fn record_health_check(
+    client: &Client,
+    domain_id: Option<i32>,
+    short_code: &str,
+    check: &HealthCheck,
+) -> Result<bool, DbError> {
+    let query = "WITH recorded AS ( \
+                     INSERT INTO health_checks (short_code, domain_id, checked_at, healthy, status_code, latency_ms, error) \
+                     VALUES ($1, $7, $2, $3, $4, $5, $6) \
+                 ) \
+                 UPDATE shortlink SET healthy = $3, health_checked_at = $2 \
+                 FROM (SELECT healthy FROM shortlink \
+                       WHERE short_code = $1 AND COALESCE(domain_id, 0) = COALESCE($7::integer, 0)) AS previous \
+                 WHERE shortlink.short_code = $1 AND COALESCE(shortlink.domain_id, 0) = COALESCE($7::integer, 0) \
+                 RETURNING previous.healthy IS DISTINCT FROM $3 AS changed";
+    let row = client
+        .query_opt(
+            query,
+            &[
+                &short_code,
+                &check.checked_at,
+                &check.healthy,
+                &check.status_code,
+                &check.latency_ms,
+                &check.error,
+                &domain_id,
+            ],
+        )
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(row.is_some_and(|row| row.get("changed")))
+}
+
+/// The most recent health checks of a link, newest first.
+#[instrument(name = "db.recent_health_checks", skip(client))]
+pub async fn recent_health_checks(
+    client: &Client,
+    domain_id: Option<i32>,
+    short_code: &str,
+    limit: i64,
+) -> Result<Vec<HealthCheck>, DbError> {
+    let query = format!(
+        "SELECT {} FROM health_checks \
+         WHERE short_code = $1 AND COALESCE(domain_id, 0) = COALESCE($3::integer, 0) \
+         ORDER BY checked_at DESC LIMIT $2",
+        HealthCheck::COLUMNS
+    );
+    let rows = client.query(&query, &[&short_code, &limit, &domain_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(HealthCheck::from_row).collect())
+}
+
+/// Delete health checks older than `retention_days`. Returns the number of rows removed.
+#[instrument(name = "db.prune_health_checks", skip(client))]
+pub async fn prune_health_checks(client: &Client, retention_days: i32) -> Result<u64, DbError> {
+    let query = "DELETE FROM health_checks WHERE checked_at < CURRENT_TIMESTAMP - make_interval(days => $1)";
+    client.execute(query, &[&retention_days]).await.map_err(|_| DbError::DatabaseError)
+}
@@ -0,0 +1,95 @@
+use crate::utils::request_context::current_request_id;
+use env_logger::{Builder, Env};
+use log::kv::{Key, Value, VisitSource};
+use log::Record;
+use serde_json::{Map, Value as JsonValue};
+use std::env;
+use std::io::Write;
+
+/// Output format of the application logs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    /// Read the log format from the `LOG_FORMAT` environment variable (`text` or `json`).
+    pub fn from_env() -> Self {
+        match env::var("LOG_FORMAT").unwrap_or_default().to_lowercase().as_str() {
+            "json" => LogFormat::Json,
+            _ => LogFormat::Text,
+        }
+    }
+}
+
+/// Configure the global logger.
+///
+/// Every line emitted while a request is being handled carries that request's ID.
+pub fn configure_logging() {
+    let format = LogFormat::from_env();
+    let mut builder = Builder::from_env(Env::default().default_filter_or("info"));
+
+    builder.format(move |buf, record| {
+        let request_id = current_request_id();
+        match format {
+            LogFormat::Json => {
+                let mut line = Map::new();
+                line.insert("timestamp".into(), buf.timestamp().to_string().into());
+                line.insert("level".into(), record.level().as_str().into());
+                line.insert("target".into(), record.target().into());
+                line.insert("message".into(), record.args().to_string().into());
+                if let Some(request_id) = request_id {
+                    line.insert("request_id".into(), request_id.into());
+                }
+                let _ = record.key_values().visit(&mut JsonFields(&mut line));
+                writeln!(buf, "{}", JsonValue::Object(line))
+            }
+            LogFormat::Text => {
+                write!(buf, "[{} {} {}]", buf.timestamp(), record.level(), record.target())?;
+                if let Some(request_id) = request_id {
+                    write!(buf, " [{}]", request_id)?;
+                }
+                write!(buf, " {}", record.args())?;
+                write_text_fields(buf, record)?;
+                writeln!(buf)
+            }
+        }
+    });
+
+    builder.init();
+}
+
+/// Append the structured fields of a record as `key=value` pairs.
+fn write_text_fields(buf: &mut env_logger::fmt::Formatter, record: &Record) -> std::io::Result<()> {
+    let mut fields = Map::new();
+    let _ = record.key_values().visit(&mut JsonFields(&mut fields));
+    for (key, value) in fields {
+        match value {
+            JsonValue::String(s) => write!(buf, " {}={}", key, s)?,
+            other => write!(buf, " {}={}", key, other)?,
+        }
+    }
+    Ok(())
+}
+
+/// Collects the key-values of a log record into a JSON object, keeping numbers and booleans typed.
+struct JsonFields<'a>(&'a mut Map<String, JsonValue>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), log::kv::Error> {
+        let value = if let Some(v) = value.to_u64() {
+            JsonValue::from(v)
+        } else if let Some(v) = value.to_i64() {
+            JsonValue::from(v)
+        } else if let Some(v) = value.to_f64() {
+            JsonValue::from(v)
+        } else if let Some(v) = value.to_bool() {
+            JsonValue::from(v)
+        } else {
+            JsonValue::from(value.to_string())
+        };
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
+    }
+}
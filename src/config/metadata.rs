@@ -0,0 +1,60 @@
+use crate::config::db::DbError;
+use crate::models::LinkMetadata;
+use tokio_postgres::Client;
+use tracing::instrument;
+
+/// Links whose destination page has not been fetched yet and is not waiting for a retry,
+/// oldest first, as `(id, original_url)`.
+#[instrument(name = "db.links_missing_metadata", skip(client))]
+pub async fn links_missing_metadata(client: &Client, limit: i64) -> Result<Vec<(i32, String)>, DbError> {
+    let query = "SELECT id, original_url FROM shortlink \
+                 WHERE metadata_fetched_at IS NULL AND deleted_at IS NULL \
+                 AND (metadata_retry_at IS NULL OR metadata_retry_at <= CURRENT_TIMESTAMP) \
+                 ORDER BY id LIMIT $1";
+    let rows = client.query(query, &[&limit]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// Store what was fetched from a link's destination.
+///
+/// Nothing is stored if the destination changed in the meantime, so the new one is fetched instead.
+#[instrument(name = "db.record_link_metadata", skip(client, original_url, metadata))]
+pub async fn record_link_metadata(
+    client: &Client,
+    link_id: i32,
+    original_url: &str,
+    metadata: &LinkMetadata,
+) -> Result<bool, DbError> {
+    let query = "UPDATE shortlink SET page_title = $3, page_description = $4, favicon_url = $5, image_url = $6, \
+                 metadata_fetched_at = CURRENT_TIMESTAMP, metadata_attempts = 0, metadata_retry_at = NULL \
+                 WHERE id = $1 AND original_url = $2";
+    let updated = client
+        .execute(
+            query,
+            &[
+                &link_id,
+                &original_url,
+                &metadata.page_title,
+                &metadata.page_description,
+                &metadata.favicon_url,
+                &metadata.image_url,
+            ],
+        )
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(updated > 0)
+}
+
+/// Count a failed attempt at fetching a link's destination and put the next one off,
+/// doubling the wait each time from a minute up to a day.
+///
+/// Returns the number of failed attempts so far, or `None` if the destination changed in the meantime.
+#[instrument(name = "db.postpone_link_metadata", skip(client, original_url))]
+pub async fn postpone_link_metadata(client: &Client, link_id: i32, original_url: &str) -> Result<Option<i32>, DbError> {
+    let query = "UPDATE shortlink SET metadata_attempts = metadata_attempts + 1, \
+                 metadata_retry_at = CURRENT_TIMESTAMP \
+                     + LEAST(make_interval(mins => 1 << LEAST(metadata_attempts, 11)), INTERVAL '1 day') \
+                 WHERE id = $1 AND original_url = $2 RETURNING metadata_attempts";
+    let row = client.query_opt(query, &[&link_id, &original_url]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.map(|row| row.get(0)))
+}
@@ -0,0 +1,162 @@
+use crate::config::db::DbError;
+use deadpool_postgres::Client;
+use log::{error, info};
+
+/// SQL migrations, applied in this order. Each one is idempotent.
+const MIGRATIONS: &[(&str, &str)] = &[
+    (
+        "2025-02-14-create-shortlink-table",
+        include_str!("../../migrations/2025-02-14-create-shortlink-table.sql"),
+    ),
+    (
+        "2026-10-19-create-api-keys-table",
+        include_str!("../../migrations/2026-10-19-create-api-keys-table.sql"),
+    ),
+    (
+        "2026-10-19-add-link-expiry-and-tags",
+        include_str!("../../migrations/2026-10-19-add-link-expiry-and-tags.sql"),
+    ),
+    (
+        "2026-10-19-create-clicks-table",
+        include_str!("../../migrations/2026-10-19-create-clicks-table.sql"),
+    ),
+    (
+        "2026-10-19-add-link-owner",
+        include_str!("../../migrations/2026-10-19-add-link-owner.sql"),
+    ),
+    (
+        "2026-10-19-add-link-password",
+        include_str!("../../migrations/2026-10-19-add-link-password.sql"),
+    ),
+    (
+        "2026-10-19-add-single-use-links",
+        include_str!("../../migrations/2026-10-19-add-single-use-links.sql"),
+    ),
+    (
+        "2026-10-19-add-link-schedule",
+        include_str!("../../migrations/2026-10-19-add-link-schedule.sql"),
+    ),
+    (
+        "2026-10-19-add-link-health",
+        include_str!("../../migrations/2026-10-19-add-link-health.sql"),
+    ),
+    (
+        "2026-10-19-add-link-targeting",
+        include_str!("../../migrations/2026-10-19-add-link-targeting.sql"),
+    ),
+    (
+        "2026-10-19-add-click-country",
+        include_str!("../../migrations/2026-10-19-add-click-country.sql"),
+    ),
+    (
+        "2026-10-19-add-link-languages",
+        include_str!("../../migrations/2026-10-19-add-link-languages.sql"),
+    ),
+    (
+        "2026-10-19-add-link-variants",
+        include_str!("../../migrations/2026-10-19-add-link-variants.sql"),
+    ),
+    (
+        "2026-10-19-add-link-passthrough",
+        include_str!("../../migrations/2026-10-19-add-link-passthrough.sql"),
+    ),
+    (
+        "2026-10-19-create-campaigns-table",
+        include_str!("../../migrations/2026-10-19-create-campaigns-table.sql"),
+    ),
+    (
+        "2026-10-19-add-link-utm",
+        include_str!("../../migrations/2026-10-19-add-link-utm.sql"),
+    ),
+    (
+        "2026-10-19-create-domains-table",
+        include_str!("../../migrations/2026-10-19-create-domains-table.sql"),
+    ),
+    (
+        "2026-10-19-add-link-domain",
+        include_str!("../../migrations/2026-10-19-add-link-domain.sql"),
+    ),
+    (
+        "2026-10-19-create-workspaces-table",
+        include_str!("../../migrations/2026-10-19-create-workspaces-table.sql"),
+    ),
+    (
+        "2026-10-19-add-workspace-ownership",
+        include_str!("../../migrations/2026-10-19-add-workspace-ownership.sql"),
+    ),
+    (
+        "2026-10-19-create-audit-events-table",
+        include_str!("../../migrations/2026-10-19-create-audit-events-table.sql"),
+    ),
+    (
+        "2026-10-19-create-link-revisions-table",
+        include_str!("../../migrations/2026-10-19-create-link-revisions-table.sql"),
+    ),
+    (
+        "2026-10-19-add-link-soft-delete",
+        include_str!("../../migrations/2026-10-19-add-link-soft-delete.sql"),
+    ),
+    (
+        "2026-10-19-add-link-labels",
+        include_str!("../../migrations/2026-10-19-add-link-labels.sql"),
+    ),
+    (
+        "2026-10-19-add-link-metadata",
+        include_str!("../../migrations/2026-10-19-add-link-metadata.sql"),
+    ),
+    (
+        "2026-10-19-add-link-interstitial",
+        include_str!("../../migrations/2026-10-19-add-link-interstitial.sql"),
+    ),
+    (
+        "2026-10-19-add-link-metadata-retries",
+        include_str!("../../migrations/2026-10-19-add-link-metadata-retries.sql"),
+    ),
+    (
+        "2026-10-19-add-campaign-workspace",
+        include_str!("../../migrations/2026-10-19-add-campaign-workspace.sql"),
+    ),
+];
+
+/// Apply every migration that has not been recorded in `schema_migrations` yet.
+///
+/// Returns the names of the migrations that were applied.
+pub async fn run_migrations(client: &mut Client) -> Result<Vec<String>, DbError> {
+    client
+        .batch_execute(
+            "CREATE TABLE IF NOT EXISTS schema_migrations (
+                name TEXT PRIMARY KEY,
+                applied_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            )",
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to create schema_migrations table: {:?}", e);
+            DbError::DatabaseError
+        })?;
+
+    let mut applied = Vec::new();
+    for (name, sql) in MIGRATIONS {
+        let done = client
+            .query_opt("SELECT 1 FROM schema_migrations WHERE name = $1", &[name])
+            .await
+            .map_err(|_| DbError::DatabaseError)?
+            .is_some();
+        if done {
+            continue;
+        }
+
+        info!("Applying migration {}", name);
+        let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+        tx.batch_execute(sql).await.map_err(|e| {
+            error!("Migration {} failed: {:?}", name, e);
+            DbError::DatabaseError
+        })?;
+        tx.execute("INSERT INTO schema_migrations (name) VALUES ($1)", &[name])
+            .await
+            .map_err(|_| DbError::DatabaseError)?;
+        tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+        applied.push(name.to_string());
+    }
+    Ok(applied)
+}
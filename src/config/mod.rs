@@ -1,2 +1,15 @@
+pub mod api_keys;
+pub mod audit;
+pub mod campaigns;
+pub mod clicks;
 pub mod db;
-pub mod redis;
\ No newline at end of file
+pub mod domains;
+pub mod geoip;
+pub mod health;
+pub mod logging;
+pub mod metadata;
+pub mod migrations;
+pub mod redis;
+pub mod revisions;
+pub mod telemetry;
+pub mod workspaces;
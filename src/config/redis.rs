@@ -1,6 +1,10 @@
-use deadpool_redis::{Config, Pool};
+use chrono::Utc;
+use deadpool_redis::redis::{AsyncCommands, RedisResult};
+use deadpool_redis::{Config, Connection, Pool};
 use std::env;
-use log::info;
+use crate::models::Destination;
+use log::{info, warn};
+use tracing::{info_span, Instrument};
 
 pub async fn configure_redis() -> Pool {
     let redis_url = env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string());
@@ -12,3 +16,89 @@ pub async fn configure_redis() -> Pool {
     info!("Successfully created Redis pool");
     pool
 }
+
+/// How long a resolved link stays cached, in seconds.
+pub const LINK_CACHE_TTL_SECS: u64 = 3600;
+
+/// Cache lifetime of a link, shortened so the entry expires when the link opens or closes.
+pub fn link_cache_ttl(destination: &Destination) -> u64 {
+    let now = Utc::now();
+    match destination.next_transition(now) {
+        Some(transition) => {
+            let remaining = (transition - now).num_seconds().max(1) as u64;
+            remaining.min(LINK_CACHE_TTL_SECS)
+        }
+        None => LINK_CACHE_TTL_SECS,
+    }
+}
+
+/// Redis key under which the destination of a short code on a domain is cached.
+pub fn link_cache_key(domain_id: Option<i32>, short_code: &str) -> String {
+    match domain_id {
+        Some(domain_id) => format!("short:{}:{}", domain_id, short_code),
+        None => format!("short:{}", short_code),
+    }
+}
+
+/// Read the cached destination of a short code.
+///
+/// Entries that cannot be decoded, such as ones written by an older version, are treated as missing.
+pub async fn get_cached_destination(
+    conn: &mut Connection,
+    domain_id: Option<i32>,
+    short_code: &str,
+) -> RedisResult<Option<Destination>> {
+    let key = link_cache_key(domain_id, short_code);
+    let cached: Option<String> = conn
+        .get(&key)
+        .instrument(info_span!("redis.get", key = %key))
+        .await?;
+    Ok(cached.and_then(|value| match serde_json::from_str(&value) {
+        Ok(destination) => Some(destination),
+        Err(e) => {
+            warn!("Ignoring undecodable cache entry {}: {}", key, e);
+            None
+        }
+    }))
+}
+
+/// Cache the destination of a short code until it expires.
+pub async fn cache_destination(
+    conn: &mut Connection,
+    domain_id: Option<i32>,
+    short_code: &str,
+    destination: &Destination,
+) -> RedisResult<()> {
+    let key = link_cache_key(domain_id, short_code);
+    let value = serde_json::to_string(destination).expect("destination serializes");
+    conn.set_ex(&key, value, link_cache_ttl(destination))
+        .instrument(info_span!("redis.set_ex", key = %key))
+        .await
+}
+
+/// Drop the cached destination of a short code.
+pub async fn invalidate_link(conn: &mut Connection, domain_id: Option<i32>, short_code: &str) -> RedisResult<()> {
+    let key = link_cache_key(domain_id, short_code);
+    conn.del::<_, ()>(&key)
+        .instrument(info_span!("redis.del", key = %key))
+        .await
+}
+
+/// Drop every cached destination, on every domain. Returns the number of keys removed.
+pub async fn purge_links(conn: &mut Connection) -> RedisResult<usize> {
+    let keys: Vec<String> = {
+        let mut iter = conn.scan_match::<_, String>(link_cache_key(None, "*")).await?;
+        let mut keys = Vec::new();
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        keys
+    };
+    if keys.is_empty() {
+        return Ok(0);
+    }
+    conn.del::<_, ()>(&keys)
+        .instrument(info_span!("redis.del", keys = keys.len()))
+        .await?;
+    Ok(keys.len())
+}
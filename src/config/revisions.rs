@@ -0,0 +1,76 @@
+use crate::config::db::DbError;
+use crate::models::{Link, LinkRevision};
+use crate::utils::validate::normalize_url;
+use tokio_postgres::types::Json;
+use tokio_postgres::{Client, Transaction};
+use tracing::instrument;
+
+/// List the revisions of a link, newest first.
+#[instrument(name = "db.list_link_revisions", skip(client))]
+pub async fn list_link_revisions(client: &Client, link_id: i32) -> Result<Vec<LinkRevision>, DbError> {
+    let query = format!(
+        "SELECT {} FROM link_revisions WHERE link_id = $1 ORDER BY revision DESC",
+        LinkRevision::COLUMNS
+    );
+    let rows = client.query(&query, &[&link_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(LinkRevision::from_row).collect())
+}
+
+/// Retrieve one revision of a link.
+#[instrument(name = "db.get_link_revision", skip(client))]
+pub async fn get_link_revision(client: &Client, link_id: i32, revision: i32) -> Result<Option<LinkRevision>, DbError> {
+    let query = format!(
+        "SELECT {} FROM link_revisions WHERE link_id = $1 AND revision = $2",
+        LinkRevision::COLUMNS
+    );
+    let row = client.query_opt(&query, &[&link_id, &revision]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(LinkRevision::from_row))
+}
+
+/// Put a link back the way a revision recorded it, which is itself recorded as a new revision.
+///
+/// The health of the destination is forgotten when the destination changes.
+/// Returns `None` if the link no longer exists.
+#[instrument(name = "db.restore_link_revision", skip(tx, revision), fields(revision = revision.revision))]
+pub async fn restore_link_revision(
+    tx: &Transaction<'_>,
+    link_id: i32,
+    revision: &LinkRevision,
+) -> Result<Option<Link>, DbError> {
+    let query = format!(
+        "UPDATE shortlink SET original_url = $2, normalized_url = $3, active_from = $4, active_until = $5, \
+         upcoming_url = $6, ended_url = $7, fallback_url = $8, targeting_rules = $9, language_rules = $10, \
+         default_language = $11, variants = $12, passthrough = $13, utm = $14, campaign = $15, \
+         healthy = CASE WHEN original_url = $2 THEN healthy END, \
+         health_checked_at = CASE WHEN original_url = $2 THEN health_checked_at END, \
+         metadata_fetched_at = CASE WHEN original_url = $2 THEN metadata_fetched_at END, \
+         metadata_attempts = CASE WHEN original_url = $2 THEN metadata_attempts ELSE 0 END, \
+         metadata_retry_at = CASE WHEN original_url = $2 THEN metadata_retry_at END \
+         WHERE id = $1 RETURNING {}",
+        Link::COLUMNS
+    );
+    let row = tx
+        .query_opt(
+            &query,
+            &[
+                &link_id,
+                &revision.original_url,
+                &normalize_url(&revision.original_url),
+                &revision.active_from,
+                &revision.active_until,
+                &revision.upcoming_url,
+                &revision.ended_url,
+                &revision.fallback_url,
+                &Json(&revision.targeting_rules),
+                &Json(&revision.language_rules),
+                &revision.default_language,
+                &Json(&revision.variants),
+                &revision.passthrough.as_str(),
+                &Json(&revision.utm),
+                &revision.campaign,
+            ],
+        )
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Link::from_row))
+}
@@ -0,0 +1,76 @@
+use log::{error, info};
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{global, Context};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::env;
+use tracing_subscriber::layer::SubscriberExt;
+use warp::http::HeaderMap;
+
+/// Service name reported with every exported span.
+const SERVICE_NAME: &str = "time_to_rust";
+
+/// Configure span export over OTLP/HTTP.
+///
+/// Export is enabled when `OTEL_EXPORTER_OTLP_ENDPOINT` is set (e.g. `http://localhost:4318`);
+/// otherwise spans are still created but dropped. Returns the provider so it can be flushed on shutdown.
+pub fn configure_tracing() -> Option<SdkTracerProvider> {
+    // Incoming `traceparent` headers are honoured whether or not export is enabled.
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let endpoint = match env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Ok(endpoint) if !endpoint.is_empty() => endpoint,
+        _ => {
+            info!("OTEL_EXPORTER_OTLP_ENDPOINT is not set, span export disabled");
+            return None;
+        }
+    };
+    info!("Exporting spans over OTLP to {}", endpoint);
+
+    let exporter = match SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            error!("Failed to create OTLP exporter: {:?}", e);
+            return None;
+        }
+    };
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+        .build();
+    let tracer = provider.tracer(SERVICE_NAME);
+    global::set_tracer_provider(provider.clone());
+
+    let subscriber = tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+    if let Err(e) = tracing::subscriber::set_global_default(subscriber) {
+        error!("Failed to install tracing subscriber: {:?}", e);
+        return None;
+    }
+
+    Some(provider)
+}
+
+/// Extract the remote trace context (W3C `traceparent`/`tracestate`) from request headers.
+pub fn extract_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
@@ -0,0 +1,240 @@
+use crate::config::api_keys::hash_key;
+use crate::config::db::DbError;
+use crate::models::{Invite, Member, Role, Workspace};
+use tokio_postgres::{Client, Transaction};
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Only lets a member stop being an owner (`$1` workspace, `$3` new role) if another owner remains.
+///
+/// Run after [`lock_owners`] in the same transaction, so two owners cannot step down at the same time.
+const KEEPS_AN_OWNER: &str = "(role <> 'owner' OR $3 = 'owner' \
+    OR (SELECT COUNT(*) FROM workspace_members AS owners WHERE owners.workspace_id = $1 AND owners.role = 'owner') > 1)";
+
+/// Create a workspace with its first owner. Returns `None` if the name is taken.
+#[instrument(name = "db.create_workspace", skip(tx))]
+pub async fn create_workspace(
+    tx: &Transaction<'_>,
+    name: &str,
+    owner_email: &str,
+) -> Result<Option<(Workspace, Member)>, DbError> {
+    let query = format!(
+        "INSERT INTO workspaces (name) VALUES ($1) ON CONFLICT (name) DO NOTHING RETURNING {}",
+        Workspace::COLUMNS
+    );
+    let Some(row) = tx.query_opt(&query, &[&name]).await.map_err(|_| DbError::DatabaseError)? else {
+        return Ok(None);
+    };
+    let workspace = Workspace::from_row(&row);
+    let query = format!(
+        "INSERT INTO workspace_members (workspace_id, email, role) VALUES ($1, $2, $3) RETURNING {}",
+        Member::COLUMNS
+    );
+    let row = tx
+        .query_one(&query, &[&workspace.id, &owner_email, &Role::Owner.as_str()])
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(Some((workspace, Member::from_row(&row))))
+}
+
+/// Look up a workspace by ID.
+#[instrument(name = "db.get_workspace", skip(client))]
+pub async fn get_workspace(client: &Client, id: i32) -> Result<Option<Workspace>, DbError> {
+    let query = format!("SELECT {} FROM workspaces WHERE id = $1", Workspace::COLUMNS);
+    let row = client.query_opt(&query, &[&id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Workspace::from_row))
+}
+
+/// Look up a workspace by name.
+#[instrument(name = "db.find_workspace", skip(client))]
+pub async fn find_workspace(client: &Client, name: &str) -> Result<Option<Workspace>, DbError> {
+    let query = format!("SELECT {} FROM workspaces WHERE name = $1", Workspace::COLUMNS);
+    let row = client.query_opt(&query, &[&name]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Workspace::from_row))
+}
+
+/// List all workspaces by name.
+#[instrument(name = "db.list_workspaces", skip(client))]
+pub async fn list_workspaces(client: &Client) -> Result<Vec<Workspace>, DbError> {
+    let query = format!("SELECT {} FROM workspaces ORDER BY name", Workspace::COLUMNS);
+    let rows = client.query(&query, &[]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(Workspace::from_row).collect())
+}
+
+/// Rename a workspace. Returns `None` if another workspace already has this name.
+#[instrument(name = "db.rename_workspace", skip(tx))]
+pub async fn rename_workspace(tx: &Transaction<'_>, id: i32, name: &str) -> Result<Option<Workspace>, DbError> {
+    let query = format!(
+        "UPDATE workspaces SET name = $2 WHERE id = $1 \
+         AND NOT EXISTS (SELECT 1 FROM workspaces AS other WHERE other.name = $2 AND other.id <> $1) \
+         RETURNING {}",
+        Workspace::COLUMNS
+    );
+    let row = tx.query_opt(&query, &[&id, &name]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Workspace::from_row))
+}
+
+/// List the members of a workspace, owners first.
+#[instrument(name = "db.list_members", skip(client))]
+pub async fn list_members(client: &Client, workspace_id: i32) -> Result<Vec<Member>, DbError> {
+    let query = format!(
+        "SELECT {} FROM workspace_members WHERE workspace_id = $1 \
+         ORDER BY array_position(ARRAY['owner', 'admin', 'editor', 'viewer'], role), email",
+        Member::COLUMNS
+    );
+    let rows = client.query(&query, &[&workspace_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(Member::from_row).collect())
+}
+
+/// Look up a member of a workspace by ID.
+#[instrument(name = "db.get_member", skip(client))]
+pub async fn get_member(client: &Client, workspace_id: i32, id: i32) -> Result<Option<Member>, DbError> {
+    let query = format!(
+        "SELECT {} FROM workspace_members WHERE workspace_id = $1 AND id = $2",
+        Member::COLUMNS
+    );
+    let row = client.query_opt(&query, &[&workspace_id, &id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Member::from_row))
+}
+
+/// Look up a member of a workspace by email.
+#[instrument(name = "db.find_member", skip(client))]
+pub async fn find_member(client: &Client, workspace_id: i32, email: &str) -> Result<Option<Member>, DbError> {
+    let query = format!(
+        "SELECT {} FROM workspace_members WHERE workspace_id = $1 AND email = $2",
+        Member::COLUMNS
+    );
+    let row = client.query_opt(&query, &[&workspace_id, &email]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Member::from_row))
+}
+
+/// Lock the owners of a workspace until the end of the transaction.
+///
+/// Changes checked by [`KEEPS_AN_OWNER`] then wait for each other, and each one counts the owners
+/// left by those committed before it rather than all of them seeing the same count.
+async fn lock_owners(tx: &Transaction<'_>, workspace_id: i32) -> Result<(), DbError> {
+    tx.execute(
+        "SELECT id FROM workspace_members WHERE workspace_id = $1 AND role = 'owner' FOR UPDATE",
+        &[&workspace_id],
+    )
+    .await
+    .map_err(|_| DbError::DatabaseError)?;
+    Ok(())
+}
+
+/// Change the role of a member. Returns `None` if the member does not exist or is the last owner.
+#[instrument(name = "db.set_member_role", skip(tx))]
+pub async fn set_member_role(
+    tx: &Transaction<'_>,
+    workspace_id: i32,
+    id: i32,
+    role: Role,
+) -> Result<Option<Member>, DbError> {
+    lock_owners(tx, workspace_id).await?;
+    let query = format!(
+        "UPDATE workspace_members SET role = $3 WHERE workspace_id = $1 AND id = $2 AND {} RETURNING {}",
+        KEEPS_AN_OWNER,
+        Member::COLUMNS
+    );
+    let row = tx
+        .query_opt(&query, &[&workspace_id, &id, &role.as_str()])
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(row.as_ref().map(Member::from_row))
+}
+
+/// Remove a member and revoke the API keys issued to them.
+/// Returns `None` if the member does not exist or is the last owner.
+#[instrument(name = "db.remove_member", skip(tx))]
+pub async fn remove_member(tx: &Transaction<'_>, workspace_id: i32, id: i32) -> Result<Option<Member>, DbError> {
+    lock_owners(tx, workspace_id).await?;
+    // Revoked first: deleting the member detaches its keys
+    tx.execute(
+        "UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP \
+         WHERE workspace_id = $1 AND member_id = $2 AND revoked_at IS NULL",
+        &[&workspace_id, &id],
+    )
+    .await
+    .map_err(|_| DbError::DatabaseError)?;
+    let query = format!(
+        "DELETE FROM workspace_members WHERE workspace_id = $1 AND id = $2 AND {} RETURNING {}",
+        KEEPS_AN_OWNER,
+        Member::COLUMNS
+    );
+    // Nobody is removed "to owner", so `$3` never exempts the last owner
+    let Some(row) = tx
+        .query_opt(&query, &[&workspace_id, &id, &""])
+        .await
+        .map_err(|_| DbError::DatabaseError)?
+    else {
+        return Ok(None);
+    };
+    Ok(Some(Member::from_row(&row)))
+}
+
+/// Invite someone to a workspace for `ttl_days`. Returns the invite and its token, which is not kept anywhere.
+#[instrument(name = "db.create_invite", skip(tx))]
+pub async fn create_invite(
+    tx: &Transaction<'_>,
+    workspace_id: i32,
+    email: &str,
+    role: Role,
+    ttl_days: i32,
+) -> Result<(Invite, String), DbError> {
+    let token = format!("inv_{}", Uuid::new_v4().simple());
+    let query = format!(
+        "INSERT INTO workspace_invites (workspace_id, email, role, token_hash, expires_at) \
+         VALUES ($1, $2, $3, $4, LOCALTIMESTAMP + make_interval(days => $5)) RETURNING {}",
+        Invite::COLUMNS
+    );
+    let row = tx
+        .query_one(&query, &[&workspace_id, &email, &role.as_str(), &hash_key(&token), &ttl_days])
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok((Invite::from_row(&row), token))
+}
+
+/// List the invites of a workspace that can still be accepted, newest first.
+#[instrument(name = "db.list_invites", skip(client))]
+pub async fn list_invites(client: &Client, workspace_id: i32) -> Result<Vec<Invite>, DbError> {
+    let query = format!(
+        "SELECT {} FROM workspace_invites \
+         WHERE workspace_id = $1 AND accepted_at IS NULL AND expires_at > LOCALTIMESTAMP ORDER BY id DESC",
+        Invite::COLUMNS
+    );
+    let rows = client.query(&query, &[&workspace_id]).await.map_err(|_| DbError::DatabaseError)?;
+    Ok(rows.iter().map(Invite::from_row).collect())
+}
+
+/// Accept an invite, adding its email to the workspace with the invited role.
+///
+/// Someone who is already a member keeps their current role. Returns `None` if the token
+/// is unknown, expired or already used.
+#[instrument(name = "db.accept_invite", skip_all)]
+pub async fn accept_invite(tx: &Transaction<'_>, token: &str) -> Result<Option<Member>, DbError> {
+    let query = format!(
+        "UPDATE workspace_invites SET accepted_at = LOCALTIMESTAMP \
+         WHERE token_hash = $1 AND accepted_at IS NULL AND expires_at > LOCALTIMESTAMP RETURNING {}",
+        Invite::COLUMNS
+    );
+    let Some(row) = tx.query_opt(&query, &[&hash_key(token)]).await.map_err(|_| DbError::DatabaseError)? else {
+        return Ok(None);
+    };
+    let invite = Invite::from_row(&row);
+    tx.execute(
+        "INSERT INTO workspace_members (workspace_id, email, role) VALUES ($1, $2, $3) \
+         ON CONFLICT (workspace_id, email) DO NOTHING",
+        &[&invite.workspace_id, &invite.email, &invite.role.as_str()],
+    )
+    .await
+    .map_err(|_| DbError::DatabaseError)?;
+    let query = format!(
+        "SELECT {} FROM workspace_members WHERE workspace_id = $1 AND email = $2",
+        Member::COLUMNS
+    );
+    let row = tx
+        .query_one(&query, &[&invite.workspace_id, &invite.email])
+        .await
+        .map_err(|_| DbError::DatabaseError)?;
+    Ok(Some(Member::from_row(&row)))
+}
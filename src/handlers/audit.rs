@@ -0,0 +1,24 @@
+use crate::config::audit::list_audit_events;
+use crate::handlers::links::get_client;
+use crate::models::{ApiKey, AuditQuery, Scope};
+use crate::utils::audit::audit_limit;
+use crate::utils::auth::require;
+use deadpool_postgres::Pool;
+use log::info;
+use tracing::instrument;
+use warp::reply::json;
+use warp::{Rejection, Reply};
+
+/// Handler to list the audit events of the API key's workspace, newest first.
+///
+/// Pass the ID of the last event received as `before` to get the next page.
+#[instrument(name = "get_audit_events", skip_all)]
+pub async fn get_audit_events(api_key: ApiKey, query: AuditQuery, db_pool: Pool) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ReadAudit)?;
+    let client = get_client(&db_pool).await?;
+    let events = list_audit_events(&client, Some(api_key.workspace_id), &query, audit_limit(query.limit))
+        .await
+        .map_err(warp::reject::custom)?;
+    info!("Listing {} audit events for API key {}", events.len(), api_key.id);
+    Ok(json(&events))
+}
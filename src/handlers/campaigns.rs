@@ -0,0 +1,32 @@
+use crate::config::campaigns::{list_campaigns, save_campaign};
+use crate::handlers::links::get_client;
+use crate::models::{ApiKey, CampaignRequest, Scope};
+use crate::utils::auth::require;
+use crate::utils::validate::{validate_campaign_name, validate_utm};
+use deadpool_postgres::Pool;
+use log::info;
+use tracing::instrument;
+use warp::reply::json;
+use warp::{Rejection, Reply};
+
+/// Handler to list the campaign presets of the API key's workspace.
+#[instrument(name = "get_campaigns", skip_all)]
+pub async fn get_campaigns(api_key: ApiKey, db_pool: Pool) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ReadLinks)?;
+    let client = get_client(&db_pool).await?;
+    let campaigns = list_campaigns(&client, Some(api_key.workspace_id)).await.map_err(warp::reject::custom)?;
+    info!("Listing campaigns for API key {}", api_key.id);
+    Ok(json(&campaigns))
+}
+
+/// Handler to save a campaign preset in the API key's workspace, replacing the one with the same name.
+#[instrument(name = "post_campaign", skip_all)]
+pub async fn post_campaign(api_key: ApiKey, body: CampaignRequest, db_pool: Pool) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::WriteLinks)?;
+    validate_campaign_name(&body.name).map_err(warp::reject::custom)?;
+    let utm = validate_utm(body.utm).map_err(warp::reject::custom)?;
+    let client = get_client(&db_pool).await?;
+    let campaign = save_campaign(&client, Some(api_key.workspace_id), &body.name, &utm).await.map_err(warp::reject::custom)?;
+    info!("Saved campaign {} for API key {}", campaign.name, api_key.id);
+    Ok(json(&campaign))
+}
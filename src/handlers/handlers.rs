@@ -1,13 +1,38 @@
-use crate::config::db::{get_original_url, insert_shortlink, DbError};
-use crate::models::{ShortenRequest, ShortenResponse};
-use crate::utils::validate::validate_link;
+use crate::config::db::{
+    consume_link, find_owned_link, get_destination, get_link, get_password_hash, insert_shortlink, DbError,
+};
+use crate::models::{
+    ApiKey, AuditAction, Availability, Destination, Domain, Link, Routing, Scope, ShortenRequest, ShortenResponse,
+    UnlockForm,
+};
+use crate::config::clicks::insert_click;
+use crate::config::redis::{cache_destination, get_cached_destination};
+use crate::utils::audit::{anonymous_actor, api_key_actor, link_event, record};
+use crate::handlers::links::{begin, commit};
+use crate::utils::auth::require;
+use crate::utils::idempotency::Idempotency;
+use crate::utils::passthrough::{pass_through, Forwarded};
+use crate::utils::template::render_template;
+use crate::utils::utm::tag_url;
+use crate::utils::password::{
+    claim_password_attempt, is_unlocked, release_password_attempt, unlock_cookie, verify_password,
+};
+use crate::utils::request_context::record_short_code;
+use crate::utils::short_code::{generate_short_code, short_url};
+use crate::utils::validate::prepare_link;
+use crate::utils::variant::variant_cookie;
+use crate::utils::visitor::Visitor;
+use crate::views::link_unavailable::{link_ended, link_removed, link_upcoming};
+use crate::views::index::index;
+use crate::views::link_used::link_used;
 use crate::views::not_found::not_found;
+use crate::views::password::password_prompt;
+use crate::views::preview::{link_preview, Continue, LinkPreview};
 use deadpool_postgres::Pool;
 use log::{error, info};
-use std::env;
-use uuid::Uuid;
-use warp::http::StatusCode;
-use warp::reply::{json, with_status};
+use warp::http::header::{LOCATION, SET_COOKIE};
+use warp::http::{HeaderValue, StatusCode};
+use warp::reply::{json, with_status, Response};
 use warp::{Rejection, Reply};
 use qrcode::QrCode;
 use image::Luma;
@@ -15,121 +40,572 @@ use image::codecs::png::PngEncoder;
 use image::ImageEncoder;
 use base64::engine::general_purpose::STANDARD as base64_std;
 use base64::Engine as _;
+use chrono::Utc;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use std::env;
 use std::io::Cursor;
-use deadpool_redis::Pool as RedisPool;
-use deadpool_redis::redis::AsyncCommands; 
-use deadpool_redis::redis::RedisResult;
+use std::net::SocketAddr;
+use tracing::{info_span, instrument, Instrument};
+use deadpool_redis::{Connection as RedisConnection, Pool as RedisPool};
 
 /// Handler to shorten a URL.
-pub async fn shorten_url(body: ShortenRequest, db_pool: Pool) -> Result<impl Reply, Rejection> {
-    let client = db_pool
+///
+/// With an `Idempotency-Key` header, a retried request replays the first response
+/// instead of creating another link. Without a `domain` in the body, links are created
+/// on the domain the request was made on. Links shortened with an API key belong to its workspace.
+/// New links are recorded in the audit log.
+#[instrument(name = "shorten_url", skip_all)]
+pub async fn shorten_url(
+    domain: Option<Domain>,
+    mut body: ShortenRequest,
+    idempotency_key: Option<String>,
+    api_key: Option<ApiKey>,
+    addr: Option<SocketAddr>,
+    db_pool: Pool,
+    redis_pool: RedisPool,
+) -> Result<Response, Rejection> {
+    if let Some(api_key) = &api_key {
+        require(api_key, Scope::WriteLinks)?;
+    }
+    if body.domain.is_none() {
+        body.domain = domain.map(|domain| domain.host);
+    }
+    let Some(idempotency_key) = idempotency_key else {
+        let response = create_short_link(body, api_key, addr, db_pool).await?;
+        return Ok(json(&response).into_response());
+    };
+
+    let owner_key_id = api_key.as_ref().map(|key| key.id);
+    let idempotency = Idempotency::new(owner_key_id, addr.map(|addr| addr.ip()), &idempotency_key, &body)?;
+    let mut redis_conn = get_redis(&redis_pool).await?;
+    if let Some(replay) = idempotency.begin(&mut redis_conn).await? {
+        return Ok(replay);
+    }
+
+    match create_short_link(body, api_key, addr, db_pool).await {
+        Ok(response) => {
+            idempotency.complete(&mut redis_conn, serde_json::to_string(&response).unwrap()).await;
+            Ok(json(&response).into_response())
+        }
+        Err(rejection) => {
+            idempotency.abandon(&mut redis_conn).await;
+            Err(rejection)
+        }
+    }
+}
+
+/// Whether anonymous requests reuse an existing anonymous link, from `REUSE_ANONYMOUS_LINKS`.
+fn reuse_anonymous_links() -> bool {
+    env::var("REUSE_ANONYMOUS_LINKS").is_ok_and(|v| v == "true" || v == "1")
+}
+
+/// Create a link for the requested URL, or return the owner's existing one when they opted in.
+async fn create_short_link(
+    body: ShortenRequest,
+    api_key: Option<ApiKey>,
+    addr: Option<SocketAddr>,
+    db_pool: Pool,
+) -> Result<ShortenResponse, Rejection> {
+    let mut client = db_pool
         .get()
+        .instrument(info_span!("db.pool.get"))
         .await
         .map_err(|e| {
             error!("Failed to get DB client: {:?}", e);
             warp::reject::custom(DbError::DatabaseError)
         })?;
-    let validated_url = validate_link(body.url.clone())?;
-    let short_code = Uuid::new_v4().to_string()[..8].to_string();
-    insert_shortlink(&client, &short_code, &validated_url)
-        .await
-        .map_err(|e| {
-            error!("Failed to insert shortlink: {:?}", e);
-            warp::reject::custom(DbError::DatabaseError)
-        })?;
-    let base_url = env::var("BASE_URL").expect("BASE_URL is not set in .env");
-    let short_url = format!("{}/{}", base_url, short_code);
+    let url = body.url.clone();
+    let workspace_id = api_key.as_ref().map(|key| key.workspace_id);
+    let link = prepare_link(body, workspace_id, &client).await.map_err(warp::reject::custom)?;
+    let owner_key_id = api_key.as_ref().map(|key| key.id);
+    let reuse = link.is_reusable()
+        && api_key.as_ref().map_or_else(reuse_anonymous_links, |key| key.reuse_existing);
 
-    // Generate QR code from short_url
-    let code = QrCode::new(&short_url).unwrap();
-    let image = code.render::<Luma<u8>>().build();
-    let mut buffer = Cursor::new(Vec::new());
-    PngEncoder::new(&mut buffer).write_image(&image, image.width(), image.height(), image::ExtendedColorType::L8).unwrap();
-    let qr_base64 = base64_std.encode(buffer.into_inner());
+    let existing = if reuse {
+        find_owned_link(&client, owner_key_id, link.domain_id(), &link.original_url)
+            .await
+            .map_err(warp::reject::custom)?
+    } else {
+        None
+    };
+    let short_code = match existing {
+        Some(short_code) => {
+            info!("Reusing short code {} for {}", short_code, url);
+            short_code
+        }
+        None => {
+            let short_code = generate_short_code();
+            let tx = begin(&mut client).await?;
+            let created = insert_shortlink(&tx, &short_code, &link, owner_key_id, workspace_id)
+                .await
+                .map_err(|e| {
+                    error!("Failed to insert shortlink: {:?}", e);
+                    warp::reject::custom(DbError::DatabaseError)
+                })?;
+            let actor = match &api_key {
+                Some(api_key) => api_key_actor(api_key, addr),
+                None => anonymous_actor(addr),
+            };
+            let event = link_event(AuditAction::LinkCreate, None, Some(&created));
+            record(&tx, &actor, &[event]).await.map_err(warp::reject::custom)?;
+            commit(tx).await?;
+            short_code
+        }
+    };
+    record_short_code(&short_code);
+    let short_url = short_url(link.domain.as_ref().map(|domain| domain.host.as_str()), &short_code);
 
     let response = ShortenResponse {
+        qr_code: qr_code(&short_url),
         short_url,
-        qr_code: format!("data:image/png;base64,{}", qr_base64),
     };
-    info!("Shortened URL: {} -> {}", body.url, response.short_url);
-    Ok(warp::reply::json(&response))
+    info!("Shortened URL: {} -> {}", url, response.short_url);
+    Ok(response)
+}
+
+/// QR code of a short URL, as a PNG `data:` URL.
+fn qr_code(short_url: &str) -> String {
+    let code = QrCode::new(short_url).unwrap();
+    let image = code.render::<Luma<u8>>().build();
+    let mut buffer = Cursor::new(Vec::new());
+    PngEncoder::new(&mut buffer).write_image(&image, image.width(), image.height(), image::ExtendedColorType::L8).unwrap();
+    format!("data:image/png;base64,{}", base64_std.encode(buffer.into_inner()))
 }
 
 /// Handler to redirect a shortened URL to the original URL.
+///
+/// Password-protected links show a password prompt unless the visitor already unlocked them.
+/// Single-use links are consumed by the first visit that gets redirected. Outside their
+/// activation window, links show a page or send visitors to the configured fallback.
+/// While a destination fails health checks, visitors go to the link's fallback URL.
+/// Targeting rules matching the visitor's `User-Agent` or location take precedence over the destination,
+/// and links with language rules send visitors to the language negotiated from `Accept-Language`.
+/// Links with variants keep each visitor on one variant with a cookie. Depending on the link's
+/// passthrough mode, the query string and the path after the short code are forwarded; the link's
+/// UTM parameters take precedence over forwarded ones. Placeholders such as `{1}`, `{path}` and
+/// `{query.id}` in the destination are filled from the visited URL. Short codes are looked up
+/// on the domain of the `Host` header, so the same code can lead elsewhere on each domain.
+/// Links with the interstitial option, or on a domain with it, show the preview page instead of
+/// redirecting; its Continue button posts back to the visited URL, which redirects.
+#[instrument(name = "redirect_url", skip(domain, forwarded, visitor, unlock_cookie, db_pool, redis_pool))]
 pub async fn redirect_url(
     code: String,
+    domain: Option<Domain>,
+    forwarded: Forwarded,
+    visitor: Visitor,
+    unlock_cookie: Option<String>,
     db_pool: Pool,
     redis_pool: RedisPool,
 ) -> Result<Box<dyn Reply>, Rejection> {
-    let mut redis_conn = redis_pool.get().await.map_err(|e| {
-        error!("Failed to get Redis connection: {:?}", e);
-        warp::reject::custom(DbError::DatabaseError)
-    })?;
+    record_short_code(&code);
+    let domain_id = domain.as_ref().map(|domain| domain.id);
+    let mut redis_conn = get_redis(&redis_pool).await?;
+    let Some(destination) = resolve_destination(domain_id, &code, &db_pool, &mut redis_conn).await? else {
+        return link_not_found(&code, domain.as_ref()).await;
+    };
+    // Only links using the path after the short code answer on longer paths
+    if !forwarded.path.is_empty() && !destination.accepts_path() {
+        return link_not_found(&code, domain.as_ref()).await;
+    }
+    if destination.deleted {
+        return link_deleted(&code).await;
+    }
+    if destination.consumed {
+        return link_already_used(&code).await;
+    }
+    if let Some(reply) = outside_window(&code, &destination).await? {
+        return Ok(reply);
+    }
 
-    // Add debug logging to track Redis operations
-    info!("Attempting to fetch from Redis for code: {}", code);
-    
-    let redis_key = format!("short:{}", code);
-    
-    // Try Redis get with explicit error logging
-    let redis_result: RedisResult<Option<String>> = redis_conn.get(&redis_key).await;
-    match redis_result {
-        Ok(Some(original_url)) => {
-            info!("Successfully retrieved URL from Redis: {}", original_url);
-            match original_url.parse::<warp::http::Uri>() {
-                Ok(uri) => return Ok(Box::new(warp::redirect::temporary(uri))),
-                Err(e) => {
-                    error!("Failed to parse URI from Redis: {:?}", e);
-                    return Err(warp::reject::custom(DbError::DatabaseError));
-                }
-            }
+    if destination.password_protected {
+        if !has_unlocked(domain_id, &code, unlock_cookie.as_deref(), &db_pool).await? {
+            info!("Short code {} is password protected, displaying prompt", code);
+            let response = password_prompt(None).await?;
+            return Ok(Box::new(with_status(response.into_response(), StatusCode::UNAUTHORIZED)));
         }
-        Ok(None) => {
-            info!("No URL found in Redis for key: {}", redis_key);
+    } else if destination.interstitial || domain.as_ref().is_some_and(|domain| domain.interstitial) {
+        info!("Short code {} shows an interstitial, displaying preview", code);
+        return preview_page(&code, domain.as_ref(), &destination, &forwarded, &visitor, Continue::Post(""), &db_pool).await;
+    }
+
+    let (target, routing) = destination.route(&visitor);
+    let target = render_template(&target, &forwarded);
+    let target = pass_through(&tag_url(&target, &destination.utm), destination.passthrough, &forwarded);
+    let location = parse_destination(&target)?;
+    if !claim_single_use(domain_id, &code, &destination, &db_pool).await? {
+        return link_already_used(&code).await;
+    }
+    info!("Redirecting short code {} to {}", code, target);
+    let cookies = assigned_variant_cookie(&code, &visitor, &routing);
+    record_click(db_pool, domain_id, code, visitor, routing);
+    Ok(with_cookies(redirect(StatusCode::TEMPORARY_REDIRECT, location), cookies))
+}
+
+/// Handler for the password prompt of a protected link.
+///
+/// A correct password redirects and sets a signed cookie so the visitor is not asked again
+/// for a while. Failed attempts are limited per short code.
+#[instrument(name = "unlock_link", skip(domain, forwarded, form, visitor, db_pool, redis_pool))]
+pub async fn unlock_link(
+    code: String,
+    domain: Option<Domain>,
+    forwarded: Forwarded,
+    form: UnlockForm,
+    visitor: Visitor,
+    db_pool: Pool,
+    redis_pool: RedisPool,
+) -> Result<Box<dyn Reply>, Rejection> {
+    record_short_code(&code);
+    let domain_id = domain.as_ref().map(|domain| domain.id);
+    let mut redis_conn = get_redis(&redis_pool).await?;
+    let Some(destination) = resolve_destination(domain_id, &code, &db_pool, &mut redis_conn).await? else {
+        return link_not_found(&code, domain.as_ref()).await;
+    };
+    // Only links using the path after the short code answer on longer paths
+    if !forwarded.path.is_empty() && !destination.accepts_path() {
+        return link_not_found(&code, domain.as_ref()).await;
+    }
+    if destination.deleted {
+        return link_deleted(&code).await;
+    }
+    if destination.consumed {
+        return link_already_used(&code).await;
+    }
+    if let Some(reply) = outside_window(&code, &destination).await? {
+        return Ok(reply);
+    }
+    let (target, routing) = destination.route(&visitor);
+    let target = render_template(&target, &forwarded);
+    let target = pass_through(&tag_url(&target, &destination.utm), destination.passthrough, &forwarded);
+    let location = parse_destination(&target)?;
+    let password_hash = match destination.password_protected {
+        true => lookup_password_hash(domain_id, &code, &db_pool).await?,
+        false => None,
+    };
+    let Some(password_hash) = password_hash else {
+        if !claim_single_use(domain_id, &code, &destination, &db_pool).await? {
+            return link_already_used(&code).await;
         }
-        Err(e) => {
-            error!("Redis error: {:?}", e);
+        let cookies = assigned_variant_cookie(&code, &visitor, &routing);
+        record_click(db_pool, domain_id, code, visitor, routing);
+        return Ok(with_cookies(redirect(StatusCode::SEE_OTHER, location), cookies));
+    };
+
+    let allowed = claim_password_attempt(&mut redis_conn, domain_id, &code).await.map_err(|e| {
+        error!("Failed to count password attempt: {:?}", e);
+        warp::reject::custom(DbError::DatabaseError)
+    })?;
+    if !allowed {
+        info!("Too many failed password attempts for short code {}", code);
+        let response = password_prompt(Some("Too many failed attempts. Try again later.")).await?;
+        return Ok(Box::new(with_status(response.into_response(), StatusCode::TOO_MANY_REQUESTS)));
+    }
+
+    if !verify_password(form.password, password_hash.clone()).await {
+        info!("Wrong password for short code {}", code);
+        let response = password_prompt(Some("Incorrect password.")).await?;
+        return Ok(Box::new(with_status(response.into_response(), StatusCode::UNAUTHORIZED)));
+    }
+
+    if let Err(e) = release_password_attempt(&mut redis_conn, domain_id, &code).await {
+        error!("Failed to give back password attempt: {:?}", e);
+    }
+    if !claim_single_use(domain_id, &code, &destination, &db_pool).await? {
+        return link_already_used(&code).await;
+    }
+    info!("Unlocked short code {}, redirecting to {}", code, target);
+    let cookies = std::iter::once(unlock_cookie(&code, &password_hash))
+        .chain(assigned_variant_cookie(&code, &visitor, &routing));
+    record_click(db_pool, domain_id, code, visitor, routing);
+    Ok(with_cookies(redirect(StatusCode::SEE_OTHER, location), cookies))
+}
+
+/// Handler for the preview page of a link, at `/{code}+` or `/{code}/preview`.
+///
+/// Shows where the link leads without following it: no click is counted and single-use links
+/// are not used up. Password-protected links keep their destination secret.
+#[instrument(name = "preview_link", skip(domain, visitor, db_pool, redis_pool))]
+pub async fn preview_link(
+    code: String,
+    domain: Option<Domain>,
+    visitor: Visitor,
+    db_pool: Pool,
+    redis_pool: RedisPool,
+) -> Result<Box<dyn Reply>, Rejection> {
+    record_short_code(&code);
+    let domain_id = domain.as_ref().map(|domain| domain.id);
+    let mut redis_conn = get_redis(&redis_pool).await?;
+    let Some(destination) = resolve_destination(domain_id, &code, &db_pool, &mut redis_conn).await? else {
+        return link_not_found(&code, domain.as_ref()).await;
+    };
+    if destination.deleted {
+        return link_deleted(&code).await;
+    }
+    if destination.consumed {
+        return link_already_used(&code).await;
+    }
+    if let Some(reply) = outside_window(&code, &destination).await? {
+        return Ok(reply);
+    }
+    let link_path = format!("/{}", utf8_percent_encode(&code, NON_ALPHANUMERIC));
+    let continue_to = match destination.password_protected {
+        true => Continue::Get(&link_path),
+        false => Continue::Post(&link_path),
+    };
+    preview_page(&code, domain.as_ref(), &destination, &Forwarded::default(), &visitor, continue_to, &db_pool).await
+}
+
+/// The preview page of a link, showing where it would send this visitor.
+async fn preview_page(
+    code: &str,
+    domain: Option<&Domain>,
+    destination: &Destination,
+    forwarded: &Forwarded,
+    visitor: &Visitor,
+    continue_to: Continue<'_>,
+    db_pool: &Pool,
+) -> Result<Box<dyn Reply>, Rejection> {
+    let client = db_pool.get().instrument(info_span!("db.pool.get")).await.map_err(|e| {
+        error!("DB connection error: {:?}", e);
+        warp::reject::custom(DbError::DatabaseError)
+    })?;
+    let Some(link) = get_link(&client, domain.map(|domain| domain.id), code).await.map_err(warp::reject::custom)? else {
+        return link_not_found(code, domain).await;
+    };
+    let protected = destination.password_protected;
+    let (target, _) = destination.route(visitor);
+    let target = render_template(&target, forwarded);
+    let target = pass_through(&tag_url(&target, &destination.utm), destination.passthrough, forwarded);
+    let short_url = short_url(domain.map(|domain| domain.host.as_str()), code);
+    let qr_code = qr_code(&short_url);
+    let response = link_preview(LinkPreview {
+        short_url: &short_url,
+        destination: (!protected).then_some(target.as_str()),
+        title: if protected { None } else { link.metadata.page_title.as_deref().or(link.title.as_deref()) },
+        created_at: link.created_at,
+        qr_code: &qr_code,
+        continue_to,
+    })
+    .await?;
+    Ok(Box::new(response))
+}
+
+/// Look up where a short code leads on a domain, from Redis if cached, otherwise from the database.
+async fn resolve_destination(
+    domain_id: Option<i32>,
+    code: &str,
+    db_pool: &Pool,
+    redis_conn: &mut RedisConnection,
+) -> Result<Option<Destination>, Rejection> {
+    match get_cached_destination(redis_conn, domain_id, code).await {
+        Ok(Some(destination)) => {
+            info!("Successfully retrieved URL from Redis: {}", destination.original_url);
+            return Ok(Some(destination));
         }
+        Ok(None) => info!("No URL found in Redis for short code: {}", code),
+        Err(e) => error!("Redis error: {:?}", e),
     }
 
     // If Redis fails, proceed with database lookup
-    let client = db_pool.get().await.map_err(|e| {
+    let client = db_pool.get().instrument(info_span!("db.pool.get")).await.map_err(|e| {
+        error!("DB connection error: {:?}", e);
+        warp::reject::custom(DbError::DatabaseError)
+    })?;
+    let destination = get_destination(&client, domain_id, code).await.map_err(|e| {
+        error!("Database error: {:?}", e);
+        warp::reject::custom(DbError::DatabaseError)
+    })?;
+
+    // Single-use links are never cached, so a consumed link cannot be served from Redis
+    if let Some(destination) = destination.as_ref().filter(|d| !d.single_use) {
+        match cache_destination(redis_conn, domain_id, code, destination).await {
+            Ok(()) => info!("Successfully cached in Redis: {}", code),
+            Err(e) => error!("Failed to cache in Redis: {:?}", e),
+        }
+    }
+    Ok(destination)
+}
+
+/// Read the password hash of a short code from the database, which is the only place it is kept.
+async fn lookup_password_hash(domain_id: Option<i32>, code: &str, db_pool: &Pool) -> Result<Option<String>, Rejection> {
+    let client = db_pool.get().instrument(info_span!("db.pool.get")).await.map_err(|e| {
         error!("DB connection error: {:?}", e);
         warp::reject::custom(DbError::DatabaseError)
     })?;
+    get_password_hash(&client, domain_id, code).await.map_err(|e| {
+        error!("Database error: {:?}", e);
+        warp::reject::custom(DbError::DatabaseError)
+    })
+}
 
-    match get_original_url(&client, &code).await {
-        Ok(Some(original_url)) => {
-            let redis_set_result: RedisResult<()> = redis_conn
-                .set_ex(&redis_key, &original_url, 3600)
-                .await;
-            
-            match redis_set_result {
-                Ok(()) => info!("Successfully cached in Redis: {}", redis_key),
-                Err(e) => error!("Failed to cache in Redis: {:?}", e),
+/// Whether the visitor's unlock cookie opens a password-protected link. Without a cookie the
+/// database is not asked for the hash.
+async fn has_unlocked(
+    domain_id: Option<i32>,
+    code: &str,
+    cookie: Option<&str>,
+    db_pool: &Pool,
+) -> Result<bool, Rejection> {
+    if cookie.is_none() {
+        return Ok(false);
+    }
+    let password_hash = lookup_password_hash(domain_id, code, db_pool).await?;
+    Ok(password_hash.is_some_and(|password_hash| is_unlocked(code, &password_hash, cookie)))
+}
+
+/// The reply for a link outside its activation window, or `None` while it is active.
+async fn outside_window(code: &str, destination: &Destination) -> Result<Option<Box<dyn Reply>>, Rejection> {
+    let reply: Box<dyn Reply> = match destination.availability(Utc::now()) {
+        Availability::Active => return Ok(None),
+        Availability::Upcoming(active_from) => match &destination.upcoming_url {
+            Some(url) => fallback_redirect(code, url)?,
+            None => {
+                info!("Short code {} is not active yet, displaying page", code);
+                let response = link_upcoming(active_from).await?;
+                Box::new(with_status(response.into_response(), StatusCode::NOT_FOUND))
+            }
+        },
+        Availability::Ended => match &destination.ended_url {
+            Some(url) => fallback_redirect(code, url)?,
+            None => {
+                info!("Short code {} has ended, displaying page", code);
+                let response = link_ended().await?;
+                Box::new(with_status(response.into_response(), StatusCode::GONE))
             }
+        },
+    };
+    Ok(Some(reply))
+}
 
-            match original_url.parse::<warp::http::Uri>() {
-                Ok(uri) => {
-                    info!("Redirecting short code {} to {}", code, original_url);
-                    return Ok(Box::new(warp::redirect::temporary(uri)));
-                }
+fn fallback_redirect(code: &str, url: &str) -> Result<Box<dyn Reply>, Rejection> {
+    info!("Short code {} is outside its activation window, redirecting to {}", code, url);
+    Ok(Box::new(redirect(StatusCode::TEMPORARY_REDIRECT, parse_destination(url)?)))
+}
+
+/// Consume a single-use link, recording it as disabled. Returns `false` if another visit already used it up.
+async fn claim_single_use(
+    domain_id: Option<i32>,
+    code: &str,
+    destination: &Destination,
+    db_pool: &Pool,
+) -> Result<bool, Rejection> {
+    if !destination.single_use {
+        return Ok(true);
+    }
+    let mut client = db_pool.get().instrument(info_span!("db.pool.get")).await.map_err(|e| {
+        error!("DB connection error: {:?}", e);
+        warp::reject::custom(DbError::DatabaseError)
+    })?;
+    let tx = begin(&mut client).await?;
+    let consumed = consume_link(&tx, domain_id, code).await.map_err(|e| {
+        error!("Database error: {:?}", e);
+        warp::reject::custom(DbError::DatabaseError)
+    })?;
+    let Some(link) = consumed else {
+        return Ok(false);
+    };
+    let before = Link { consumed_at: None, ..link.clone() };
+    let event = link_event(AuditAction::LinkDisable, Some(&before), Some(&link));
+    record(&tx, &anonymous_actor(None), &[event]).await.map_err(warp::reject::custom)?;
+    commit(tx).await?;
+    Ok(true)
+}
+
+async fn get_redis(redis_pool: &RedisPool) -> Result<RedisConnection, Rejection> {
+    redis_pool.get().instrument(info_span!("redis.pool.get")).await.map_err(|e| {
+        error!("Failed to get Redis connection: {:?}", e);
+        warp::reject::custom(DbError::DatabaseError)
+    })
+}
+
+fn parse_destination(original_url: &str) -> Result<HeaderValue, Rejection> {
+    // `Uri` has no fragment, so it only validates the part before `#`
+    let without_fragment = original_url.split('#').next().unwrap_or_default();
+    without_fragment
+        .parse::<warp::http::Uri>()
+        .map_err(|e| e.to_string())
+        .and_then(|_| HeaderValue::from_str(original_url).map_err(|e| e.to_string()))
+        .map_err(|e| {
+            error!("Failed to parse destination URI: {}", e);
+            warp::reject::custom(DbError::DatabaseError)
+        })
+}
+
+/// A redirect to `location`, which unlike `warp::redirect` keeps the URL fragment.
+fn redirect(status: StatusCode, location: HeaderValue) -> Response {
+    let mut response = status.into_response();
+    response.headers_mut().insert(LOCATION, location);
+    response
+}
+
+async fn link_already_used(code: &str) -> Result<Box<dyn Reply>, Rejection> {
+    info!("Short code {} was already used, displaying used page", code);
+    let response = link_used().await?;
+    Ok(Box::new(warp::reply::with_status(response.into_response(), StatusCode::GONE)))
+}
+
+async fn link_deleted(code: &str) -> Result<Box<dyn Reply>, Rejection> {
+    info!("Short code {} is in the trash, displaying removed page", code);
+    let response = link_removed().await?;
+    Ok(Box::new(warp::reply::with_status(response.into_response(), StatusCode::GONE)))
+}
+
+/// The 404 page, or a redirect to the domain's own 404 destination.
+async fn link_not_found(code: &str, domain: Option<&Domain>) -> Result<Box<dyn Reply>, Rejection> {
+    if let Some(url) = domain.and_then(|domain| domain.not_found_url.as_deref()) {
+        info!("Short code {} not found, redirecting to {}", code, url);
+        return Ok(Box::new(redirect(StatusCode::FOUND, parse_destination(url)?)));
+    }
+    info!("Short code {} not found, displaying 404 page", code);
+    let response = not_found().await?;
+    Ok(Box::new(warp::reply::with_status(response.into_response(), StatusCode::NOT_FOUND)))
+}
+
+/// Cookie keeping the visitor on the variant they were just sent to, unless they already have it.
+fn assigned_variant_cookie(code: &str, visitor: &Visitor, routing: &Routing) -> Option<String> {
+    let variant = routing.variant.as_deref()?;
+    (visitor.variant.as_deref() != Some(variant)).then(|| variant_cookie(code, variant))
+}
+
+/// Add `Set-Cookie` headers to a reply.
+fn with_cookies(reply: impl Reply, cookies: impl IntoIterator<Item = String>) -> Box<dyn Reply> {
+    let mut response = reply.into_response();
+    for cookie in cookies {
+        match HeaderValue::from_str(&cookie) {
+            Ok(value) => {
+                response.headers_mut().append(SET_COOKIE, value);
+            }
+            Err(e) => error!("Invalid cookie {:?}: {:?}", cookie, e),
+        }
+    }
+    Box::new(response)
+}
+
+/// Handler for `/`: the index page, or a redirect to the domain's own root destination.
+pub async fn home(domain: Option<Domain>) -> Result<Box<dyn Reply>, Rejection> {
+    match domain.and_then(|domain| domain.root_url) {
+        Some(url) => Ok(Box::new(redirect(StatusCode::FOUND, parse_destination(&url)?))),
+        None => Ok(Box::new(index().await?)),
+    }
+}
+
+/// Record a click in the background so the redirect is not delayed.
+fn record_click(db_pool: Pool, domain_id: Option<i32>, code: String, visitor: Visitor, routing: Routing) {
+    tokio::spawn(
+        async move {
+            let client = match db_pool.get().await {
+                Ok(client) => client,
                 Err(e) => {
-                    error!("Failed to parse URI from database: {:?}", e);
-                    return Err(warp::reject::custom(DbError::DatabaseError));
+                    error!("Failed to get DB client to record click: {:?}", e);
+                    return;
                 }
+            };
+            if let Err(e) = insert_click(&client, domain_id, &code, &visitor, &routing).await {
+                error!("Failed to record click for {}: {:?}", code, e);
             }
         }
-        Ok(None) => {
-            info!("Short code {} not found, displaying 404 page", code);
-            let response = not_found().await?;
-            return Ok(Box::new(warp::reply::with_status(response.into_response(), StatusCode::NOT_FOUND)));
-        }
-        Err(e) => {
-            error!("Database error: {:?}", e);
-            return Err(warp::reject::custom(DbError::DatabaseError));
-        }
-    }
+        .in_current_span(),
+    );
 }
 
 /// Handler to manage rejections and errors.
@@ -138,24 +614,105 @@ pub async fn handle_rejection(err: Rejection) -> Result<Box<dyn Reply>, Rejectio
         info!("Route not found, displaying 404 page");
         let response = not_found().await?;
         Ok(Box::new(warp::reply::with_status(response.into_response(), StatusCode::NOT_FOUND)))
-    } else if let Some(_) = err.find::<warp::body::BodyDeserializeError>() {
+    } else if err.find::<warp::body::BodyDeserializeError>().is_some() {
         error!("Invalid request body, redirecting to /");
         Ok(Box::new(warp::redirect::temporary(
             "/".parse::<warp::http::Uri>().unwrap(),
         )))
-    } else if let Some(_) = err.find::<crate::utils::validate::InvalidLink>() {
+    } else if err.find::<crate::utils::validate::InvalidLink>().is_some() {
         error!("Invalid link provided");
         Ok(Box::new(with_status(
             json(&serde_json::json!({ "error": "Invalid link provided" })),
             StatusCode::BAD_REQUEST,
         )))
-    } else if let Some(_) = err.find::<crate::utils::rate_limit::TooManyRequests>() {
+    } else if err.find::<crate::utils::rate_limit::TooManyRequests>().is_some() {
         error!("Too many requests");
         Ok(Box::new(with_status(
             json(&serde_json::json!({ "error": "Too many requests, slow down!" })),
             StatusCode::TOO_MANY_REQUESTS,
         )))
-    } else if let Some(_) = err.find::<crate::config::db::DbError>() {
+    } else if err.find::<warp::reject::InvalidQuery>().is_some() {
+        error!("Invalid query string");
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": "Invalid query string" })),
+            StatusCode::BAD_REQUEST,
+        )))
+    } else if err.find::<crate::utils::auth::Unauthorized>().is_some() {
+        error!("Missing or invalid API key");
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": "Missing or invalid API key" })),
+            StatusCode::UNAUTHORIZED,
+        )))
+    } else if let Some(e) = err.find::<crate::utils::auth::Forbidden>() {
+        error!("API key lacks the {} permission", e.scope.as_str());
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": format!("This API key lacks the {} permission", e.scope.as_str()) })),
+            StatusCode::FORBIDDEN,
+        )))
+    } else if let Some(e) = err.find::<crate::utils::workspace::WorkspaceError>() {
+        error!("Workspace request failed: {}", e);
+        Ok(Box::new(with_status(json(&serde_json::json!({ "error": e.to_string() })), e.status())))
+    } else if let Some(crate::utils::validate::LinkError::Database(_)) = err.find() {
+        error!("Database error occurred");
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": "Database error occurred" })),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )))
+    } else if let Some(e) = err.find::<crate::utils::validate::LinkError>() {
+        error!("Invalid link request: {}", e);
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": e.to_string() })),
+            StatusCode::BAD_REQUEST,
+        )))
+    } else if err.find::<crate::utils::idempotency::InvalidIdempotencyKey>().is_some() {
+        error!("Invalid idempotency key");
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": "Invalid Idempotency-Key header" })),
+            StatusCode::BAD_REQUEST,
+        )))
+    } else if err.find::<crate::utils::idempotency::IdempotencyKeyInUse>().is_some() {
+        error!("Idempotency key in use");
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": "A request with this Idempotency-Key is still in progress" })),
+            StatusCode::CONFLICT,
+        )))
+    } else if err.find::<crate::utils::idempotency::IdempotencyKeyReused>().is_some() {
+        error!("Idempotency key reused with a different request");
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": "Idempotency-Key was already used for a different request" })),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        )))
+    } else if let Some(e) = err.find::<crate::handlers::links::BatchTooLarge>() {
+        error!("Batch too large");
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": format!("Too many items, the maximum is {}", e.max) })),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        )))
+    } else if err.find::<crate::utils::search::InvalidCursor>().is_some() {
+        error!("Invalid cursor");
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": "Invalid cursor" })),
+            StatusCode::BAD_REQUEST,
+        )))
+    } else if err.find::<crate::handlers::links::RevisionNotFound>().is_some() {
+        error!("Revision not found");
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": "Revision not found" })),
+            StatusCode::NOT_FOUND,
+        )))
+    } else if err.find::<crate::handlers::links::LinkNotFound>().is_some() {
+        error!("Link not found");
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": "Link not found" })),
+            StatusCode::NOT_FOUND,
+        )))
+    } else if err.find::<warp::reject::PayloadTooLarge>().is_some() {
+        error!("Request body too large");
+        Ok(Box::new(with_status(
+            json(&serde_json::json!({ "error": "Request body too large" })),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        )))
+    } else if err.find::<crate::config::db::DbError>().is_some() {
         error!("Database error occurred");
         Ok(Box::new(with_status(
             json(&serde_json::json!({ "error": "Database error occurred" })),
@@ -0,0 +1,489 @@
+use crate::config::clicks::{stream_clicks, variant_click_counts};
+use crate::config::db::{
+    get_link, insert_shortlinks, search_links, set_link_interstitial, stream_links, update_link_labels, DbError, LinkFilter,
+};
+use crate::config::domains::get_domain;
+use crate::config::health::recent_health_checks;
+use crate::config::redis::invalidate_link;
+use crate::config::revisions::{get_link_revision, list_link_revisions, restore_link_revision};
+use crate::models::{
+    ApiKey, AuditAction, BulkShortenResponse, BulkShortenResult, DomainQuery, ExportQuery, ImportQuery, Link,
+    LinkDetails, LinkLabels, LinkPatch, LinkSearchQuery, NewLink, Scope, ShortenRequest, VariantStats, VariantStatsResponse,
+};
+use crate::utils::audit::{api_key_actor, link_change_events, link_event, record};
+use crate::utils::auth::require;
+use crate::utils::domain::normalize_host;
+use crate::utils::export::{click_lines, link_lines};
+use crate::utils::import::{import_records, read_records, ImportError, TransferFormat};
+use crate::utils::search::{decode_cursor, link_limit, link_page, parse_tags};
+use crate::utils::short_code::short_url;
+use crate::utils::validate::{prepare_link, validate_labels};
+use bytes::Bytes;
+use deadpool_postgres::Pool;
+use deadpool_redis::Pool as RedisPool;
+use futures_util::{Stream, StreamExt};
+use hyper::Body;
+use log::{error, info};
+use std::env;
+use std::io::Cursor;
+use std::net::SocketAddr;
+use tracing::{info_span, instrument, Instrument};
+use warp::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use warp::http::{HeaderValue, StatusCode};
+use warp::reply::{json, with_status, Response};
+use warp::{Rejection, Reply};
+
+/// Default maximum number of items accepted by the bulk endpoint.
+const DEFAULT_BULK_MAX_ITEMS: usize = 1000;
+/// Number of health checks included in link details.
+const RECENT_HEALTH_CHECKS: i64 = 20;
+
+#[derive(Debug)]
+pub struct BatchTooLarge {
+    pub max: usize,
+}
+
+impl warp::reject::Reject for BatchTooLarge {}
+
+#[derive(Debug)]
+pub struct LinkNotFound;
+
+impl warp::reject::Reject for LinkNotFound {}
+
+#[derive(Debug)]
+pub struct RevisionNotFound;
+
+impl warp::reject::Reject for RevisionNotFound {}
+
+/// Maximum number of items accepted by the bulk endpoint, from `BULK_MAX_ITEMS`.
+fn bulk_max_items() -> usize {
+    env::var("BULK_MAX_ITEMS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_BULK_MAX_ITEMS)
+}
+
+/// Handler to shorten many URLs in one request.
+///
+/// Items are validated individually; valid ones are inserted in a single transaction.
+/// Each result reports either the new short link or why that item failed.
+#[instrument(name = "bulk_shorten", skip_all, fields(items = body.len()))]
+pub async fn bulk_shorten(
+    api_key: ApiKey,
+    addr: Option<SocketAddr>,
+    body: Vec<ShortenRequest>,
+    db_pool: Pool,
+) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::WriteLinks)?;
+    let max = bulk_max_items();
+    if body.len() > max {
+        return Err(warp::reject::custom(BatchTooLarge { max }));
+    }
+
+    let mut client = get_client(&db_pool).await?;
+    let mut results: Vec<BulkShortenResult> = Vec::with_capacity(body.len());
+    let mut valid: Vec<(usize, NewLink)> = Vec::new();
+    for (index, item) in body.into_iter().enumerate() {
+        let url = item.url.clone();
+        let error = match prepare_link(item, Some(api_key.workspace_id), &client).await {
+            Ok(link) => {
+                valid.push((index, link));
+                None
+            }
+            Err(e) => Some(e.to_string()),
+        };
+        results.push(BulkShortenResult {
+            index,
+            url,
+            short_code: None,
+            short_url: None,
+            error,
+        });
+    }
+
+    if !valid.is_empty() {
+        let links: Vec<NewLink> = valid.iter().map(|(_, link)| link.clone()).collect();
+        let tx = begin(&mut client).await?;
+        let stored = insert_shortlinks(&tx, &links, Some(api_key.id), Some(api_key.workspace_id)).await.map_err(|e| {
+            error!("Failed to insert shortlinks: {:?}", e);
+            warp::reject::custom(DbError::DatabaseError)
+        })?;
+
+        let events: Vec<_> = stored
+            .iter()
+            .flatten()
+            .map(|link| link_event(AuditAction::LinkCreate, None, Some(link)))
+            .collect();
+        record(&tx, &api_key_actor(&api_key, addr), &events).await.map_err(warp::reject::custom)?;
+        commit(tx).await?;
+
+        for ((index, _), link) in valid.into_iter().zip(stored) {
+            let result = &mut results[index];
+            match link {
+                Some(link) => {
+                    result.short_url = Some(short_url(link.domain.as_deref(), &link.short_code));
+                    result.short_code = Some(link.short_code);
+                }
+                None => result.error = Some("No free short code available".to_string()),
+            }
+        }
+    }
+
+    let created = results.iter().filter(|r| r.error.is_none()).count();
+    let response = BulkShortenResponse {
+        created,
+        failed: results.len() - created,
+        results,
+    };
+    info!("Bulk shortened {} of {} URLs for API key {}", response.created, response.results.len(), api_key.id);
+    Ok(json(&response))
+}
+
+/// Handler to show a link with its recent destination health checks.
+#[instrument(name = "get_link_details", skip(api_key, db_pool))]
+pub async fn get_link_details(
+    code: String,
+    query: DomainQuery,
+    api_key: ApiKey,
+    db_pool: Pool,
+) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ReadLinks)?;
+    let client = get_client(&db_pool).await?;
+    let domain_id = link_domain_id(&client, query.domain.as_deref()).await?;
+    let link = workspace_link(&client, &api_key, domain_id, &code).await?;
+    let health_checks = recent_health_checks(&client, domain_id, &code, RECENT_HEALTH_CHECKS)
+        .await
+        .map_err(warp::reject::custom)?;
+    info!("Showing link {} for API key {}", code, api_key.id);
+    Ok(json(&LinkDetails {
+        short_url: short_url(link.domain.as_deref(), &link.short_code),
+        link,
+        health_checks,
+    }))
+}
+
+/// Handler to list and search the links of the API key's workspace, a page at a time.
+#[instrument(name = "get_links", skip(api_key, db_pool))]
+pub async fn get_links(api_key: ApiKey, query: LinkSearchQuery, db_pool: Pool) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ReadLinks)?;
+    let search = query.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    let after = query
+        .cursor
+        .as_deref()
+        .map(|cursor| decode_cursor(cursor, search.is_some()))
+        .transpose()
+        .map_err(warp::reject::custom)?;
+    let limit = link_limit(query.limit);
+    let client = get_client(&db_pool).await?;
+    let domain_id = match query.domain.as_deref() {
+        Some(host) => match get_domain(&client, &normalize_host(host)).await.map_err(warp::reject::custom)? {
+            Some(domain) => Some(domain.id),
+            None => return Ok(json(&link_page(Vec::new(), limit))),
+        },
+        None => None,
+    };
+    let tags = parse_tags(query.tag.as_deref());
+    let filter = LinkFilter {
+        query: search,
+        tags: &tags,
+        folder: query.folder.as_deref().map(|folder| folder.trim().trim_matches('/')),
+        domain_id,
+        owner_key_id: query.owner_key_id,
+        created_after: query.created_after,
+        created_before: query.created_before,
+        after,
+    };
+    let links = search_links(&client, Some(api_key.workspace_id), &filter, limit + 1)
+        .await
+        .map_err(warp::reject::custom)?;
+    Ok(json(&link_page(links, limit)))
+}
+
+/// Handler to change the tags, title, notes or folder of a link, or turn its preview page on or off.
+///
+/// Turning the preview page on or off drops the link's cached destination, so visits follow right away.
+#[instrument(name = "patch_link", skip(api_key, addr, body, db_pool, redis_pool))]
+pub async fn patch_link(
+    code: String,
+    query: DomainQuery,
+    api_key: ApiKey,
+    addr: Option<SocketAddr>,
+    body: LinkPatch,
+    db_pool: Pool,
+    redis_pool: RedisPool,
+) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::WriteLinks)?;
+    let mut client = get_client(&db_pool).await?;
+    let domain_id = link_domain_id(&client, query.domain.as_deref()).await?;
+    let before = workspace_link(&client, &api_key, domain_id, &code).await?;
+    let labels = validate_labels(body.labels.apply(LinkLabels::from(&before))).map_err(warp::reject::custom)?;
+    let tx = begin(&mut client).await?;
+    let mut link = update_link_labels(&tx, domain_id, &code, &labels)
+        .await
+        .map_err(warp::reject::custom)?
+        .ok_or_else(|| warp::reject::custom(LinkNotFound))?;
+    let toggled = body.interstitial.filter(|&interstitial| interstitial != link.interstitial);
+    if let Some(interstitial) = toggled {
+        link = set_link_interstitial(&tx, domain_id, &code, interstitial)
+            .await
+            .map_err(warp::reject::custom)?
+            .ok_or_else(|| warp::reject::custom(LinkNotFound))?;
+    }
+    let events = link_change_events(AuditAction::LinkUpdate, &before, &link);
+    record(&tx, &api_key_actor(&api_key, addr), &events).await.map_err(warp::reject::custom)?;
+    commit(tx).await?;
+    if toggled.is_some() {
+        match redis_pool.get().await {
+            Ok(mut conn) => {
+                if let Err(e) = invalidate_link(&mut conn, domain_id, &code).await {
+                    error!("Failed to invalidate cached link {}: {:?}", code, e);
+                }
+            }
+            Err(e) => error!("Failed to get Redis connection: {:?}", e),
+        }
+    }
+    info!("Labeled {} for API key {}", code, api_key.id);
+    Ok(json(&link))
+}
+
+/// Handler to compare the clicks of a link's variants.
+#[instrument(name = "get_variant_stats", skip(api_key, db_pool))]
+pub async fn get_variant_stats(
+    code: String,
+    query: DomainQuery,
+    api_key: ApiKey,
+    db_pool: Pool,
+) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ReadLinks)?;
+    let client = get_client(&db_pool).await?;
+    let domain_id = link_domain_id(&client, query.domain.as_deref()).await?;
+    let link = workspace_link(&client, &api_key, domain_id, &code).await?;
+    let counts = variant_click_counts(&client, domain_id, &code).await.map_err(warp::reject::custom)?;
+    let total_clicks: i64 = counts.values().sum();
+    let variants = link
+        .variants
+        .into_iter()
+        .map(|variant| {
+            let clicks = counts.get(&variant.name).copied().unwrap_or(0);
+            VariantStats {
+                share: if total_clicks > 0 { clicks as f64 / total_clicks as f64 } else { 0.0 },
+                name: variant.name,
+                url: variant.url,
+                weight: variant.weight,
+                clicks,
+            }
+        })
+        .collect();
+    info!("Showing variant stats of {} for API key {}", code, api_key.id);
+    Ok(json(&VariantStatsResponse { short_code: link.short_code, total_clicks, variants }))
+}
+
+/// Handler to list the revisions of a link, newest first.
+#[instrument(name = "get_link_revisions", skip(api_key, db_pool))]
+pub async fn get_link_revisions(
+    code: String,
+    query: DomainQuery,
+    api_key: ApiKey,
+    db_pool: Pool,
+) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ReadLinks)?;
+    let client = get_client(&db_pool).await?;
+    let domain_id = link_domain_id(&client, query.domain.as_deref()).await?;
+    let link = workspace_link(&client, &api_key, domain_id, &code).await?;
+    let revisions = list_link_revisions(&client, link.id).await.map_err(warp::reject::custom)?;
+    info!("Showing revisions of {} for API key {}", code, api_key.id);
+    Ok(json(&revisions))
+}
+
+/// Handler to put a link back the way one of its revisions recorded it.
+///
+/// The restore is recorded as a new revision, and the cached destination is dropped so
+/// redirects follow it right away.
+#[instrument(name = "post_revision_restore", skip(api_key, addr, db_pool, redis_pool))]
+pub async fn post_revision_restore(
+    code: String,
+    revision: i32,
+    query: DomainQuery,
+    api_key: ApiKey,
+    addr: Option<SocketAddr>,
+    db_pool: Pool,
+    redis_pool: RedisPool,
+) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::WriteLinks)?;
+    let mut client = get_client(&db_pool).await?;
+    let domain_id = link_domain_id(&client, query.domain.as_deref()).await?;
+    let before = workspace_link(&client, &api_key, domain_id, &code).await?;
+    let revision = get_link_revision(&client, before.id, revision)
+        .await
+        .map_err(warp::reject::custom)?
+        .ok_or_else(|| warp::reject::custom(RevisionNotFound))?;
+    let tx = begin(&mut client).await?;
+    let link = restore_link_revision(&tx, before.id, &revision)
+        .await
+        .map_err(warp::reject::custom)?
+        .ok_or_else(|| warp::reject::custom(LinkNotFound))?;
+    let events = link_change_events(AuditAction::LinkRestore, &before, &link);
+    record(&tx, &api_key_actor(&api_key, addr), &events).await.map_err(warp::reject::custom)?;
+    commit(tx).await?;
+    match redis_pool.get().await {
+        Ok(mut conn) => {
+            if let Err(e) = invalidate_link(&mut conn, domain_id, &code).await {
+                error!("Failed to invalidate cached link {}: {:?}", code, e);
+            }
+        }
+        Err(e) => error!("Failed to get Redis connection: {:?}", e),
+    }
+    info!("Restored {} to revision {} for API key {}", code, revision.revision, api_key.id);
+    Ok(json(&link))
+}
+
+/// Handler to stream the links of the API key's workspace as CSV or JSONL.
+#[instrument(name = "export_links", skip_all)]
+pub async fn export_links(api_key: ApiKey, query: ExportQuery, db_pool: Pool) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ReadLinks)?;
+    let client = get_client(&db_pool).await?;
+    let rows = stream_links(&client, Some(api_key.workspace_id)).await.map_err(warp::reject::custom)?;
+    info!("Exporting links for API key {}", api_key.id);
+    Ok(streamed_export(client, link_lines(rows, query.format), query.format, "links"))
+}
+
+/// Handler to stream the clicks on the links of the API key's workspace as CSV or JSONL.
+#[instrument(name = "export_clicks", skip_all)]
+pub async fn export_clicks(api_key: ApiKey, query: ExportQuery, db_pool: Pool) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ReadLinks)?;
+    let client = get_client(&db_pool).await?;
+    let rows = stream_clicks(&client, Some(api_key.workspace_id)).await.map_err(warp::reject::custom)?;
+    info!("Exporting clicks for API key {}", api_key.id);
+    Ok(streamed_export(client, click_lines(rows, query.format), query.format, "clicks"))
+}
+
+/// Handler to import links into the API key's workspace from a CSV or JSONL body, keeping their short codes.
+///
+/// Links of other workspaces are never overwritten; they count as conflicts.
+#[instrument(name = "import_links", skip_all)]
+pub async fn import_links(
+    api_key: ApiKey,
+    addr: Option<SocketAddr>,
+    query: ImportQuery,
+    body: Bytes,
+    db_pool: Pool,
+    redis_pool: RedisPool,
+) -> Result<Box<dyn Reply>, Rejection> {
+    require(&api_key, Scope::WriteLinks)?;
+    let actor = api_key_actor(&api_key, addr);
+    let mut client = get_client(&db_pool).await?;
+    let records = read_records(Cursor::new(body), query.format);
+    let summary = match import_records(&mut client, records, Some(api_key.workspace_id), query.on_conflict, &actor).await {
+        Ok(summary) => summary,
+        Err(ImportError::Database(e)) => {
+            error!("Import failed: {:?}", e);
+            return Err(warp::reject::custom(e));
+        }
+        Err(e @ ImportError::Conflict { .. }) => {
+            info!("Import aborted: {}", e);
+            return Ok(Box::new(with_status(
+                json(&serde_json::json!({ "error": e.to_string() })),
+                StatusCode::CONFLICT,
+            )));
+        }
+    };
+
+    if !summary.updated_codes.is_empty() {
+        match redis_pool.get().await {
+            Ok(mut conn) => {
+                for (domain_id, code) in &summary.updated_codes {
+                    if let Err(e) = invalidate_link(&mut conn, *domain_id, code).await {
+                        error!("Failed to invalidate cached link {}: {:?}", code, e);
+                    }
+                }
+            }
+            Err(e) => error!("Failed to get Redis connection: {:?}", e),
+        }
+    }
+
+    info!(
+        "Imported links for API key {}: {} inserted, {} updated, {} skipped, {} failed",
+        api_key.id, summary.inserted, summary.updated, summary.skipped, summary.failed
+    );
+    Ok(Box::new(json(&summary)))
+}
+
+/// The ID of the domain named in a management request, `None` for the default domain.
+///
+/// An unknown domain has no links, so it is reported as a missing link.
+pub(crate) async fn link_domain_id(client: &deadpool_postgres::Client, host: Option<&str>) -> Result<Option<i32>, Rejection> {
+    let Some(host) = host else {
+        return Ok(None);
+    };
+    match get_domain(client, &normalize_host(host)).await.map_err(warp::reject::custom)? {
+        Some(domain) => Ok(Some(domain.id)),
+        None => Err(warp::reject::custom(LinkNotFound)),
+    }
+}
+
+/// Look up a link of the API key's workspace; links of other workspaces are reported as missing.
+pub(crate) async fn workspace_link(
+    client: &deadpool_postgres::Client,
+    api_key: &ApiKey,
+    domain_id: Option<i32>,
+    code: &str,
+) -> Result<Link, Rejection> {
+    get_link(client, domain_id, code)
+        .await
+        .map_err(warp::reject::custom)?
+        .filter(|link| link.workspace_id == Some(api_key.workspace_id))
+        .ok_or_else(|| warp::reject::custom(LinkNotFound))
+}
+
+/// Check out a database connection.
+pub(crate) async fn get_client(db_pool: &Pool) -> Result<deadpool_postgres::Client, Rejection> {
+    db_pool
+        .get()
+        .instrument(info_span!("db.pool.get"))
+        .await
+        .map_err(|e| {
+            error!("Failed to get DB client: {:?}", e);
+            warp::reject::custom(DbError::DatabaseError)
+        })
+}
+
+/// Start a transaction, so a change is committed together with its audit events.
+pub(crate) async fn begin(client: &mut deadpool_postgres::Client) -> Result<deadpool_postgres::Transaction<'_>, Rejection> {
+    client.transaction().await.map_err(|e| {
+        error!("Failed to start transaction: {:?}", e);
+        warp::reject::custom(DbError::DatabaseError)
+    })
+}
+
+/// Commit a transaction started with [`begin`].
+pub(crate) async fn commit(tx: deadpool_postgres::Transaction<'_>) -> Result<(), Rejection> {
+    tx.commit().await.map_err(|e| {
+        error!("Failed to commit transaction: {:?}", e);
+        warp::reject::custom(DbError::DatabaseError)
+    })
+}
+
+/// Build a response that streams export lines as they are read from the database.
+fn streamed_export<S>(client: deadpool_postgres::Client, lines: S, format: TransferFormat, name: &str) -> Response
+where
+    S: Stream<Item = Result<String, DbError>> + Send + 'static,
+{
+    // The connection stays checked out until the last row has been sent
+    let body = Body::wrap_stream(lines.map(move |line| {
+        let _ = &client;
+        line.map(Bytes::from)
+    }));
+    let (content_type, extension) = match format {
+        TransferFormat::Csv => ("text/csv; charset=utf-8", "csv"),
+        TransferFormat::Jsonl => ("application/x-ndjson", "jsonl"),
+    };
+    let mut response = Response::new(body);
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    headers.insert(
+        CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{}.{}\"", name, extension))
+            .expect("file name is a valid header value"),
+    );
+    response
+}
@@ -1,2 +1,19 @@
+#[allow(clippy::module_inception)]
 pub mod handlers;
-pub use handlers::{shorten_url, redirect_url, handle_rejection};
+pub mod audit;
+pub mod campaigns;
+pub mod links;
+pub mod trash;
+pub mod workspaces;
+pub use audit::get_audit_events;
+pub use campaigns::{get_campaigns, post_campaign};
+pub use handlers::{home, shorten_url, redirect_url, preview_link, unlock_link, handle_rejection};
+pub use links::{
+    bulk_shorten, export_clicks, export_links, get_link_details, get_link_revisions, get_links, get_variant_stats,
+    import_links, patch_link, post_revision_restore,
+};
+pub use trash::{delete_short_link, get_trash, post_trash_restore};
+pub use workspaces::{
+    delete_key, delete_member, get_invites, get_keys, get_workspace_details, post_invite, post_invite_acceptance,
+    post_key, post_member_role, post_workspace,
+};
@@ -0,0 +1,106 @@
+use crate::config::db::{delete_link, get_deleted_link, list_deleted_links, undelete_link};
+use crate::config::redis::invalidate_link;
+use crate::handlers::links::{begin, commit, get_client, link_domain_id, workspace_link, LinkNotFound};
+use crate::models::{ApiKey, AuditAction, DomainQuery, Scope, TrashQuery, TrashedLink};
+use crate::utils::audit::{api_key_actor, link_event, record};
+use crate::utils::auth::require;
+use crate::utils::trash::trashed;
+use deadpool_postgres::Pool;
+use deadpool_redis::Pool as RedisPool;
+use log::{error, info};
+use std::net::SocketAddr;
+use tracing::instrument;
+use warp::reject::custom;
+use warp::reply::json;
+use warp::{Rejection, Reply};
+
+/// Number of links in the trash listed when no limit is given.
+const DEFAULT_TRASH_LIMIT: i64 = 50;
+/// Most links in the trash listed at once.
+const MAX_TRASH_LIMIT: i64 = 500;
+
+/// Handler to move a link to the trash.
+///
+/// Its short code stays taken until the link is purged, and visitors get a "link removed" page meanwhile.
+#[instrument(name = "delete_short_link", skip(api_key, addr, db_pool, redis_pool))]
+pub async fn delete_short_link(
+    code: String,
+    query: DomainQuery,
+    api_key: ApiKey,
+    addr: Option<SocketAddr>,
+    db_pool: Pool,
+    redis_pool: RedisPool,
+) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::WriteLinks)?;
+    let mut client = get_client(&db_pool).await?;
+    let domain_id = link_domain_id(&client, query.domain.as_deref()).await?;
+    let before = workspace_link(&client, &api_key, domain_id, &code).await?;
+    let tx = begin(&mut client).await?;
+    let link = delete_link(&tx, domain_id, &code)
+        .await
+        .map_err(custom)?
+        .ok_or_else(|| custom(LinkNotFound))?;
+    let event = link_event(AuditAction::LinkDelete, Some(&before), Some(&link));
+    record(&tx, &api_key_actor(&api_key, addr), &[event]).await.map_err(custom)?;
+    commit(tx).await?;
+    invalidate(&redis_pool, domain_id, &code).await;
+    info!("Moved {} to the trash for API key {}", code, api_key.id);
+    Ok(json(&trashed(link)))
+}
+
+/// Handler to list the links in the trash of the API key's workspace, most recently deleted first.
+#[instrument(name = "get_trash", skip(api_key, db_pool))]
+pub async fn get_trash(api_key: ApiKey, query: TrashQuery, db_pool: Pool) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ReadLinks)?;
+    let client = get_client(&db_pool).await?;
+    let limit = query.limit.unwrap_or(DEFAULT_TRASH_LIMIT).clamp(1, MAX_TRASH_LIMIT);
+    let offset = query.offset.unwrap_or(0).max(0);
+    let links = list_deleted_links(&client, Some(api_key.workspace_id), limit, offset)
+        .await
+        .map_err(custom)?;
+    let links: Vec<TrashedLink> = links.into_iter().map(trashed).collect();
+    Ok(json(&links))
+}
+
+/// Handler to take a link of the API key's workspace back out of the trash.
+#[instrument(name = "post_trash_restore", skip(api_key, addr, db_pool, redis_pool))]
+pub async fn post_trash_restore(
+    code: String,
+    query: DomainQuery,
+    api_key: ApiKey,
+    addr: Option<SocketAddr>,
+    db_pool: Pool,
+    redis_pool: RedisPool,
+) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::WriteLinks)?;
+    let mut client = get_client(&db_pool).await?;
+    let domain_id = link_domain_id(&client, query.domain.as_deref()).await?;
+    let before = get_deleted_link(&client, domain_id, &code)
+        .await
+        .map_err(custom)?
+        .filter(|link| link.workspace_id == Some(api_key.workspace_id))
+        .ok_or_else(|| custom(LinkNotFound))?;
+    let tx = begin(&mut client).await?;
+    let link = undelete_link(&tx, domain_id, &code)
+        .await
+        .map_err(custom)?
+        .ok_or_else(|| custom(LinkNotFound))?;
+    let event = link_event(AuditAction::LinkUndelete, Some(&before), Some(&link));
+    record(&tx, &api_key_actor(&api_key, addr), &[event]).await.map_err(custom)?;
+    commit(tx).await?;
+    invalidate(&redis_pool, domain_id, &code).await;
+    info!("Restored {} from the trash for API key {}", code, api_key.id);
+    Ok(json(&link))
+}
+
+/// Drop the cached destination of a link whose trash state changed.
+async fn invalidate(redis_pool: &RedisPool, domain_id: Option<i32>, code: &str) {
+    match redis_pool.get().await {
+        Ok(mut conn) => {
+            if let Err(e) = invalidate_link(&mut conn, domain_id, code).await {
+                error!("Failed to invalidate cached link {}: {:?}", code, e);
+            }
+        }
+        Err(e) => error!("Failed to get Redis connection: {:?}", e),
+    }
+}
@@ -0,0 +1,262 @@
+use crate::config::api_keys::{create_api_key, get_api_key, list_api_keys, revoke_api_key};
+use crate::config::db::DbError;
+use crate::config::workspaces::{
+    accept_invite, create_invite, get_member, get_workspace, list_invites, list_members, remove_member,
+    rename_workspace, set_member_role,
+};
+use crate::handlers::links::{begin, commit, get_client};
+use crate::models::{
+    AcceptedInvite, ApiKey, ApiKeyRequest, ApiKeyResponse, AuditAction, InviteRequest, InviteResponse, Member,
+    RoleRequest, Scope, WorkspaceDetails, WorkspaceRequest,
+};
+use crate::utils::audit::{
+    api_key_actor, invite_actor, invite_event, key_event, member_event, record, workspace_event,
+};
+use crate::utils::auth::{require, Forbidden};
+use crate::utils::workspace::{invite_ttl_days, invite_url, validate_email, validate_workspace_name, WorkspaceError};
+use deadpool_postgres::Pool;
+use log::info;
+use std::net::SocketAddr;
+use tracing::instrument;
+use warp::reject::custom;
+use warp::reply::json;
+use warp::{Rejection, Reply};
+
+/// Handler to show the API key's workspace with its members.
+#[instrument(name = "get_workspace", skip_all)]
+pub async fn get_workspace_details(api_key: ApiKey, db_pool: Pool) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ManageMembers)?;
+    let client = get_client(&db_pool).await?;
+    let workspace = get_workspace(&client, api_key.workspace_id)
+        .await
+        .map_err(custom)?
+        .ok_or_else(|| custom(DbError::DatabaseError))?;
+    let members = list_members(&client, workspace.id).await.map_err(custom)?;
+    Ok(json(&WorkspaceDetails { workspace, members }))
+}
+
+/// Handler to rename the API key's workspace.
+#[instrument(name = "post_workspace", skip_all)]
+pub async fn post_workspace(
+    api_key: ApiKey,
+    addr: Option<SocketAddr>,
+    body: WorkspaceRequest,
+    db_pool: Pool,
+) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ManageWorkspace)?;
+    let name = validate_workspace_name(&body.name).map_err(custom)?;
+    let mut client = get_client(&db_pool).await?;
+    let before = get_workspace(&client, api_key.workspace_id).await.map_err(custom)?;
+    let tx = begin(&mut client).await?;
+    let workspace = rename_workspace(&tx, api_key.workspace_id, &name)
+        .await
+        .map_err(custom)?
+        .ok_or_else(|| custom(WorkspaceError::NameTaken))?;
+    let event = workspace_event(AuditAction::WorkspaceUpdate, before.as_ref(), &workspace);
+    record(&tx, &api_key_actor(&api_key, addr), &[event]).await.map_err(custom)?;
+    commit(tx).await?;
+    info!("Renamed workspace {} to {} for API key {}", workspace.id, workspace.name, api_key.id);
+    Ok(json(&workspace))
+}
+
+/// Handler to change the role of a member.
+///
+/// Keys can only move members between roles whose permissions they hold themselves.
+#[instrument(name = "post_member_role", skip(api_key, addr, body, db_pool))]
+pub async fn post_member_role(
+    id: i32,
+    api_key: ApiKey,
+    addr: Option<SocketAddr>,
+    body: RoleRequest,
+    db_pool: Pool,
+) -> Result<impl Reply, Rejection> {
+    let mut client = get_client(&db_pool).await?;
+    let before = managed_member(&client, &api_key, id).await?;
+    grantable(&api_key, body.role.scopes())?;
+    let tx = begin(&mut client).await?;
+    let member = set_member_role(&tx, api_key.workspace_id, before.id, body.role)
+        .await
+        .map_err(custom)?
+        .ok_or_else(|| custom(WorkspaceError::LastOwner))?;
+    let event = member_event(AuditAction::MemberUpdate, Some(&before), Some(&member));
+    record(&tx, &api_key_actor(&api_key, addr), &[event]).await.map_err(custom)?;
+    commit(tx).await?;
+    info!("Made member {} {} for API key {}", member.id, member.role.as_str(), api_key.id);
+    Ok(json(&member))
+}
+
+/// Handler to remove a member and revoke the API keys issued to them.
+#[instrument(name = "delete_member", skip(api_key, addr, db_pool))]
+pub async fn delete_member(
+    id: i32,
+    api_key: ApiKey,
+    addr: Option<SocketAddr>,
+    db_pool: Pool,
+) -> Result<impl Reply, Rejection> {
+    let mut client = get_client(&db_pool).await?;
+    managed_member(&client, &api_key, id).await?;
+    let tx = begin(&mut client).await?;
+    let member = remove_member(&tx, api_key.workspace_id, id)
+        .await
+        .map_err(custom)?
+        .ok_or_else(|| custom(WorkspaceError::LastOwner))?;
+    let event = member_event(AuditAction::MemberRemove, Some(&member), None);
+    record(&tx, &api_key_actor(&api_key, addr), &[event]).await.map_err(custom)?;
+    commit(tx).await?;
+    info!("Removed member {} for API key {}", member.id, api_key.id);
+    Ok(json(&member))
+}
+
+/// Handler to list the invites of the API key's workspace that can still be accepted.
+#[instrument(name = "get_invites", skip_all)]
+pub async fn get_invites(api_key: ApiKey, db_pool: Pool) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ManageMembers)?;
+    let client = get_client(&db_pool).await?;
+    let invites = list_invites(&client, api_key.workspace_id).await.map_err(custom)?;
+    Ok(json(&invites))
+}
+
+/// Handler to invite someone to the API key's workspace.
+///
+/// The invite link is only returned here; whoever opens it joins with the invited role.
+#[instrument(name = "post_invite", skip_all)]
+pub async fn post_invite(
+    api_key: ApiKey,
+    addr: Option<SocketAddr>,
+    body: InviteRequest,
+    db_pool: Pool,
+) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ManageMembers)?;
+    grantable(&api_key, body.role.scopes())?;
+    let email = validate_email(&body.email).map_err(custom)?;
+    let mut client = get_client(&db_pool).await?;
+    let tx = begin(&mut client).await?;
+    let (invite, token) = create_invite(&tx, api_key.workspace_id, &email, body.role, invite_ttl_days())
+        .await
+        .map_err(custom)?;
+    record(&tx, &api_key_actor(&api_key, addr), &[invite_event(&invite)]).await.map_err(custom)?;
+    commit(tx).await?;
+    info!("Invited {} as {} for API key {}", invite.email, invite.role.as_str(), api_key.id);
+    Ok(json(&InviteResponse { invite, invite_url: invite_url(&token) }))
+}
+
+/// Handler to accept an invite, returning an API key for the new member.
+#[instrument(name = "post_invite_acceptance", skip_all)]
+pub async fn post_invite_acceptance(
+    token: String,
+    addr: Option<SocketAddr>,
+    db_pool: Pool,
+) -> Result<impl Reply, Rejection> {
+    let mut client = get_client(&db_pool).await?;
+    let tx = begin(&mut client).await?;
+    let member = accept_invite(&tx, &token)
+        .await
+        .map_err(custom)?
+        .ok_or_else(|| custom(WorkspaceError::InvalidInvite))?;
+    let (key, secret) = create_api_key(&tx, member.workspace_id, Some(member.id), &member.email, member.role.scopes())
+        .await
+        .map_err(custom)?;
+    let events = [
+        member_event(AuditAction::MemberJoin, None, Some(&member)),
+        key_event(AuditAction::KeyCreate, None, Some(&key)),
+    ];
+    record(&tx, &invite_actor(&member.email, addr), &events).await.map_err(custom)?;
+    commit(tx).await?;
+    let workspace = get_workspace(&client, member.workspace_id)
+        .await
+        .map_err(custom)?
+        .ok_or_else(|| custom(WorkspaceError::InvalidInvite))?;
+    info!("{} joined workspace {} as {}", member.email, workspace.id, member.role.as_str());
+    Ok(json(&AcceptedInvite {
+        workspace,
+        member,
+        key: ApiKeyResponse { key, secret },
+    }))
+}
+
+/// Handler to list the API keys of the API key's workspace, including revoked ones.
+#[instrument(name = "get_keys", skip_all)]
+pub async fn get_keys(api_key: ApiKey, db_pool: Pool) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ManageKeys)?;
+    let client = get_client(&db_pool).await?;
+    let keys = list_api_keys(&client, Some(api_key.workspace_id)).await.map_err(custom)?;
+    Ok(json(&keys))
+}
+
+/// Handler to issue an API key for the API key's workspace.
+///
+/// Keys can only be given scopes their issuer holds, and get all of them if none are requested.
+/// A key issued by a member's key stays limited by that member's role.
+#[instrument(name = "post_key", skip_all)]
+pub async fn post_key(
+    api_key: ApiKey,
+    addr: Option<SocketAddr>,
+    body: ApiKeyRequest,
+    db_pool: Pool,
+) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ManageKeys)?;
+    let scopes = match body.scopes {
+        Some(scopes) => scopes,
+        None => Scope::ALL.iter().copied().filter(|&scope| api_key.allows(scope)).collect(),
+    };
+    if scopes.is_empty() {
+        return Err(custom(WorkspaceError::NoScopes));
+    }
+    grantable(&api_key, &scopes)?;
+    let mut client = get_client(&db_pool).await?;
+    let tx = begin(&mut client).await?;
+    let (key, secret) = create_api_key(&tx, api_key.workspace_id, api_key.member_id, body.name.trim(), &scopes)
+        .await
+        .map_err(custom)?;
+    let event = key_event(AuditAction::KeyCreate, None, Some(&key));
+    record(&tx, &api_key_actor(&api_key, addr), &[event]).await.map_err(custom)?;
+    commit(tx).await?;
+    info!("Issued API key {} for API key {}", key.id, api_key.id);
+    Ok(json(&ApiKeyResponse { key, secret }))
+}
+
+/// Handler to revoke an API key of the API key's workspace.
+#[instrument(name = "delete_key", skip(api_key, addr, db_pool))]
+pub async fn delete_key(
+    id: i32,
+    api_key: ApiKey,
+    addr: Option<SocketAddr>,
+    db_pool: Pool,
+) -> Result<impl Reply, Rejection> {
+    require(&api_key, Scope::ManageKeys)?;
+    let mut client = get_client(&db_pool).await?;
+    let before = get_api_key(&client, Some(api_key.workspace_id), id)
+        .await
+        .map_err(custom)?
+        .ok_or_else(|| custom(WorkspaceError::KeyNotFound))?;
+    grantable(&api_key, &before.scopes)?;
+    let tx = begin(&mut client).await?;
+    let key = revoke_api_key(&tx, Some(api_key.workspace_id), before.id)
+        .await
+        .map_err(custom)?
+        .ok_or_else(|| custom(WorkspaceError::KeyNotFound))?;
+    let event = key_event(AuditAction::KeyRevoke, Some(&before), Some(&key));
+    record(&tx, &api_key_actor(&api_key, addr), &[event]).await.map_err(custom)?;
+    commit(tx).await?;
+    info!("Revoked API key {} for API key {}", key.id, api_key.id);
+    Ok(json(&key))
+}
+
+/// Look up a member of the API key's workspace that the key may manage.
+async fn managed_member(client: &deadpool_postgres::Client, api_key: &ApiKey, id: i32) -> Result<Member, Rejection> {
+    require(api_key, Scope::ManageMembers)?;
+    let member = get_member(client, api_key.workspace_id, id)
+        .await
+        .map_err(custom)?
+        .ok_or_else(|| custom(WorkspaceError::MemberNotFound))?;
+    grantable(api_key, member.role.scopes())?;
+    Ok(member)
+}
+
+/// Reject the request unless the API key holds every scope it is handing out or taking away.
+fn grantable(api_key: &ApiKey, scopes: &[Scope]) -> Result<(), Rejection> {
+    match scopes.iter().find(|&&scope| !api_key.allows(scope)) {
+        Some(&scope) => Err(custom(Forbidden { scope })),
+        None => Ok(()),
+    }
+}
@@ -1,38 +1,56 @@
+use clap::Parser;
 use dotenv::dotenv;
-use env_logger;
+use log::{error, info};
 use std::collections::HashMap;
-use std::env;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use utils::rate_limit::with_ip_rate_limit;
-use warp::Filter;
 use deadpool_redis::redis::AsyncCommands; 
-use config::db::configure_db;
-
-mod config;
-mod handlers;
-mod models;
-mod routes;
-mod utils;
-mod views;
-
-use crate::routes::create_routes;
+use time_to_rust::cli::{self, Cli, Command, ServeArgs};
+use time_to_rust::config;
+use time_to_rust::config::db::configure_db;
+use time_to_rust::config::geoip::configure_geoip;
+use time_to_rust::config::logging::configure_logging;
+use time_to_rust::config::telemetry::configure_tracing;
+use time_to_rust::routes::{self, create_routes};
+use time_to_rust::utils;
+use time_to_rust::utils::health_check::{run_health_checks, HealthCheckConfig};
+use time_to_rust::utils::metadata::{run_metadata_fetcher, MetadataConfig};
+use time_to_rust::utils::rate_limit::with_ip_rate_limit;
+use time_to_rust::utils::trash::run_trash_purge;
+use warp::Filter;
 
-/// Main function to start the server.
+/// Main function: start the server, or run an admin command.
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    env_logger::init();
+    configure_logging();
+
+    let args = Cli::parse();
+    match args.command {
+        None => serve(args.serve).await,
+        Some(Command::Serve(serve_args)) => serve(serve_args).await,
+        Some(command) => {
+            if let Err(e) = cli::run(command, args.output).await {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Start the server.
+async fn serve(args: ServeArgs) {
+    let tracer_provider = configure_tracing();
 
     // Configure the PostgreSQL database connection pool
     let pool = configure_db().await;
 
     // Check database connection
     match pool.get().await {
-        Ok(_) => println!("Successfully connected to the database."),
+        Ok(_) => info!("Successfully connected to the database."),
         Err(e) => {
-            eprintln!("Failed to connect to the database: {:?}", e);
-            eprintln!("Please check the DATABASE_URL and ensure the database server is reachable.");
+            error!("Failed to connect to the database: {:?}", e);
+            error!("Please check the DATABASE_URL and ensure the database server is reachable.");
             std::process::exit(1);
         }
     }
@@ -43,26 +61,45 @@ async fn main() {
     // Check Redis connection
     let mut conn = redis_pool.get().await.expect("Failed to get Redis connection");
     match conn.ping::<String>().await {
-        Ok(pong) => println!("Successfully connected to Redis: {}", pong),
+        Ok(pong) => info!("Successfully connected to Redis: {}", pong),
         Err(e) => {
-            eprintln!("Failed to connect to Redis: {:?}", e);
-            eprintln!("Please check the REDIS_URL and ensure the Redis server is reachable.");
+            error!("Failed to connect to Redis: {:?}", e);
+            error!("Please check the REDIS_URL and ensure the Redis server is reachable.");
             std::process::exit(1);
         }
     }
 
+    // Load the GeoIP database used for geo targeting and click countries
+    configure_geoip();
+
+    // Check link destinations in the background
+    if let Some(health_config) = HealthCheckConfig::from_env() {
+        tokio::spawn(run_health_checks(health_config, pool.clone(), redis_pool.clone()));
+    }
+
+    // Fetch titles, descriptions and images of link destinations in the background
+    if let Some(metadata_config) = MetadataConfig::from_env() {
+        tokio::spawn(run_metadata_fetcher(metadata_config, pool.clone()));
+    }
+
+    // Release the short codes of links that have been in the trash long enough
+    tokio::spawn(run_trash_purge(pool.clone(), redis_pool.clone()));
+
     let rate_limiter = Arc::new(Mutex::new(HashMap::new()));
 
     let routes = create_routes(pool.clone(), redis_pool.clone())
         .and(with_ip_rate_limit(rate_limiter.clone()))
         .recover(utils::validate::error_handler)
-        .with(warp::log("warp::server"));
+        .map(warp::Reply::into_response)
+        .boxed();
 
-    let port: u16 = env::var("PORT")
-        .unwrap_or_else(|_| "3030".to_string())
-        .parse()
-        .expect("PORT must be a number");
+    info!("Server is running on port {}", args.port);
+    routes::serve(routes, ([127, 0, 0, 1], args.port).into()).await;
 
-    println!("Server is running on port {}", port);
-    warp::serve(routes).run(([127, 0, 0, 1], port)).await;
+    // Flush any spans still waiting to be exported
+    if let Some(provider) = tracer_provider {
+        if let Err(e) = provider.shutdown() {
+            error!("Failed to flush spans: {:?}", e);
+        }
+    }
 }
@@ -0,0 +1,61 @@
+use crate::models::{Role, Scope};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use tokio_postgres::Row;
+
+/// An API key. Only a hash of the secret is stored; the prefix identifies it to humans.
+///
+/// Keys belong to a workspace and are limited to their scopes. A key issued to a member is
+/// further limited to what the member's current role allows.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKey {
+    pub id: i32,
+    pub workspace_id: i32,
+    pub member_id: Option<i32>,
+    pub scopes: Vec<Scope>,
+    /// Current role of the member the key was issued to.
+    pub member_role: Option<Role>,
+    pub name: String,
+    pub key_prefix: String,
+    pub created_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    /// Shortening a URL this key already shortened returns the existing link.
+    pub reuse_existing: bool,
+}
+
+impl ApiKey {
+    /// Columns selected by every query that builds an `ApiKey`.
+    pub const COLUMNS: &'static str = "id, workspace_id, member_id, scopes, \
+        (SELECT role FROM workspace_members WHERE workspace_members.id = api_keys.member_id) AS member_role, \
+        name, key_prefix, created_at, revoked_at, reuse_existing";
+
+    pub fn from_row(row: &Row) -> Self {
+        ApiKey {
+            id: row.get("id"),
+            workspace_id: row.get("workspace_id"),
+            member_id: row.get("member_id"),
+            scopes: row
+                .get::<_, Vec<&str>>("scopes")
+                .into_iter()
+                .filter_map(|scope| scope.parse().ok())
+                .collect(),
+            member_role: row.get::<_, Option<&str>>("member_role").and_then(|role| role.parse().ok()),
+            name: row.get("name"),
+            key_prefix: row.get("key_prefix"),
+            created_at: row.get("created_at"),
+            revoked_at: row.get("revoked_at"),
+            reuse_existing: row.get("reuse_existing"),
+        }
+    }
+
+    /// Whether this key may do what `scope` covers.
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+            && match (self.member_id, self.member_role) {
+                (None, _) => true,
+                (Some(_), Some(role)) => role.scopes().contains(&scope),
+                // The member has been removed
+                (Some(_), None) => false,
+            }
+    }
+}
@@ -0,0 +1,158 @@
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::IpAddr;
+use tokio_postgres::Row;
+
+/// A change recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum AuditAction {
+    #[serde(rename = "link.create")]
+    #[value(name = "link.create")]
+    LinkCreate,
+    #[serde(rename = "link.update")]
+    #[value(name = "link.update")]
+    LinkUpdate,
+    #[serde(rename = "link.delete")]
+    #[value(name = "link.delete")]
+    LinkDelete,
+    #[serde(rename = "link.restore")]
+    #[value(name = "link.restore")]
+    LinkRestore,
+    #[serde(rename = "link.undelete")]
+    #[value(name = "link.undelete")]
+    LinkUndelete,
+    #[serde(rename = "link.purge")]
+    #[value(name = "link.purge")]
+    LinkPurge,
+    /// A link stopped redirecting straight away: it got an end date, became single-use or used up,
+    /// or was put behind its preview page.
+    #[serde(rename = "link.disable")]
+    #[value(name = "link.disable")]
+    LinkDisable,
+    #[serde(rename = "key.create")]
+    #[value(name = "key.create")]
+    KeyCreate,
+    #[serde(rename = "key.update")]
+    #[value(name = "key.update")]
+    KeyUpdate,
+    #[serde(rename = "key.revoke")]
+    #[value(name = "key.revoke")]
+    KeyRevoke,
+    #[serde(rename = "member.invite")]
+    #[value(name = "member.invite")]
+    MemberInvite,
+    #[serde(rename = "member.join")]
+    #[value(name = "member.join")]
+    MemberJoin,
+    #[serde(rename = "member.update")]
+    #[value(name = "member.update")]
+    MemberUpdate,
+    #[serde(rename = "member.remove")]
+    #[value(name = "member.remove")]
+    MemberRemove,
+    #[serde(rename = "workspace.create")]
+    #[value(name = "workspace.create")]
+    WorkspaceCreate,
+    #[serde(rename = "workspace.update")]
+    #[value(name = "workspace.update")]
+    WorkspaceUpdate,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::LinkCreate => "link.create",
+            AuditAction::LinkUpdate => "link.update",
+            AuditAction::LinkDelete => "link.delete",
+            AuditAction::LinkRestore => "link.restore",
+            AuditAction::LinkUndelete => "link.undelete",
+            AuditAction::LinkPurge => "link.purge",
+            AuditAction::LinkDisable => "link.disable",
+            AuditAction::KeyCreate => "key.create",
+            AuditAction::KeyUpdate => "key.update",
+            AuditAction::KeyRevoke => "key.revoke",
+            AuditAction::MemberInvite => "member.invite",
+            AuditAction::MemberJoin => "member.join",
+            AuditAction::MemberUpdate => "member.update",
+            AuditAction::MemberRemove => "member.remove",
+            AuditAction::WorkspaceCreate => "workspace.create",
+            AuditAction::WorkspaceUpdate => "workspace.update",
+        }
+    }
+}
+
+/// Who made a change.
+#[derive(Debug, Clone, Serialize)]
+pub struct Actor {
+    /// `api_key`, `anonymous`, `invite`, `cli` or `system`.
+    #[serde(rename = "actor")]
+    pub kind: String,
+    #[serde(rename = "actor_key_id")]
+    pub key_id: Option<i32>,
+    /// Name of the API key, email of the invitee, or system user running the CLI.
+    #[serde(rename = "actor_name")]
+    pub name: Option<String>,
+    pub ip: Option<IpAddr>,
+}
+
+/// A change about to be recorded in the audit log.
+#[derive(Debug, Clone)]
+pub struct NewAuditEvent {
+    pub workspace_id: Option<i32>,
+    pub action: AuditAction,
+    /// What changed, such as `link:go.example.com/abc` or `key:12`.
+    pub target: String,
+    /// Changed fields, each with its value `before` and `after` the change.
+    pub diff: Value,
+}
+
+/// A recorded change.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub workspace_id: Option<i32>,
+    #[serde(flatten)]
+    pub actor: Actor,
+    pub action: String,
+    pub target: String,
+    pub diff: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditEvent {
+    /// Columns selected by every query that builds an `AuditEvent`.
+    pub const COLUMNS: &'static str =
+        "id, workspace_id, actor, actor_key_id, actor_name, ip, action, target, diff, created_at";
+
+    pub fn from_row(row: &Row) -> Self {
+        AuditEvent {
+            id: row.get("id"),
+            workspace_id: row.get("workspace_id"),
+            actor: Actor {
+                kind: row.get("actor"),
+                key_id: row.get("actor_key_id"),
+                name: row.get("actor_name"),
+                ip: row.get("ip"),
+            },
+            action: row.get("action"),
+            target: row.get("target"),
+            diff: row.get("diff"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+/// Filters of the audit log, newest events first.
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    pub action: Option<AuditAction>,
+    pub target: Option<String>,
+    pub actor_key_id: Option<i32>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Only events older than this ID, to page through the log.
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
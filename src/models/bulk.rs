@@ -0,0 +1,22 @@
+use serde::Serialize;
+
+/// Outcome of one item of a bulk shorten request.
+#[derive(Debug, Serialize)]
+pub struct BulkShortenResult {
+    pub index: usize,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Response structure for a bulk shorten request.
+#[derive(Debug, Serialize)]
+pub struct BulkShortenResponse {
+    pub created: usize,
+    pub failed: usize,
+    pub results: Vec<BulkShortenResult>,
+}
@@ -0,0 +1,84 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::types::Json;
+use tokio_postgres::Row;
+
+/// UTM parameters added to the destinations of a link.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UtmParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utm_source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utm_medium: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utm_campaign: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utm_term: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utm_content: Option<String>,
+}
+
+impl UtmParams {
+    pub fn is_empty(&self) -> bool {
+        self.pairs().next().is_none()
+    }
+
+    /// The parameters that are set, as query string pairs in a fixed order.
+    pub fn pairs(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        [
+            ("utm_source", &self.utm_source),
+            ("utm_medium", &self.utm_medium),
+            ("utm_campaign", &self.utm_campaign),
+            ("utm_term", &self.utm_term),
+            ("utm_content", &self.utm_content),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.as_deref()?)))
+    }
+
+    /// These parameters, with the missing ones taken from `preset`.
+    pub fn or(self, preset: &UtmParams) -> UtmParams {
+        UtmParams {
+            utm_source: self.utm_source.or_else(|| preset.utm_source.clone()),
+            utm_medium: self.utm_medium.or_else(|| preset.utm_medium.clone()),
+            utm_campaign: self.utm_campaign.or_else(|| preset.utm_campaign.clone()),
+            utm_term: self.utm_term.or_else(|| preset.utm_term.clone()),
+            utm_content: self.utm_content.or_else(|| preset.utm_content.clone()),
+        }
+    }
+}
+
+/// A reusable set of UTM parameters, referenced by name when creating links.
+#[derive(Debug, Clone, Serialize)]
+pub struct Campaign {
+    pub id: i32,
+    /// Workspace the preset belongs to; `None` for the presets of links without a workspace.
+    pub workspace_id: Option<i32>,
+    pub name: String,
+    #[serde(flatten)]
+    pub utm: UtmParams,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl Campaign {
+    /// Columns selected by every query that builds a `Campaign`.
+    pub const COLUMNS: &'static str = "id, workspace_id, name, utm, created_at";
+
+    pub fn from_row(row: &Row) -> Self {
+        Campaign {
+            id: row.get("id"),
+            workspace_id: row.get("workspace_id"),
+            name: row.get("name"),
+            utm: row.get::<_, Json<UtmParams>>("utm").0,
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+/// Request structure for creating or replacing a campaign preset.
+#[derive(Debug, Deserialize)]
+pub struct CampaignRequest {
+    pub name: String,
+    #[serde(flatten)]
+    pub utm: UtmParams,
+}
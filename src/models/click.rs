@@ -0,0 +1,44 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+
+/// A recorded visit of a short link.
+#[derive(Debug, Clone, Serialize)]
+pub struct Click {
+    pub short_code: String,
+    pub clicked_at: DateTime<Utc>,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    /// ISO country code of the visitor, if it could be resolved.
+    pub country: Option<String>,
+    /// Language the visitor was sent to, for links with language rules.
+    pub language: Option<String>,
+    /// Variant the visitor was assigned, for links splitting their traffic.
+    pub variant: Option<String>,
+    /// Host of the branded domain the link was visited on, `None` for the default domain.
+    pub domain: Option<String>,
+}
+
+impl Click {
+    pub fn from_row(row: &Row) -> Self {
+        Click {
+            short_code: row.get("short_code"),
+            clicked_at: row.get("clicked_at"),
+            referer: row.get("referer"),
+            user_agent: row.get("user_agent"),
+            country: row.get("country"),
+            language: row.get("language"),
+            variant: row.get("variant"),
+            domain: row.get("domain"),
+        }
+    }
+}
+
+/// How a visit was routed, stored with its click.
+#[derive(Debug, Clone, Default)]
+pub struct Routing {
+    /// Language chosen from the visitor's `Accept-Language` header.
+    pub language: Option<String>,
+    /// Variant the visitor was kept on or assigned.
+    pub variant: Option<String>,
+}
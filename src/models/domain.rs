@@ -0,0 +1,41 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+
+/// A branded short domain. Links on it are resolved by the `Host` header of the request.
+#[derive(Debug, Clone, Serialize)]
+pub struct Domain {
+    pub id: i32,
+    /// Host name as sent in the `Host` header, lowercase, with the port if it is not the default one.
+    pub host: String,
+    /// Where `/` redirects; `None` shows the index page.
+    pub root_url: Option<String>,
+    /// Where unknown short codes redirect; `None` shows the 404 page.
+    pub not_found_url: Option<String>,
+    /// Every link on the domain shows the preview page before continuing.
+    pub interstitial: bool,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl Domain {
+    /// Columns selected by every query that builds a `Domain`.
+    pub const COLUMNS: &'static str = "id, host, root_url, not_found_url, interstitial, created_at";
+
+    pub fn from_row(row: &Row) -> Self {
+        Domain {
+            id: row.get("id"),
+            host: row.get("host"),
+            root_url: row.get("root_url"),
+            not_found_url: row.get("not_found_url"),
+            interstitial: row.get("interstitial"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+/// Query string selecting the domain of a link in the management API; the default domain if omitted.
+#[derive(Debug, Deserialize)]
+pub struct DomainQuery {
+    #[serde(default)]
+    pub domain: Option<String>,
+}
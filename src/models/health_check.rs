@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+
+/// One health check of a link's destination.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthCheck {
+    pub checked_at: DateTime<Utc>,
+    pub healthy: bool,
+    /// HTTP status of the response; `None` if no response arrived.
+    pub status_code: Option<i32>,
+    pub latency_ms: i32,
+    pub error: Option<String>,
+}
+
+impl HealthCheck {
+    /// Columns selected by every query that builds a `HealthCheck`.
+    pub const COLUMNS: &'static str = "checked_at, healthy, status_code, latency_ms, error";
+
+    pub fn from_row(row: &Row) -> Self {
+        HealthCheck {
+            checked_at: row.get("checked_at"),
+            healthy: row.get("healthy"),
+            status_code: row.get("status_code"),
+            latency_ms: row.get("latency_ms"),
+            error: row.get("error"),
+        }
+    }
+}
@@ -0,0 +1,509 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use crate::models::targeting::{LanguageRule, TargetingRule};
+use crate::models::{Domain, HealthCheck, LinkMetadata, Routing, UtmParams, Variant};
+use crate::utils::language::{negotiate_language, LANG_PLACEHOLDER};
+use crate::utils::template::is_template;
+use crate::utils::user_agent::parse_user_agent;
+use crate::utils::variant::pick_variant;
+use crate::utils::visitor::Visitor;
+use std::str::FromStr;
+use tokio_postgres::types::Json;
+use tokio_postgres::Row;
+
+/// A stored short link.
+#[derive(Debug, Clone, Serialize)]
+pub struct Link {
+    pub id: i32,
+    pub short_code: String,
+    #[serde(skip)]
+    pub domain_id: Option<i32>,
+    /// Host of the link's branded domain; `None` for the default domain.
+    pub domain: Option<String>,
+    pub original_url: String,
+    pub created_at: Option<NaiveDateTime>,
+    pub active_from: Option<DateTime<Utc>>,
+    pub active_until: Option<DateTime<Utc>>,
+    pub upcoming_url: Option<String>,
+    pub ended_url: Option<String>,
+    pub tags: Vec<String>,
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    /// Folder the link is filed in, such as `marketing/spring`.
+    pub folder: Option<String>,
+    /// API key that created the link; `None` for anonymous links.
+    pub owner_key_id: Option<i32>,
+    pub workspace_id: Option<i32>,
+    pub password_protected: bool,
+    pub single_use: bool,
+    pub consumed_at: Option<DateTime<Utc>>,
+    /// Visitors see the preview page before continuing to the destination.
+    pub interstitial: bool,
+    /// Destination used while the primary one fails health checks.
+    pub fallback_url: Option<String>,
+    /// Outcome of the latest health check; `None` until checked.
+    pub healthy: Option<bool>,
+    pub health_checked_at: Option<DateTime<Utc>>,
+    pub targeting_rules: Vec<TargetingRule>,
+    pub language_rules: Vec<LanguageRule>,
+    pub default_language: Option<String>,
+    pub variants: Vec<Variant>,
+    pub passthrough: PassthroughMode,
+    #[serde(flatten)]
+    pub utm: UtmParams,
+    /// Campaign preset the UTM parameters came from.
+    pub campaign: Option<String>,
+    /// When the link was moved to the trash; `None` while it is in use.
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pub metadata: LinkMetadata,
+    /// When the destination page was fetched; `None` until it has been.
+    pub metadata_fetched_at: Option<DateTime<Utc>>,
+}
+
+impl Link {
+    /// Columns selected by every query that builds a `Link`.
+    pub const COLUMNS: &'static str = "id, short_code, domain_id, \
+        (SELECT host FROM domains WHERE domains.id = shortlink.domain_id) AS domain, \
+        original_url, created_at, active_from, active_until, \
+        upcoming_url, ended_url, tags, title, notes, folder, owner_key_id, workspace_id, \
+        (password_hash IS NOT NULL) AS password_protected, single_use, consumed_at, interstitial, \
+        fallback_url, healthy, health_checked_at, targeting_rules, language_rules, default_language, \
+        variants, passthrough, utm, campaign, deleted_at, \
+        page_title, page_description, favicon_url, image_url, metadata_fetched_at";
+
+    pub fn from_row(row: &Row) -> Self {
+        Link {
+            id: row.get("id"),
+            short_code: row.get("short_code"),
+            domain_id: row.get("domain_id"),
+            domain: row.get("domain"),
+            original_url: row.get("original_url"),
+            created_at: row.get("created_at"),
+            active_from: row.get("active_from"),
+            active_until: row.get("active_until"),
+            upcoming_url: row.get("upcoming_url"),
+            ended_url: row.get("ended_url"),
+            tags: row.get("tags"),
+            title: row.get("title"),
+            notes: row.get("notes"),
+            folder: row.get("folder"),
+            owner_key_id: row.get("owner_key_id"),
+            workspace_id: row.get("workspace_id"),
+            password_protected: row.get("password_protected"),
+            single_use: row.get("single_use"),
+            consumed_at: row.get("consumed_at"),
+            interstitial: row.get("interstitial"),
+            fallback_url: row.get("fallback_url"),
+            healthy: row.get("healthy"),
+            health_checked_at: row.get("health_checked_at"),
+            targeting_rules: row.get::<_, Json<Vec<TargetingRule>>>("targeting_rules").0,
+            language_rules: row.get::<_, Json<Vec<LanguageRule>>>("language_rules").0,
+            default_language: row.get("default_language"),
+            variants: row.get::<_, Json<Vec<Variant>>>("variants").0,
+            passthrough: row.get::<_, &str>("passthrough").parse().unwrap_or_default(),
+            utm: row.get::<_, Json<UtmParams>>("utm").0,
+            campaign: row.get("campaign"),
+            deleted_at: row.get("deleted_at"),
+            metadata: LinkMetadata {
+                page_title: row.get("page_title"),
+                page_description: row.get("page_description"),
+                favicon_url: row.get("favicon_url"),
+                image_url: row.get("image_url"),
+            },
+            metadata_fetched_at: row.get("metadata_fetched_at"),
+        }
+    }
+}
+
+/// What a link forwards from the visited URL to its destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PassthroughMode {
+    /// Ignore anything after the short code
+    #[default]
+    None,
+    /// Forward the query string, e.g. `utm_source`
+    Query,
+    /// Append the path after the short code to the destination path
+    Path,
+    /// Forward both the path and the query string
+    Both,
+}
+
+impl PassthroughMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PassthroughMode::None => "none",
+            PassthroughMode::Query => "query",
+            PassthroughMode::Path => "path",
+            PassthroughMode::Both => "both",
+        }
+    }
+
+    pub fn forwards_query(self) -> bool {
+        matches!(self, PassthroughMode::Query | PassthroughMode::Both)
+    }
+
+    pub fn forwards_path(self) -> bool {
+        matches!(self, PassthroughMode::Path | PassthroughMode::Both)
+    }
+}
+
+impl FromStr for PassthroughMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(PassthroughMode::None),
+            "query" => Ok(PassthroughMode::Query),
+            "path" => Ok(PassthroughMode::Path),
+            "both" => Ok(PassthroughMode::Both),
+            _ => Err(format!("unknown passthrough mode {:?}", value)),
+        }
+    }
+}
+
+/// A link about to be created, apart from its short code and owner.
+#[derive(Debug, Clone, Default)]
+pub struct NewLink {
+    /// Branded domain the link is created on; `None` for the default domain.
+    pub domain: Option<Domain>,
+    pub original_url: String,
+    pub password_hash: Option<String>,
+    pub single_use: bool,
+    pub interstitial: bool,
+    pub active_from: Option<DateTime<Utc>>,
+    pub active_until: Option<DateTime<Utc>>,
+    pub upcoming_url: Option<String>,
+    pub ended_url: Option<String>,
+    pub fallback_url: Option<String>,
+    pub targeting_rules: Vec<TargetingRule>,
+    pub language_rules: Vec<LanguageRule>,
+    pub default_language: Option<String>,
+    pub variants: Vec<Variant>,
+    pub passthrough: PassthroughMode,
+    pub utm: UtmParams,
+    pub campaign: Option<String>,
+    pub labels: LinkLabels,
+}
+
+impl NewLink {
+    pub fn domain_id(&self) -> Option<i32> {
+        self.domain.as_ref().map(|domain| domain.id)
+    }
+
+    /// Whether an identical request may be answered with this link instead of a new one.
+    /// Links with access restrictions, a schedule or alternative destinations are never shared.
+    pub fn is_reusable(&self) -> bool {
+        self.password_hash.is_none()
+            && !self.single_use
+            && !self.interstitial
+            && self.active_from.is_none()
+            && self.active_until.is_none()
+            && self.fallback_url.is_none()
+            && self.targeting_rules.is_empty()
+            && self.language_rules.is_empty()
+            && self.default_language.is_none()
+            && self.variants.is_empty()
+            && self.passthrough == PassthroughMode::None
+            && self.utm.is_empty()
+            && self.labels.is_empty()
+    }
+}
+
+/// How a link is organized: its tags, title, notes and folder.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkLabels {
+    pub tags: Vec<String>,
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    pub folder: Option<String>,
+}
+
+impl LinkLabels {
+    pub fn is_empty(&self) -> bool {
+        *self == LinkLabels::default()
+    }
+}
+
+impl From<&Link> for LinkLabels {
+    fn from(link: &Link) -> Self {
+        LinkLabels {
+            tags: link.tags.clone(),
+            title: link.title.clone(),
+            notes: link.notes.clone(),
+            folder: link.folder.clone(),
+        }
+    }
+}
+
+/// Changes to a link's labels. Fields left out are kept; an empty string or list clears them.
+#[derive(Debug, Default, Deserialize)]
+pub struct LabelsRequest {
+    pub tags: Option<Vec<String>>,
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    pub folder: Option<String>,
+}
+
+impl LabelsRequest {
+    /// The labels of a link after applying these changes.
+    pub fn apply(self, labels: LinkLabels) -> LinkLabels {
+        let replace = |change: Option<String>, current: Option<String>| match change {
+            Some(value) if value.trim().is_empty() => None,
+            Some(value) => Some(value),
+            None => current,
+        };
+        LinkLabels {
+            tags: self.tags.unwrap_or(labels.tags),
+            title: replace(self.title, labels.title),
+            notes: replace(self.notes, labels.notes),
+            folder: replace(self.folder, labels.folder),
+        }
+    }
+}
+
+/// Changes to a link made through the link API: its labels, and whether visitors see the preview page first.
+#[derive(Debug, Default, Deserialize)]
+pub struct LinkPatch {
+    #[serde(flatten)]
+    pub labels: LabelsRequest,
+    pub interstitial: Option<bool>,
+}
+
+/// Where a link stands relative to its activation window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Availability {
+    /// The link opens at this time, which is still in the future.
+    Upcoming(DateTime<Utc>),
+    Active,
+    /// `active_until` has passed.
+    Ended,
+}
+
+/// What the redirect needs to know about a link. This is what gets cached in Redis,
+/// except for single-use links, which are never cached.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Destination {
+    pub original_url: String,
+    #[serde(default)]
+    pub active_from: Option<DateTime<Utc>>,
+    #[serde(default, alias = "expires_at")]
+    pub active_until: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub upcoming_url: Option<String>,
+    #[serde(default)]
+    pub ended_url: Option<String>,
+    /// Only whether the link has a password is cached; the hash stays in the database.
+    /// Required, so cache entries from before it was added are not read as unprotected.
+    pub password_protected: bool,
+    #[serde(default)]
+    pub single_use: bool,
+    #[serde(default)]
+    pub consumed: bool,
+    #[serde(default)]
+    pub fallback_url: Option<String>,
+    #[serde(default)]
+    pub healthy: Option<bool>,
+    #[serde(default)]
+    pub targeting_rules: Vec<TargetingRule>,
+    #[serde(default)]
+    pub language_rules: Vec<LanguageRule>,
+    #[serde(default)]
+    pub default_language: Option<String>,
+    #[serde(default)]
+    pub variants: Vec<Variant>,
+    #[serde(default)]
+    pub passthrough: PassthroughMode,
+    #[serde(default)]
+    pub utm: UtmParams,
+    /// The link is in the trash.
+    #[serde(default)]
+    pub deleted: bool,
+    /// Visitors see the preview page before continuing.
+    #[serde(default)]
+    pub interstitial: bool,
+}
+
+impl Destination {
+    /// Columns selected by every query that builds a `Destination`.
+    pub const COLUMNS: &'static str =
+        "original_url, active_from, active_until, upcoming_url, ended_url, \
+         (password_hash IS NOT NULL) AS password_protected, single_use, (consumed_at IS NOT NULL) AS consumed, \
+         fallback_url, healthy, targeting_rules, language_rules, default_language, variants, passthrough, utm, \
+         (deleted_at IS NOT NULL) AS deleted, interstitial";
+
+    pub fn from_row(row: &Row) -> Self {
+        Destination {
+            original_url: row.get("original_url"),
+            active_from: row.get("active_from"),
+            active_until: row.get("active_until"),
+            upcoming_url: row.get("upcoming_url"),
+            ended_url: row.get("ended_url"),
+            password_protected: row.get("password_protected"),
+            single_use: row.get("single_use"),
+            consumed: row.get("consumed"),
+            fallback_url: row.get("fallback_url"),
+            healthy: row.get("healthy"),
+            targeting_rules: row.get::<_, Json<Vec<TargetingRule>>>("targeting_rules").0,
+            language_rules: row.get::<_, Json<Vec<LanguageRule>>>("language_rules").0,
+            default_language: row.get("default_language"),
+            variants: row.get::<_, Json<Vec<Variant>>>("variants").0,
+            passthrough: row.get::<_, &str>("passthrough").parse().unwrap_or_default(),
+            utm: row.get::<_, Json<UtmParams>>("utm").0,
+            deleted: row.get("deleted"),
+            interstitial: row.get("interstitial"),
+        }
+    }
+
+    /// Whether a visit may add a path after the short code: the link forwards it, or one of the
+    /// destinations visitors can be routed to is a template.
+    pub fn accepts_path(&self) -> bool {
+        self.passthrough.forwards_path()
+            || std::iter::once(&self.original_url)
+                .chain(&self.fallback_url)
+                .chain(self.targeting_rules.iter().map(|rule| &rule.url))
+                .chain(self.language_rules.iter().filter_map(|rule| rule.url.as_ref()))
+                .chain(self.variants.iter().map(|variant| &variant.url))
+                .any(|url| is_template(url))
+    }
+
+    /// Where a visitor is sent, and how they were routed there.
+    ///
+    /// The first targeting rule matching their device and location wins, then the destination
+    /// of their negotiated language, then their variant, then the current destination. `{lang}`
+    /// in the chosen destination is replaced by the negotiated language.
+    pub fn route(&self, visitor: &Visitor) -> (String, Routing) {
+        let user_agent = parse_user_agent(visitor.user_agent.as_deref());
+        let language = self.language(visitor.accept_language.as_deref());
+        let mut variant = None;
+        let url = self
+            .targeting_rules
+            .iter()
+            .find(|rule| rule.matches(&user_agent, &visitor.location))
+            .map(|rule| rule.url.as_str())
+            .or_else(|| {
+                let lang = language?;
+                self.language_rules.iter().find(|rule| rule.lang == lang)?.url.as_deref()
+            })
+            .or_else(|| {
+                variant = self.variant(visitor.variant.as_deref());
+                variant.map(|variant| variant.url.as_str())
+            })
+            .unwrap_or_else(|| self.current_url());
+        let url = match language {
+            Some(lang) => url.replace(LANG_PLACEHOLDER, lang),
+            None => url.to_string(),
+        };
+        let routing = Routing {
+            language: language.map(str::to_string),
+            variant: variant.map(|variant| variant.name.clone()),
+        };
+        (url, routing)
+    }
+
+    /// The variant a visitor keeps, or a new one drawn by weight.
+    fn variant(&self, assigned: Option<&str>) -> Option<&Variant> {
+        assigned
+            .and_then(|name| self.variants.iter().find(|variant| variant.name == name))
+            .or_else(|| pick_variant(&self.variants))
+    }
+
+    /// The language to send a visitor to, for links with language rules or a default language.
+    fn language(&self, accept_language: Option<&str>) -> Option<&str> {
+        let default = self.default_language.as_deref();
+        if self.language_rules.is_empty() && default.is_none() {
+            return None;
+        }
+        let available: Vec<&str> = self.language_rules.iter().map(|rule| rule.lang.as_str()).chain(default).collect();
+        negotiate_language(accept_language, &available, default).or(default)
+    }
+
+    /// Where visitors are sent: the fallback while the primary destination is unhealthy.
+    pub fn current_url(&self) -> &str {
+        match (&self.fallback_url, self.healthy) {
+            (Some(fallback_url), Some(false)) => fallback_url,
+            _ => &self.original_url,
+        }
+    }
+
+    /// Where the link stands at `now`.
+    pub fn availability(&self, now: DateTime<Utc>) -> Availability {
+        match (self.active_from, self.active_until) {
+            (Some(from), _) if now < from => Availability::Upcoming(from),
+            (_, Some(until)) if now >= until => Availability::Ended,
+            _ => Availability::Active,
+        }
+    }
+
+    /// The next time the link changes availability, if any.
+    pub fn next_transition(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        [self.active_from, self.active_until]
+            .into_iter()
+            .flatten()
+            .filter(|&time| time > now)
+            .min()
+    }
+}
+
+/// A link with its recent health checks, as returned by the link metadata API.
+#[derive(Debug, Serialize)]
+pub struct LinkDetails {
+    #[serde(flatten)]
+    pub link: Link,
+    pub short_url: String,
+    pub health_checks: Vec<HealthCheck>,
+}
+
+/// A link in the trash, as listed by the trash API.
+#[derive(Debug, Serialize)]
+pub struct TrashedLink {
+    #[serde(flatten)]
+    pub link: Link,
+    pub short_url: String,
+    /// When the link is deleted for good and its short code can be taken again.
+    pub purge_at: Option<DateTime<Utc>>,
+}
+
+/// Paging of the trash API.
+#[derive(Debug, Default, Deserialize)]
+pub struct TrashQuery {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Filters of the link listing, newest links first.
+#[derive(Debug, Default, Deserialize)]
+pub struct LinkSearchQuery {
+    /// Words to look for in the title, notes and destination.
+    pub q: Option<String>,
+    /// Comma-separated tags the links must all have.
+    pub tag: Option<String>,
+    pub folder: Option<String>,
+    /// Host of a branded domain; links on every domain if omitted.
+    pub domain: Option<String>,
+    pub owner_key_id: Option<i32>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// A link as listed by the link listing, with its short URL.
+#[derive(Debug, Serialize)]
+pub struct ListedLink {
+    #[serde(flatten)]
+    pub link: Link,
+    pub short_url: String,
+}
+
+/// A page of the link listing.
+#[derive(Debug, Serialize)]
+pub struct LinkPage {
+    pub links: Vec<ListedLink>,
+    /// Pass as `cursor` to get the next page; `None` on the last page.
+    pub next_cursor: Option<String>,
+}
@@ -0,0 +1,13 @@
+use serde::Serialize;
+
+/// Details of a link's destination page, read from its HTML.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LinkMetadata {
+    /// The page's `<title>`, or its `og:title`.
+    pub page_title: Option<String>,
+    /// The page's `description` or `og:description` meta tag.
+    pub page_description: Option<String>,
+    pub favicon_url: Option<String>,
+    /// The page's `og:image`.
+    pub image_url: Option<String>,
+}
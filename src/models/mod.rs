@@ -1,3 +1,39 @@
+pub mod api_key;
+pub mod audit;
+pub mod bulk;
+pub mod campaign;
+pub mod click;
+pub mod domain;
+pub mod health_check;
+pub mod link;
+pub mod metadata;
+pub mod revision;
 pub mod shorten_response;
+pub mod targeting;
+pub mod transfer;
+pub mod unlock;
+pub mod variant;
+pub mod workspace;
 
+pub use api_key::ApiKey;
+pub use audit::{Actor, AuditAction, AuditEvent, AuditQuery, NewAuditEvent};
+pub use bulk::{BulkShortenResponse, BulkShortenResult};
+pub use campaign::{Campaign, CampaignRequest, UtmParams};
+pub use click::{Click, Routing};
+pub use domain::{Domain, DomainQuery};
+pub use health_check::HealthCheck;
+pub use link::{
+    Availability, Destination, LabelsRequest, Link, LinkDetails, LinkLabels, LinkPage, LinkPatch, LinkSearchQuery, ListedLink, NewLink,
+    PassthroughMode, TrashQuery, TrashedLink,
+};
+pub use metadata::LinkMetadata;
+pub use revision::LinkRevision;
 pub use shorten_response::{ShortenResponse, ShortenRequest};
+pub use targeting::{LanguageRule, TargetingRule};
+pub use transfer::{ExportQuery, ImportQuery};
+pub use unlock::UnlockForm;
+pub use variant::{Variant, VariantStats, VariantStatsResponse};
+pub use workspace::{
+    AcceptedInvite, ApiKeyRequest, ApiKeyResponse, Invite, InviteRequest, InviteResponse, Member, Role, RoleRequest,
+    Scope, Workspace, WorkspaceDetails, WorkspaceRequest,
+};
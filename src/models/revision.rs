@@ -0,0 +1,54 @@
+use crate::models::{LanguageRule, PassthroughMode, TargetingRule, UtmParams, Variant};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::types::Json;
+use tokio_postgres::Row;
+
+/// A version of a link's destination, rules and schedule.
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkRevision {
+    /// Number of the version, counting from 1 for the link as it was created.
+    pub revision: i32,
+    pub original_url: String,
+    pub active_from: Option<DateTime<Utc>>,
+    pub active_until: Option<DateTime<Utc>>,
+    pub upcoming_url: Option<String>,
+    pub ended_url: Option<String>,
+    pub fallback_url: Option<String>,
+    pub targeting_rules: Vec<TargetingRule>,
+    pub language_rules: Vec<LanguageRule>,
+    pub default_language: Option<String>,
+    pub variants: Vec<Variant>,
+    pub passthrough: PassthroughMode,
+    #[serde(flatten)]
+    pub utm: UtmParams,
+    pub campaign: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl LinkRevision {
+    /// Columns selected by every query that builds a `LinkRevision`.
+    pub const COLUMNS: &'static str = "revision, original_url, active_from, active_until, upcoming_url, ended_url, \
+        fallback_url, targeting_rules, language_rules, default_language, variants, passthrough, utm, campaign, \
+        created_at";
+
+    pub fn from_row(row: &Row) -> Self {
+        LinkRevision {
+            revision: row.get("revision"),
+            original_url: row.get("original_url"),
+            active_from: row.get("active_from"),
+            active_until: row.get("active_until"),
+            upcoming_url: row.get("upcoming_url"),
+            ended_url: row.get("ended_url"),
+            fallback_url: row.get("fallback_url"),
+            targeting_rules: row.get::<_, Json<Vec<TargetingRule>>>("targeting_rules").0,
+            language_rules: row.get::<_, Json<Vec<LanguageRule>>>("language_rules").0,
+            default_language: row.get("default_language"),
+            variants: row.get::<_, Json<Vec<Variant>>>("variants").0,
+            passthrough: row.get::<_, &str>("passthrough").parse().unwrap_or_default(),
+            utm: row.get::<_, Json<UtmParams>>("utm").0,
+            campaign: row.get("campaign"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::models::{LanguageRule, PassthroughMode, TargetingRule, UtmParams, Variant};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 /// Response structure for shortened URL.
@@ -8,7 +10,68 @@ pub struct ShortenResponse {
 }
 
 /// Request structure for shortening URL.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ShortenRequest {
     pub url: String,
+    /// Host of the branded domain to create the link on; defaults to the domain the request was made on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// Visitors must enter this password before being redirected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// The link works once and is then used up.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub single_use: bool,
+    /// Visitors see the preview page and continue from there instead of being redirected straight away.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interstitial: bool,
+    /// The link does not resolve before this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_from: Option<DateTime<Utc>>,
+    /// The link stops resolving at this time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active_until: Option<DateTime<Utc>>,
+    /// Where to send visitors before `active_from`, instead of showing a page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upcoming_url: Option<String>,
+    /// Where to send visitors after `active_until`, instead of showing a page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended_url: Option<String>,
+    /// Where to send visitors while `url` fails health checks.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback_url: Option<String>,
+    /// Alternative destinations by device, OS or browser; the first matching rule wins.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targeting_rules: Vec<TargetingRule>,
+    /// Languages the link is available in, negotiated with the `Accept-Language` header.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub language_rules: Vec<LanguageRule>,
+    /// Language used when none of the visitor's languages is available.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_language: Option<String>,
+    /// Destinations splitting the traffic by weight; each visitor sticks to one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<Variant>,
+    /// What to forward from the visited URL: `none`, `query`, `path` or `both`.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub passthrough: PassthroughMode,
+    /// `utm_source`, `utm_medium`, `utm_campaign`, `utm_term` and `utm_content` added to the destinations.
+    #[serde(flatten)]
+    pub utm: UtmParams,
+    /// Name of a campaign preset supplying the UTM parameters not given explicitly.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub campaign: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Folder to file the link in, such as `marketing/spring`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
 }
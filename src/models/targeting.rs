@@ -0,0 +1,176 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Operating system named in a targeting rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Os {
+    Ios,
+    Android,
+    Windows,
+    Macos,
+    Linux,
+    Chromeos,
+}
+
+/// Kind of device named in a targeting rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceClass {
+    Mobile,
+    Tablet,
+    Desktop,
+    Bot,
+}
+
+/// Browser named in a targeting rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Browser {
+    Chrome,
+    Safari,
+    Firefox,
+    Edge,
+    Opera,
+    Samsung,
+}
+
+/// Continent named in a targeting rule, by its two-letter code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Continent {
+    Af,
+    An,
+    As,
+    Eu,
+    Na,
+    Oc,
+    Sa,
+}
+
+impl FromStr for Continent {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        parse_name(&code.to_uppercase())
+    }
+}
+
+/// Where a visitor is, according to the GeoIP database.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GeoLocation {
+    /// ISO 3166-1 alpha-2 code, uppercase.
+    pub country: Option<String>,
+    pub continent: Option<Continent>,
+}
+
+/// What the `User-Agent` header says about a visitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserAgent {
+    pub os: Option<Os>,
+    pub device: DeviceClass,
+    pub browser: Option<Browser>,
+}
+
+/// Send visitors matching every given condition to `url`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetingRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<Os>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<DeviceClass>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub browser: Option<Browser>,
+    /// ISO 3166-1 alpha-2 country code.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub continent: Option<Continent>,
+    pub url: String,
+}
+
+impl TargetingRule {
+    /// Whether the rule has at least one condition; a rule without any would match everyone.
+    pub fn has_conditions(&self) -> bool {
+        self.os.is_some()
+            || self.device.is_some()
+            || self.browser.is_some()
+            || self.country.is_some()
+            || self.continent.is_some()
+    }
+
+    pub fn matches(&self, user_agent: &UserAgent, location: &GeoLocation) -> bool {
+        self.os.is_none_or(|os| user_agent.os == Some(os))
+            && self.device.is_none_or(|device| user_agent.device == device)
+            && self.browser.is_none_or(|browser| user_agent.browser == Some(browser))
+            && self.country.as_ref().is_none_or(|country| location.country.as_ref() == Some(country))
+            && self.continent.is_none_or(|continent| location.continent == Some(continent))
+    }
+}
+
+/// Parses the CLI form `os=ios,device=mobile=>https://example.com`.
+impl FromStr for TargetingRule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (conditions, url) = value
+            .split_once("=>")
+            .ok_or_else(|| format!("expected CONDITIONS=>URL, got {:?}", value))?;
+        let mut rule = TargetingRule {
+            os: None,
+            device: None,
+            browser: None,
+            country: None,
+            continent: None,
+            url: url.trim().to_string(),
+        };
+        for condition in conditions.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+            let (key, name) = condition
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, got {:?}", condition))?;
+            let name = name.trim();
+            match key.trim() {
+                "os" => rule.os = Some(parse_name(&name.to_lowercase())?),
+                "device" => rule.device = Some(parse_name(&name.to_lowercase())?),
+                "browser" => rule.browser = Some(parse_name(&name.to_lowercase())?),
+                "country" => rule.country = Some(name.to_uppercase()),
+                "continent" => rule.continent = Some(name.parse()?),
+                other => {
+                    return Err(format!(
+                        "unknown condition {:?}, expected os, device, browser, country or continent",
+                        other
+                    ))
+                }
+            }
+        }
+        Ok(rule)
+    }
+}
+
+/// A language a link is available in, optionally with its own destination.
+///
+/// Without a `url`, the link's destination is used, with `{lang}` replaced by `lang`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LanguageRule {
+    pub lang: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// Parses the CLI form `fr` or `fr=>https://example.fr`.
+impl FromStr for LanguageRule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value.split_once("=>") {
+            Some((lang, url)) => LanguageRule { lang: lang.trim().to_string(), url: Some(url.trim().to_string()) },
+            None => LanguageRule { lang: value.trim().to_string(), url: None },
+        })
+    }
+}
+
+/// Parse one of the names a targeting enum is serialized as.
+fn parse_name<T: DeserializeOwned>(name: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(name.to_string())).map_err(|_| format!("unknown value {:?}", name))
+}
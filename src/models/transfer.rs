@@ -0,0 +1,18 @@
+use crate::utils::import::{ConflictPolicy, TransferFormat};
+use serde::Deserialize;
+
+/// Query parameters of the export endpoints.
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: TransferFormat,
+}
+
+/// Query parameters of the import endpoint.
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub format: TransferFormat,
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+}
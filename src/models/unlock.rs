@@ -0,0 +1,9 @@
+use serde::Deserialize;
+
+/// Form posted from the password prompt of a protected link, or from the Continue button of
+/// the preview page, which sends no password.
+#[derive(Debug, Deserialize)]
+pub struct UnlockForm {
+    #[serde(default)]
+    pub password: String,
+}
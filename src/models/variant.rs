@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// One destination of a link splitting its traffic, receiving a share proportional to `weight`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Variant {
+    pub name: String,
+    pub url: String,
+    pub weight: u32,
+}
+
+/// Parses the CLI form `NAME:WEIGHT=>URL`, e.g. `a:70=>https://example.com/a`.
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, url) = value
+            .split_once("=>")
+            .ok_or_else(|| format!("expected NAME:WEIGHT=>URL, got {:?}", value))?;
+        let (name, weight) = name
+            .split_once(':')
+            .ok_or_else(|| format!("expected NAME:WEIGHT=>URL, got {:?}", value))?;
+        Ok(Variant {
+            name: name.trim().to_string(),
+            url: url.trim().to_string(),
+            weight: weight.trim().parse().map_err(|_| format!("invalid weight {:?}", weight))?,
+        })
+    }
+}
+
+/// Clicks of one variant, as returned by the variant stats API.
+#[derive(Debug, Serialize)]
+pub struct VariantStats {
+    pub name: String,
+    pub url: String,
+    pub weight: u32,
+    pub clicks: i64,
+    /// Fraction of all variant clicks that went to this variant.
+    pub share: f64,
+}
+
+/// Clicks per variant of a link.
+#[derive(Debug, Serialize)]
+pub struct VariantStatsResponse {
+    pub short_code: String,
+    pub total_clicks: i64,
+    pub variants: Vec<VariantStats>,
+}
@@ -0,0 +1,242 @@
+use crate::models::ApiKey;
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tokio_postgres::Row;
+
+/// A permission an API key can be granted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum Scope {
+    /// Read links, their stats and exports, and campaign presets
+    ReadLinks,
+    /// Create, import and change links and campaign presets
+    WriteLinks,
+    /// Invite members, change their roles and remove them
+    ManageMembers,
+    /// Issue and revoke the workspace's API keys
+    ManageKeys,
+    /// Rename the workspace
+    ManageWorkspace,
+    /// Read the audit log of the workspace
+    ReadAudit,
+}
+
+impl Scope {
+    pub const ALL: &'static [Scope] = &[
+        Scope::ReadLinks,
+        Scope::WriteLinks,
+        Scope::ManageMembers,
+        Scope::ManageKeys,
+        Scope::ManageWorkspace,
+        Scope::ReadAudit,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ReadLinks => "read_links",
+            Scope::WriteLinks => "write_links",
+            Scope::ManageMembers => "manage_members",
+            Scope::ManageKeys => "manage_keys",
+            Scope::ManageWorkspace => "manage_workspace",
+            Scope::ReadAudit => "read_audit",
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .iter()
+            .find(|scope| scope.as_str() == value)
+            .copied()
+            .ok_or_else(|| format!("unknown scope {:?}", value))
+    }
+}
+
+/// The role of a workspace member, which decides what its API keys may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Owner,
+    Admin,
+    Editor,
+    Viewer,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Admin => "admin",
+            Role::Editor => "editor",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    /// What members with this role are allowed to do.
+    pub fn scopes(&self) -> &'static [Scope] {
+        match self {
+            Role::Owner => Scope::ALL,
+            Role::Admin => &[
+                Scope::ReadLinks,
+                Scope::WriteLinks,
+                Scope::ManageMembers,
+                Scope::ManageKeys,
+                Scope::ReadAudit,
+            ],
+            Role::Editor => &[Scope::ReadLinks, Scope::WriteLinks],
+            Role::Viewer => &[Scope::ReadLinks],
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "owner" => Ok(Role::Owner),
+            "admin" => Ok(Role::Admin),
+            "editor" => Ok(Role::Editor),
+            "viewer" => Ok(Role::Viewer),
+            _ => Err(format!("unknown role {:?}", value)),
+        }
+    }
+}
+
+/// A team sharing the management of its links.
+#[derive(Debug, Clone, Serialize)]
+pub struct Workspace {
+    pub id: i32,
+    pub name: String,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl Workspace {
+    /// Columns selected by every query that builds a `Workspace`.
+    pub const COLUMNS: &'static str = "id, name, created_at";
+
+    pub fn from_row(row: &Row) -> Self {
+        Workspace {
+            id: row.get("id"),
+            name: row.get("name"),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+/// A person belonging to a workspace.
+#[derive(Debug, Clone, Serialize)]
+pub struct Member {
+    pub id: i32,
+    pub workspace_id: i32,
+    pub email: String,
+    pub role: Role,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl Member {
+    /// Columns selected by every query that builds a `Member`.
+    pub const COLUMNS: &'static str = "id, workspace_id, email, role, created_at";
+
+    pub fn from_row(row: &Row) -> Self {
+        Member {
+            id: row.get("id"),
+            workspace_id: row.get("workspace_id"),
+            email: row.get("email"),
+            role: row.get::<_, &str>("role").parse().unwrap_or(Role::Viewer),
+            created_at: row.get("created_at"),
+        }
+    }
+}
+
+/// An invitation to join a workspace, accepted by visiting its token link.
+#[derive(Debug, Clone, Serialize)]
+pub struct Invite {
+    pub id: i32,
+    pub workspace_id: i32,
+    pub email: String,
+    pub role: Role,
+    pub created_at: Option<NaiveDateTime>,
+    pub expires_at: NaiveDateTime,
+}
+
+impl Invite {
+    /// Columns selected by every query that builds an `Invite`.
+    pub const COLUMNS: &'static str = "id, workspace_id, email, role, created_at, expires_at";
+
+    pub fn from_row(row: &Row) -> Self {
+        Invite {
+            id: row.get("id"),
+            workspace_id: row.get("workspace_id"),
+            email: row.get("email"),
+            role: row.get::<_, &str>("role").parse().unwrap_or(Role::Viewer),
+            created_at: row.get("created_at"),
+            expires_at: row.get("expires_at"),
+        }
+    }
+}
+
+/// A workspace with its members, as shown to its API keys.
+#[derive(Debug, Serialize)]
+pub struct WorkspaceDetails {
+    #[serde(flatten)]
+    pub workspace: Workspace,
+    pub members: Vec<Member>,
+}
+
+/// Request structure for renaming a workspace.
+#[derive(Debug, Deserialize)]
+pub struct WorkspaceRequest {
+    pub name: String,
+}
+
+/// Request structure for inviting someone to a workspace.
+#[derive(Debug, Deserialize)]
+pub struct InviteRequest {
+    pub email: String,
+    pub role: Role,
+}
+
+/// Response structure for a new invite; the token is only shown here.
+#[derive(Debug, Serialize)]
+pub struct InviteResponse {
+    #[serde(flatten)]
+    pub invite: Invite,
+    pub invite_url: String,
+}
+
+/// Request structure for changing the role of a member.
+#[derive(Debug, Deserialize)]
+pub struct RoleRequest {
+    pub role: Role,
+}
+
+/// Request structure for issuing an API key; without scopes it gets all of the issuer's.
+#[derive(Debug, Deserialize)]
+pub struct ApiKeyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Option<Vec<Scope>>,
+}
+
+/// Response structure for a new API key; the secret is only shown here.
+#[derive(Debug, Serialize)]
+pub struct ApiKeyResponse {
+    #[serde(flatten)]
+    pub key: ApiKey,
+    pub secret: String,
+}
+
+/// Response structure for an accepted invite, with the new member's API key.
+#[derive(Debug, Serialize)]
+pub struct AcceptedInvite {
+    pub workspace: Workspace,
+    pub member: Member,
+    pub key: ApiKeyResponse,
+}
@@ -1,3 +1,6 @@
+#[allow(clippy::module_inception)]
 mod routes;
+mod server;
 
-pub use routes::create_routes;
\ No newline at end of file
+pub use routes::{create_routes, route_template};
+pub use server::serve;
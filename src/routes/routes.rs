@@ -1,8 +1,35 @@
-use crate::handlers::{handle_rejection, redirect_url, shorten_url};
-use crate::views::{index::index, not_found::not_found};
+use crate::handlers::{
+    bulk_shorten, delete_key, delete_short_link, get_audit_events, delete_member, export_clicks, export_links, get_campaigns, get_invites, get_keys,
+    get_link_details, get_link_revisions, get_links, get_trash, get_variant_stats, get_workspace_details, handle_rejection, home, import_links, patch_link, post_campaign,
+    post_invite, post_invite_acceptance, post_key, post_member_role, post_revision_restore, post_trash_restore, post_workspace, preview_link, redirect_url, shorten_url,
+    unlock_link,
+};
+use crate::models::{AuditQuery, DomainQuery, ExportQuery, ImportQuery, LinkSearchQuery, TrashQuery};
+use crate::models::ApiKey;
+use crate::utils::auth::{authenticate, authenticate_optional};
+use crate::utils::domain::request_domain;
+use crate::utils::idempotency::IDEMPOTENCY_KEY_HEADER;
+use crate::utils::passthrough::forwarded;
+use crate::utils::request_context::client_addr;
+use crate::utils::password::UNLOCK_COOKIE;
+use crate::utils::visitor::visitor;
+use crate::views::not_found::not_found;
 use deadpool_postgres::Pool;
 use deadpool_redis::Pool as RedisPool;
-use warp::Filter;
+use warp::{Filter, Rejection};
+
+/// Largest request body accepted by the bulk endpoint.
+const BULK_MAX_BODY_BYTES: u64 = 4 * 1024 * 1024;
+/// Largest request body accepted by the import endpoint.
+const IMPORT_MAX_BODY_BYTES: u64 = 64 * 1024 * 1024;
+/// Largest password form accepted when unlocking a link.
+const UNLOCK_MAX_BODY_BYTES: u64 = 4 * 1024;
+/// Largest campaign preset accepted.
+const CAMPAIGN_MAX_BODY_BYTES: u64 = 16 * 1024;
+/// Largest workspace, member, invite or API key request accepted.
+const WORKSPACE_MAX_BODY_BYTES: u64 = 4 * 1024;
+/// Largest change of link labels accepted.
+const LABELS_MAX_BODY_BYTES: u64 = 16 * 1024;
 
 /// Create the routes for the application.
 pub fn create_routes(
@@ -11,29 +38,391 @@ pub fn create_routes(
 ) -> warp::filters::BoxedFilter<(impl warp::Reply,)> {
     let shorten = warp::post()
         .and(warp::path("shorten"))
+        .and(request_domain(db_pool.clone()))
         .and(warp::body::json())
+        .and(warp::header::optional::<String>(IDEMPOTENCY_KEY_HEADER))
+        .and(with_optional_api_key(db_pool.clone()))
+        .and(client_addr())
         .and(with_db(db_pool.clone()))
+        .and(with_redis(redis_pool.clone()))
         .and_then(shorten_url)
         .recover(handle_rejection)
         .boxed();
 
     let redirect = warp::get()
         .and(warp::path::param())
+        .and(request_domain(db_pool.clone()))
+        .and(forwarded())
+        .and(visitor())
+        .and(warp::cookie::optional::<String>(UNLOCK_COOKIE))
         .and(with_db(db_pool.clone()))
         .and(with_redis(redis_pool.clone()))
         .and_then(redirect_url)
         .boxed();
 
-    let index_route = warp::get().and(warp::path::end()).and_then(index).boxed();
+    let preview = warp::get()
+        .and(preview_code())
+        .and(request_domain(db_pool.clone()))
+        .and(visitor())
+        .and(with_db(db_pool.clone()))
+        .and(with_redis(redis_pool.clone()))
+        .and_then(preview_link)
+        .boxed();
+
+    let unlock = warp::post()
+        .and(warp::path::param())
+        .and(request_domain(db_pool.clone()))
+        .and(forwarded())
+        .and(warp::body::content_length_limit(UNLOCK_MAX_BODY_BYTES))
+        .and(warp::body::form())
+        .and(visitor())
+        .and(with_db(db_pool.clone()))
+        .and(with_redis(redis_pool.clone()))
+        .and_then(unlock_link)
+        .boxed();
+
+    let bulk = warp::post()
+        .and(warp::path!("links" / "bulk"))
+        .and(with_api_key(db_pool.clone()))
+        .and(client_addr())
+        .and(warp::body::content_length_limit(BULK_MAX_BODY_BYTES))
+        .and(warp::body::json())
+        .and(with_db(db_pool.clone()))
+        .and_then(bulk_shorten)
+        .boxed();
+
+    let import = warp::post()
+        .and(warp::path!("links" / "import"))
+        .and(with_api_key(db_pool.clone()))
+        .and(client_addr())
+        .and(warp::query::<ImportQuery>())
+        .and(warp::body::content_length_limit(IMPORT_MAX_BODY_BYTES))
+        .and(warp::body::bytes())
+        .and(with_db(db_pool.clone()))
+        .and(with_redis(redis_pool.clone()))
+        .and_then(import_links)
+        .boxed();
+
+    let export = warp::get()
+        .and(warp::path!("links" / "export"))
+        .and(with_api_key(db_pool.clone()))
+        .and(warp::query::<ExportQuery>())
+        .and(with_db(db_pool.clone()))
+        .and_then(export_links)
+        .boxed();
+
+    let export_click_data = warp::get()
+        .and(warp::path!("clicks" / "export"))
+        .and(with_api_key(db_pool.clone()))
+        .and(warp::query::<ExportQuery>())
+        .and(with_db(db_pool.clone()))
+        .and_then(export_clicks)
+        .boxed();
+
+    let links = warp::get()
+        .and(warp::path!("links"))
+        .and(with_api_key(db_pool.clone()))
+        .and(warp::query::<LinkSearchQuery>())
+        .and(with_db(db_pool.clone()))
+        .and_then(get_links)
+        .boxed();
+
+    let label_link = warp::patch()
+        .and(warp::path!("links" / String))
+        .and(warp::query::<DomainQuery>())
+        .and(with_api_key(db_pool.clone()))
+        .and(client_addr())
+        .and(warp::body::content_length_limit(LABELS_MAX_BODY_BYTES))
+        .and(warp::body::json())
+        .and(with_db(db_pool.clone()))
+        .and(with_redis(redis_pool.clone()))
+        .and_then(patch_link)
+        .boxed();
+
+    let link_details = warp::get()
+        .and(warp::path!("links" / String))
+        .and(warp::query::<DomainQuery>())
+        .and(with_api_key(db_pool.clone()))
+        .and(with_db(db_pool.clone()))
+        .and_then(get_link_details)
+        .boxed();
+
+    let variant_stats = warp::get()
+        .and(warp::path!("links" / String / "variants"))
+        .and(warp::query::<DomainQuery>())
+        .and(with_api_key(db_pool.clone()))
+        .and(with_db(db_pool.clone()))
+        .and_then(get_variant_stats)
+        .boxed();
+
+    let delete_link = warp::delete()
+        .and(warp::path!("links" / String))
+        .and(warp::query::<DomainQuery>())
+        .and(with_api_key(db_pool.clone()))
+        .and(client_addr())
+        .and(with_db(db_pool.clone()))
+        .and(with_redis(redis_pool.clone()))
+        .and_then(delete_short_link)
+        .boxed();
+
+    let trash = warp::get()
+        .and(warp::path!("trash"))
+        .and(with_api_key(db_pool.clone()))
+        .and(warp::query::<TrashQuery>())
+        .and(with_db(db_pool.clone()))
+        .and_then(get_trash)
+        .boxed();
+
+    let restore_trashed = warp::post()
+        .and(warp::path!("trash" / String / "restore"))
+        .and(warp::query::<DomainQuery>())
+        .and(with_api_key(db_pool.clone()))
+        .and(client_addr())
+        .and(with_db(db_pool.clone()))
+        .and(with_redis(redis_pool.clone()))
+        .and_then(post_trash_restore)
+        .boxed();
+
+    let revisions = warp::get()
+        .and(warp::path!("links" / String / "revisions"))
+        .and(warp::query::<DomainQuery>())
+        .and(with_api_key(db_pool.clone()))
+        .and(with_db(db_pool.clone()))
+        .and_then(get_link_revisions)
+        .boxed();
+
+    let restore_revision = warp::post()
+        .and(warp::path!("links" / String / "revisions" / i32 / "restore"))
+        .and(warp::query::<DomainQuery>())
+        .and(with_api_key(db_pool.clone()))
+        .and(client_addr())
+        .and(with_db(db_pool.clone()))
+        .and(with_redis(redis_pool.clone()))
+        .and_then(post_revision_restore)
+        .boxed();
+
+    let campaigns = warp::get()
+        .and(warp::path!("campaigns"))
+        .and(with_api_key(db_pool.clone()))
+        .and(with_db(db_pool.clone()))
+        .and_then(get_campaigns)
+        .boxed();
+
+    let save_campaign = warp::post()
+        .and(warp::path!("campaigns"))
+        .and(with_api_key(db_pool.clone()))
+        .and(warp::body::content_length_limit(CAMPAIGN_MAX_BODY_BYTES))
+        .and(warp::body::json())
+        .and(with_db(db_pool.clone()))
+        .and_then(post_campaign)
+        .boxed();
+
+    let workspace = warp::get()
+        .and(warp::path!("workspace"))
+        .and(with_api_key(db_pool.clone()))
+        .and(with_db(db_pool.clone()))
+        .and_then(get_workspace_details)
+        .boxed();
+
+    let rename_workspace = warp::post()
+        .and(warp::path!("workspace"))
+        .and(with_api_key(db_pool.clone()))
+        .and(client_addr())
+        .and(warp::body::content_length_limit(WORKSPACE_MAX_BODY_BYTES))
+        .and(warp::body::json())
+        .and(with_db(db_pool.clone()))
+        .and_then(post_workspace)
+        .boxed();
+
+    let member_role = warp::post()
+        .and(warp::path!("workspace" / "members" / i32))
+        .and(with_api_key(db_pool.clone()))
+        .and(client_addr())
+        .and(warp::body::content_length_limit(WORKSPACE_MAX_BODY_BYTES))
+        .and(warp::body::json())
+        .and(with_db(db_pool.clone()))
+        .and_then(post_member_role)
+        .boxed();
+
+    let remove_member = warp::delete()
+        .and(warp::path!("workspace" / "members" / i32))
+        .and(with_api_key(db_pool.clone()))
+        .and(client_addr())
+        .and(with_db(db_pool.clone()))
+        .and_then(delete_member)
+        .boxed();
+
+    let invites = warp::get()
+        .and(warp::path!("workspace" / "invites"))
+        .and(with_api_key(db_pool.clone()))
+        .and(with_db(db_pool.clone()))
+        .and_then(get_invites)
+        .boxed();
+
+    let invite = warp::post()
+        .and(warp::path!("workspace" / "invites"))
+        .and(with_api_key(db_pool.clone()))
+        .and(client_addr())
+        .and(warp::body::content_length_limit(WORKSPACE_MAX_BODY_BYTES))
+        .and(warp::body::json())
+        .and(with_db(db_pool.clone()))
+        .and_then(post_invite)
+        .boxed();
+
+    // The invite token is the credential, so no API key is needed
+    let accept_invite = warp::post()
+        .and(warp::path!("invites" / String))
+        .and(client_addr())
+        .and(with_db(db_pool.clone()))
+        .and_then(post_invite_acceptance)
+        .boxed();
+
+    let keys = warp::get()
+        .and(warp::path!("workspace" / "keys"))
+        .and(with_api_key(db_pool.clone()))
+        .and(with_db(db_pool.clone()))
+        .and_then(get_keys)
+        .boxed();
+
+    let issue_key = warp::post()
+        .and(warp::path!("workspace" / "keys"))
+        .and(with_api_key(db_pool.clone()))
+        .and(client_addr())
+        .and(warp::body::content_length_limit(WORKSPACE_MAX_BODY_BYTES))
+        .and(warp::body::json())
+        .and(with_db(db_pool.clone()))
+        .and_then(post_key)
+        .boxed();
+
+    let revoke_key = warp::delete()
+        .and(warp::path!("workspace" / "keys" / i32))
+        .and(with_api_key(db_pool.clone()))
+        .and(client_addr())
+        .and(with_db(db_pool.clone()))
+        .and_then(delete_key)
+        .boxed();
+
+    let audit = warp::get()
+        .and(warp::path!("audit"))
+        .and(with_api_key(db_pool.clone()))
+        .and(warp::query::<AuditQuery>())
+        .and(with_db(db_pool.clone()))
+        .and_then(get_audit_events)
+        .boxed();
+
+    // Management API; once under `/api`, rejections are answered here
+    let api = warp::path("api")
+        .and(
+            bulk.or(import)
+                .or(export)
+                .or(export_click_data)
+                .or(links)
+                .or(link_details)
+                .or(label_link)
+                .or(variant_stats)
+                .or(revisions)
+                .or(restore_revision)
+                .or(delete_link)
+                .or(trash)
+                .or(restore_trashed)
+                .or(campaigns)
+                .or(save_campaign)
+                .or(workspace)
+                .or(rename_workspace)
+                .or(member_role)
+                .or(remove_member)
+                .or(invites)
+                .or(invite)
+                .or(accept_invite)
+                .or(keys)
+                .or(issue_key)
+                .or(revoke_key)
+                .or(audit)
+                .recover(handle_rejection),
+        )
+        .boxed();
+
+    let index_route = warp::get()
+        .and(warp::path::end())
+        .and(request_domain(db_pool.clone()))
+        .and_then(home)
+        .boxed();
 
     index_route
+        .or(api)
+        .or(preview)
         .or(redirect)
+        .or(unlock)
         .or(shorten)
         .or(warp::any().and_then(not_found).boxed())
         .recover(handle_rejection)
         .boxed()
 }
 
+/// Templates of the paths served by [`create_routes`], the fixed ones before those they overlap with.
+const ROUTE_TEMPLATES: &[&str] = &[
+    "/",
+    "/shorten",
+    "/api/links",
+    "/api/links/bulk",
+    "/api/links/import",
+    "/api/links/export",
+    "/api/clicks/export",
+    "/api/links/{code}",
+    "/api/links/{code}/variants",
+    "/api/links/{code}/revisions",
+    "/api/links/{code}/revisions/{revision}/restore",
+    "/api/trash",
+    "/api/trash/{code}/restore",
+    "/api/campaigns",
+    "/api/workspace",
+    "/api/workspace/members/{id}",
+    "/api/workspace/invites",
+    "/api/workspace/keys",
+    "/api/workspace/keys/{id}",
+    "/api/invites/{token}",
+    "/api/audit",
+    "/{code}",
+    "/{code}/preview",
+    "/{code}/{*path}",
+];
+
+/// The template of the route serving `path`, such as `/api/links/{code}`, or `None` if no route matches.
+///
+/// A final `{*name}` part matches the rest of the path, however many segments it has.
+pub fn route_template(path: &str) -> Option<&'static str> {
+    let segments: Vec<&str> = path.strip_prefix('/').unwrap_or(path).split('/').collect();
+    ROUTE_TEMPLATES.iter().copied().find(|template| {
+        let parts: Vec<&str> = template.trim_start_matches('/').split('/').collect();
+        let (parts, rest) = match parts.split_last() {
+            Some((last, init)) if last.starts_with("{*") => (init, true),
+            _ => (parts.as_slice(), false),
+        };
+        let lengths_match = if rest { segments.len() > parts.len() } else { segments.len() == parts.len() };
+        lengths_match
+            && parts.iter().zip(&segments).all(|(part, segment)| {
+                if part.starts_with('{') {
+                    !segment.is_empty()
+                } else {
+                    part == segment
+                }
+            })
+    })
+}
+
+/// The short code of a preview page: `/{code}+` or `/{code}/preview`.
+fn preview_code() -> impl Filter<Extract = (String,), Error = Rejection> + Clone {
+    let plus = warp::path::param::<String>()
+        .and(warp::path::end())
+        .and_then(|segment: String| async move {
+            match segment.strip_suffix('+') {
+                Some(code) if !code.is_empty() => Ok(code.to_string()),
+                _ => Err(warp::reject::not_found()),
+            }
+        });
+    plus.or(warp::path!(String / "preview")).unify()
+}
+
 /// Attach the database pool to the filter.
 fn with_db(
     db_pool: Pool,
@@ -47,3 +436,21 @@ fn with_redis(
 ) -> impl Filter<Extract = (RedisPool,), Error = std::convert::Infallible> + Clone {
     warp::any().map(move || redis_pool.clone())
 }
+
+/// Require a valid API key in the `Authorization` header.
+fn with_api_key(
+    db_pool: Pool,
+) -> impl Filter<Extract = (ApiKey,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(with_db(db_pool))
+        .and_then(authenticate)
+}
+
+/// Resolve the API key in the `Authorization` header, if one was sent.
+fn with_optional_api_key(
+    db_pool: Pool,
+) -> impl Filter<Extract = (Option<ApiKey>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(with_db(db_pool))
+        .and_then(authenticate_optional)
+}
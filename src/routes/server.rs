@@ -0,0 +1,179 @@
+use super::route_template;
+use crate::config::telemetry::extract_context;
+use crate::utils::request_context::{self, ClientAddr, RequestContext, REQUEST_ID_HEADER};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn, Service};
+use hyper::{Body, Method, Request};
+use log::{error, info};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::{info_span, Instrument};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use warp::filters::BoxedFilter;
+use warp::http::HeaderValue;
+use warp::reply::Response;
+
+/// Serve the application routes on `addr` until Ctrl+C is received.
+///
+/// Each request gets an ID (taken from `X-Request-Id` or generated), which is
+/// echoed in the response and attached to every log line, followed by one access log line.
+pub async fn serve(routes: BoxedFilter<(Response,)>, addr: SocketAddr) {
+    let service = warp::service(routes);
+    let make_service = make_service_fn(move |conn: &AddrStream| {
+        let client_addr = conn.remote_addr();
+        let service = service.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                handle(service.clone(), client_addr, req)
+            }))
+        }
+    });
+
+    let server = hyper::Server::bind(&addr)
+        .serve(make_service)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+            info!("Shutting down");
+        });
+    if let Err(e) = server.await {
+        error!("Server error: {:?}", e);
+    }
+}
+
+/// Handle a single request within its request context.
+async fn handle<S>(mut service: S, client_addr: SocketAddr, mut req: Request<Body>) -> Result<Response, Infallible>
+where
+    S: Service<Request<Body>, Response = Response, Error = Infallible>,
+{
+    let started = Instant::now();
+    let request_id = request_context::request_id_or_new(
+        req.headers().get(REQUEST_ID_HEADER).and_then(|v| v.to_str().ok()),
+    );
+    let header_value = HeaderValue::from_str(&request_id).expect("request ID is a valid header value");
+    req.headers_mut().insert(REQUEST_ID_HEADER, header_value.clone());
+    req.extensions_mut().insert(ClientAddr(client_addr));
+
+    let method = req.method().clone();
+    let route = req.uri().path().to_string();
+    // Named after the route template rather than the path, which would make one span name per short code
+    let template = route_template(&route);
+    let span = info_span!(
+        "request",
+        otel.name = %span_name(&method, template),
+        otel.kind = "server",
+        http.request.method = %method,
+        http.route = Empty,
+        url.path = %route,
+        request_id = %request_id,
+        client.address = %client_addr.ip(),
+        http.response.status_code = Empty,
+    );
+    if let Some(template) = template {
+        span.record("http.route", template);
+    }
+    let _ = span.set_parent(extract_context(req.headers()));
+    let context = RequestContext::new(request_id);
+    let scoped = context.clone();
+
+    let handled = async move {
+        let mut response = service.call(req).await?;
+        response.headers_mut().insert(REQUEST_ID_HEADER, header_value);
+
+        let status = response.status().as_u16();
+        tracing::Span::current().record("http.response.status_code", status);
+        let latency_ms = started.elapsed().as_micros() as f64 / 1000.0;
+        let short_code = context.short_code().unwrap_or_default();
+        let client_ip = client_addr.ip().to_string();
+        info!(
+            target: "access",
+            method = method.as_str(), route = route.as_str(), status = status,
+            latency_ms = latency_ms, short_code = short_code.as_str(), client_ip = client_ip.as_str();
+            "{} {} {} {:.2}ms", method, route, status, latency_ms
+        );
+
+        Ok(response)
+    };
+    request_context::scope(scoped, handled.instrument(span)).await
+}
+
+/// The name of a request span: the method and route template, or the method alone for unknown paths.
+fn span_name(method: &Method, template: Option<&str>) -> String {
+    match template {
+        Some(template) => format!("{} {}", method, template),
+        None => method.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry::Value;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+    use tracing_subscriber::layer::SubscriberExt;
+    use warp::Filter;
+
+    /// Handle a request to `path` and return the request span as exported.
+    async fn request_span(method: Method, path: &str) -> SpanData {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder().with_simple_exporter(exporter.clone()).build();
+        let subscriber = tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let guard = tracing::subscriber::set_default(subscriber);
+
+        let routes = warp::any().map(warp::reply).map(warp::Reply::into_response).boxed();
+        let req = Request::builder().method(method).uri(path).body(Body::empty()).unwrap();
+        handle(warp::service(routes), ([127, 0, 0, 1], 40000).into(), req).await.unwrap();
+        drop(guard);
+
+        let mut spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 1);
+        spans.remove(0)
+    }
+
+    fn attribute(span: &SpanData, key: &str) -> Option<Value> {
+        span.attributes.iter().find(|kv| kv.key.as_str() == key).map(|kv| kv.value.clone())
+    }
+
+    #[tokio::test]
+    async fn spans_are_named_after_the_route_template() {
+        for code in ["abc123", "xyz789"] {
+            let span = request_span(Method::GET, &format!("/{}", code)).await;
+            assert_eq!(span.name, "GET /{code}");
+            assert_eq!(attribute(&span, "http.route"), Some("/{code}".into()));
+            assert_eq!(attribute(&span, "url.path"), Some(format!("/{}", code).into()));
+        }
+
+        let span = request_span(Method::POST, "/api/links/abc123/revisions/3/restore").await;
+        assert_eq!(span.name, "POST /api/links/{code}/revisions/{revision}/restore");
+        assert_eq!(attribute(&span, "url.path"), Some("/api/links/abc123/revisions/3/restore".into()));
+
+        let span = request_span(Method::POST, "/api/links/bulk").await;
+        assert_eq!(span.name, "POST /api/links/bulk");
+    }
+
+    #[tokio::test]
+    async fn paths_after_a_short_code_share_the_catch_all_template() {
+        for path in ["/abc123/docs", "/abc123/docs/getting-started", "/xyz789/a/b/c?ref=x"] {
+            let span = request_span(Method::GET, path).await;
+            assert_eq!(span.name, "GET /{code}/{*path}");
+            assert_eq!(attribute(&span, "http.route"), Some("/{code}/{*path}".into()));
+        }
+
+        // More specific routes still win over the catch-all
+        let span = request_span(Method::GET, "/abc123/preview").await;
+        assert_eq!(span.name, "GET /{code}/preview");
+        let span = request_span(Method::GET, "/api/links/abc123/variants").await;
+        assert_eq!(span.name, "GET /api/links/{code}/variants");
+    }
+
+    #[tokio::test]
+    async fn spans_of_unknown_paths_are_named_after_the_method() {
+        // Any non-empty first segment may be a short code, so only an empty one is unknown
+        let span = request_span(Method::GET, "//wp-admin/setup-config.php").await;
+        assert_eq!(span.name, "GET");
+        assert_eq!(attribute(&span, "http.route"), None);
+        assert_eq!(attribute(&span, "url.path"), Some("//wp-admin/setup-config.php".into()));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::config::audit::insert_audit_events;
+use crate::config::db::DbError;
+use crate::models::{Actor, ApiKey, AuditAction, Invite, Link, Member, NewAuditEvent, Workspace};
+use log::error;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::env;
+use std::net::SocketAddr;
+use tokio_postgres::Transaction;
+
+/// Number of audit events returned when no limit is given.
+pub const DEFAULT_AUDIT_LIMIT: i64 = 50;
+/// Most audit events returned at once.
+const MAX_AUDIT_LIMIT: i64 = 500;
+
+/// The number of events to return for a requested limit.
+pub fn audit_limit(requested: Option<i64>) -> i64 {
+    requested.unwrap_or(DEFAULT_AUDIT_LIMIT).clamp(1, MAX_AUDIT_LIMIT)
+}
+
+/// A request made with an API key.
+pub fn api_key_actor(api_key: &ApiKey, addr: Option<SocketAddr>) -> Actor {
+    Actor {
+        kind: "api_key".to_string(),
+        key_id: Some(api_key.id),
+        name: Some(api_key.name.clone()),
+        ip: addr.map(|addr| addr.ip()),
+    }
+}
+
+/// A request made without an API key.
+pub fn anonymous_actor(addr: Option<SocketAddr>) -> Actor {
+    Actor {
+        kind: "anonymous".to_string(),
+        key_id: None,
+        name: None,
+        ip: addr.map(|addr| addr.ip()),
+    }
+}
+
+/// Someone accepting an invite sent to `email`.
+pub fn invite_actor(email: &str, addr: Option<SocketAddr>) -> Actor {
+    Actor {
+        kind: "invite".to_string(),
+        key_id: None,
+        name: Some(email.to_string()),
+        ip: addr.map(|addr| addr.ip()),
+    }
+}
+
+/// The system user running an admin command.
+pub fn cli_actor() -> Actor {
+    Actor {
+        kind: "cli".to_string(),
+        key_id: None,
+        name: env::var("USER").or_else(|_| env::var("USERNAME")).ok(),
+        ip: None,
+    }
+}
+
+/// The server itself, such as when purging the trash.
+pub fn system_actor() -> Actor {
+    Actor {
+        kind: "system".to_string(),
+        key_id: None,
+        name: None,
+        ip: None,
+    }
+}
+
+/// The fields that differ between two versions of something, each with its value `before` and `after`.
+///
+/// A missing version counts as having no fields, so creations and deletions list every field set.
+pub fn diff<T: Serialize>(before: Option<&T>, after: Option<&T>) -> Value {
+    let fields = |value: Option<&T>| match value.map(serde_json::to_value) {
+        Some(Ok(Value::Object(fields))) => fields,
+        _ => Map::new(),
+    };
+    let (before, after) = (fields(before), fields(after));
+    let mut changes = Map::new();
+    for key in before.keys().chain(after.keys().filter(|key| !before.contains_key(*key))) {
+        let old = before.get(key).unwrap_or(&Value::Null);
+        let new = after.get(key).unwrap_or(&Value::Null);
+        if old != new {
+            changes.insert(key.clone(), serde_json::json!({ "before": old, "after": new }));
+        }
+    }
+    Value::Object(changes)
+}
+
+/// How a link is named in the audit log.
+pub fn link_target(domain: Option<&str>, code: &str) -> String {
+    match domain {
+        Some(domain) => format!("link:{}/{}", domain, code),
+        None => format!("link:{}", code),
+    }
+}
+
+/// A link being created (no `before`), changed, or deleted (no `after`).
+pub fn link_event(action: AuditAction, before: Option<&Link>, after: Option<&Link>) -> NewAuditEvent {
+    let link = after.or(before).expect("a link event needs a version of the link");
+    NewAuditEvent {
+        workspace_id: link.workspace_id,
+        action,
+        target: link_target(link.domain.as_deref(), &link.short_code),
+        diff: diff(before, after),
+    }
+}
+
+/// A link being changed, followed by a `link.disable` event if the change stops it from redirecting
+/// straight away. That event lists only the fields responsible: a new `active_until`, `single_use`
+/// or `consumed_at` being set, or `interstitial` being turned on.
+pub fn link_change_events(action: AuditAction, before: &Link, after: &Link) -> Vec<NewAuditEvent> {
+    let event = link_event(action, Some(before), Some(after));
+    let disables = |field: &str| match field {
+        "active_until" => after.active_until.is_some(),
+        "single_use" => after.single_use,
+        "consumed_at" => after.consumed_at.is_some(),
+        "interstitial" => after.interstitial,
+        _ => false,
+    };
+    let disabling: Map<String, Value> = match &event.diff {
+        Value::Object(changes) => changes
+            .iter()
+            .filter(|(field, _)| disables(field))
+            .map(|(field, change)| (field.clone(), change.clone()))
+            .collect(),
+        _ => Map::new(),
+    };
+    if disabling.is_empty() {
+        return vec![event];
+    }
+    let disable = NewAuditEvent {
+        workspace_id: event.workspace_id,
+        action: AuditAction::LinkDisable,
+        target: event.target.clone(),
+        diff: Value::Object(disabling),
+    };
+    vec![event, disable]
+}
+
+/// A member joining (no `before`), changing role, or leaving (no `after`).
+pub fn member_event(action: AuditAction, before: Option<&Member>, after: Option<&Member>) -> NewAuditEvent {
+    let member = after.or(before).expect("a member event needs a version of the member");
+    NewAuditEvent {
+        workspace_id: Some(member.workspace_id),
+        action,
+        target: format!("member:{}", member.id),
+        diff: diff(before, after),
+    }
+}
+
+/// An API key being issued (no `before`) or changed.
+pub fn key_event(action: AuditAction, before: Option<&ApiKey>, after: Option<&ApiKey>) -> NewAuditEvent {
+    let key = after.or(before).expect("a key event needs a version of the key");
+    NewAuditEvent {
+        workspace_id: Some(key.workspace_id),
+        action,
+        target: format!("key:{}", key.id),
+        diff: diff(before, after),
+    }
+}
+
+/// A workspace being created (no `before`) or changed.
+pub fn workspace_event(action: AuditAction, before: Option<&Workspace>, after: &Workspace) -> NewAuditEvent {
+    NewAuditEvent {
+        workspace_id: Some(after.id),
+        action,
+        target: format!("workspace:{}", after.id),
+        diff: diff(before, Some(after)),
+    }
+}
+
+/// Someone being invited to a workspace.
+pub fn invite_event(invite: &Invite) -> NewAuditEvent {
+    NewAuditEvent {
+        workspace_id: Some(invite.workspace_id),
+        action: AuditAction::MemberInvite,
+        target: format!("invite:{}", invite.id),
+        diff: diff(None, Some(invite)),
+    }
+}
+
+/// Append events to the audit log, in the transaction making the changes they describe.
+///
+/// A change is only committed along with its events: if they cannot be recorded, the caller
+/// returns the error and the transaction is rolled back.
+pub async fn record(tx: &Transaction<'_>, actor: &Actor, events: &[NewAuditEvent]) -> Result<(), DbError> {
+    insert_audit_events(tx, actor, events).await.map_err(|e| {
+        error!("Failed to record {} audit events: {:?}", events.len(), e);
+        e
+    })
+}
@@ -0,0 +1,55 @@
+use crate::config::api_keys::find_api_key;
+use crate::config::db::DbError;
+use crate::models::{ApiKey, Scope};
+use deadpool_postgres::Pool;
+use log::error;
+use warp::reject::{custom, Rejection};
+
+#[derive(Debug)]
+pub struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+/// The API key is valid but lacks a permission the request needs.
+#[derive(Debug)]
+pub struct Forbidden {
+    pub scope: Scope,
+}
+
+impl warp::reject::Reject for Forbidden {}
+
+/// Reject the request unless the API key has `scope`.
+pub fn require(api_key: &ApiKey, scope: Scope) -> Result<(), Rejection> {
+    if api_key.allows(scope) {
+        Ok(())
+    } else {
+        Err(custom(Forbidden { scope }))
+    }
+}
+
+/// Resolve the API key sent as `Authorization: Bearer <key>`.
+pub async fn authenticate(authorization: Option<String>, db_pool: Pool) -> Result<ApiKey, Rejection> {
+    let secret = authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|secret| !secret.is_empty())
+        .ok_or_else(|| custom(Unauthorized))?;
+
+    let client = db_pool.get().await.map_err(|e| {
+        error!("Failed to get DB client: {:?}", e);
+        custom(DbError::DatabaseError)
+    })?;
+    find_api_key(&client, secret)
+        .await
+        .map_err(custom)?
+        .ok_or_else(|| custom(Unauthorized))
+}
+
+/// Resolve the API key if an `Authorization` header was sent; requests without one are anonymous.
+pub async fn authenticate_optional(authorization: Option<String>, db_pool: Pool) -> Result<Option<ApiKey>, Rejection> {
+    match authorization {
+        Some(_) => authenticate(authorization, db_pool).await.map(Some),
+        None => Ok(None),
+    }
+}
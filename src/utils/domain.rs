@@ -0,0 +1,73 @@
+use crate::config::db::DbError;
+use crate::config::domains::list_domains;
+use crate::models::Domain;
+use deadpool_postgres::Pool;
+use log::error;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use warp::{Filter, Rejection};
+
+/// How long the domains are kept in memory, so changes made elsewhere apply within this delay.
+const DOMAIN_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Every domain by host, with the time it was loaded.
+static DOMAINS: RwLock<Option<(Instant, HashMap<String, Domain>)>> = RwLock::new(None);
+
+/// Normalize a `Host` header or domain name: lowercase, without a trailing dot or a default port.
+pub fn normalize_host(host: &str) -> String {
+    let host = host.trim().to_ascii_lowercase();
+    let host = host.strip_suffix(":80").or_else(|| host.strip_suffix(":443")).unwrap_or(&host);
+    host.trim_end_matches('.').to_string()
+}
+
+/// Whether `host` is a plausible host name, with an optional port.
+pub fn is_valid_host(host: &str) -> bool {
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) => (name, Some(port)),
+        None => (host, None),
+    };
+    (1..=253).contains(&name.len())
+        && name.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && label.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+        })
+        && port.is_none_or(|port| port.parse::<u16>().is_ok_and(|port| port > 0))
+}
+
+/// The domain with this host, or `None` for the default domain of `BASE_URL` and unknown hosts.
+pub async fn resolve_domain(db_pool: &Pool, host: &str) -> Result<Option<Domain>, DbError> {
+    let host = normalize_host(host);
+    if let Some((loaded_at, domains)) = DOMAINS.read().unwrap().as_ref() {
+        if loaded_at.elapsed() < DOMAIN_CACHE_TTL {
+            return Ok(domains.get(&host).cloned());
+        }
+    }
+    let client = db_pool.get().await.map_err(|_| DbError::DatabaseError)?;
+    let domains: HashMap<String, Domain> = list_domains(&client)
+        .await?
+        .into_iter()
+        .map(|domain| (domain.host.clone(), domain))
+        .collect();
+    let domain = domains.get(&host).cloned();
+    *DOMAINS.write().unwrap() = Some((Instant::now(), domains));
+    Ok(domain)
+}
+
+/// Extract the domain a request was made on, from its `Host` header.
+pub fn request_domain(db_pool: Pool) -> impl Filter<Extract = (Option<Domain>,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("host").and_then(move |host: Option<String>| {
+        let db_pool = db_pool.clone();
+        async move {
+            let Some(host) = host else {
+                return Ok(None);
+            };
+            resolve_domain(&db_pool, &host).await.map_err(|e| {
+                error!("Failed to resolve domain {}: {:?}", host, e);
+                warp::reject::custom(e)
+            })
+        }
+    })
+}
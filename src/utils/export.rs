@@ -0,0 +1,90 @@
+use crate::config::db::DbError;
+use crate::models::{Click, Link};
+use crate::utils::import::TransferFormat;
+use chrono::SecondsFormat;
+use futures_util::stream::{self, Stream, StreamExt};
+use tokio_postgres::RowStream;
+
+/// Turn a stream of `shortlink` rows into export lines, each ending with a newline.
+///
+/// The fields match what the importer reads, so an export can be imported elsewhere unchanged.
+pub fn link_lines(rows: RowStream, format: TransferFormat) -> impl Stream<Item = Result<String, DbError>> {
+    let header = csv_header(format, &["code", "url", "created_at", "active_from", "active_until", "tags", "domain"]);
+    header.chain(rows.map(move |row| {
+        let link = Link::from_row(&row.map_err(|_| DbError::DatabaseError)?);
+        let created_at = link.created_at.map(|t| t.format("%Y-%m-%dT%H:%M:%S%.f").to_string());
+        let active_from = link.active_from.map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true));
+        let active_until = link.active_until.map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true));
+        Ok(match format {
+            TransferFormat::Csv => csv_line(&[
+                &link.short_code,
+                &link.original_url,
+                created_at.as_deref().unwrap_or_default(),
+                active_from.as_deref().unwrap_or_default(),
+                active_until.as_deref().unwrap_or_default(),
+                &link.tags.join("|"),
+                link.domain.as_deref().unwrap_or_default(),
+            ]),
+            TransferFormat::Jsonl => json_line(serde_json::json!({
+                "code": link.short_code,
+                "url": link.original_url,
+                "created_at": created_at,
+                "active_from": active_from,
+                "active_until": active_until,
+                "tags": link.tags,
+                "domain": link.domain,
+            })),
+        })
+    }))
+}
+
+/// Turn a stream of `clicks` rows into export lines, each ending with a newline.
+pub fn click_lines(rows: RowStream, format: TransferFormat) -> impl Stream<Item = Result<String, DbError>> {
+    let header = csv_header(format, &["code", "clicked_at", "referer", "user_agent", "country", "language", "variant", "domain"]);
+    header.chain(rows.map(move |row| {
+        let click = Click::from_row(&row.map_err(|_| DbError::DatabaseError)?);
+        Ok(match format {
+            TransferFormat::Csv => csv_line(&[
+                &click.short_code,
+                &click.clicked_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                click.referer.as_deref().unwrap_or_default(),
+                click.user_agent.as_deref().unwrap_or_default(),
+                click.country.as_deref().unwrap_or_default(),
+                click.language.as_deref().unwrap_or_default(),
+                click.variant.as_deref().unwrap_or_default(),
+                click.domain.as_deref().unwrap_or_default(),
+            ]),
+            TransferFormat::Jsonl => json_line(serde_json::json!({
+                "code": click.short_code,
+                "clicked_at": click.clicked_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+                "referer": click.referer,
+                "user_agent": click.user_agent,
+                "country": click.country,
+                "language": click.language,
+                "variant": click.variant,
+                "domain": click.domain,
+            })),
+        })
+    }))
+}
+
+/// The header line for CSV output, nothing for JSONL.
+fn csv_header(format: TransferFormat, columns: &[&str]) -> impl Stream<Item = Result<String, DbError>> {
+    let header = match format {
+        TransferFormat::Csv => Some(Ok(csv_line(columns))),
+        TransferFormat::Jsonl => None,
+    };
+    stream::iter(header)
+}
+
+/// Format one CSV line, quoting fields where needed.
+fn csv_line(fields: &[&str]) -> String {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(fields).expect("writing to memory cannot fail");
+    String::from_utf8(writer.into_inner().expect("writing to memory cannot fail")).expect("fields are UTF-8")
+}
+
+/// Format one JSONL line.
+fn json_line(value: serde_json::Value) -> String {
+    format!("{}\n", value)
+}
@@ -0,0 +1,357 @@
+use crate::config::health::{links_to_check, prune_health_checks, record_health_check};
+use crate::config::redis::invalidate_link;
+use crate::models::HealthCheck;
+use crate::utils::metadata::{http_client, is_blocked, may_fetch, PublicResolver};
+use crate::utils::template::render_sample;
+use chrono::Utc;
+use deadpool_postgres::Pool;
+use deadpool_redis::Pool as RedisPool;
+use futures_util::{stream, StreamExt};
+use log::{error, info, warn};
+use reqwest::{Client, Method, StatusCode};
+use std::env;
+use std::time::{Duration, Instant};
+use tracing::{info_span, Instrument};
+use url::Url;
+
+/// Default seconds between two rounds of health checks.
+const DEFAULT_INTERVAL_SECS: u64 = 300;
+/// Default number of destinations checked at the same time.
+const DEFAULT_CONCURRENCY: usize = 8;
+/// Default seconds to wait for a destination to answer.
+const DEFAULT_TIMEOUT_SECS: u64 = 10;
+/// Days of health check history kept.
+const RETENTION_DAYS: i32 = 30;
+/// Why a destination on a non-public address is not checked.
+const BLOCKED: &str = "Destination is not a public http(s) URL";
+
+/// Settings of the background health checker.
+#[derive(Debug, Clone)]
+pub struct HealthCheckConfig {
+    pub interval: Duration,
+    pub concurrency: usize,
+    pub timeout: Duration,
+    /// Whether destinations on loopback, private and other non-public addresses may be checked.
+    pub allow_private: bool,
+}
+
+impl HealthCheckConfig {
+    /// Read `HEALTH_CHECK_INTERVAL_SECS`, `HEALTH_CHECK_CONCURRENCY`, `HEALTH_CHECK_TIMEOUT_SECS`
+    /// and `HEALTH_CHECK_ALLOW_PRIVATE_IPS`.
+    ///
+    /// Returns `None` when the interval is set to 0, which disables the checker.
+    pub fn from_env() -> Option<Self> {
+        let interval = env_number("HEALTH_CHECK_INTERVAL_SECS", DEFAULT_INTERVAL_SECS);
+        if interval == 0 {
+            return None;
+        }
+        Some(HealthCheckConfig {
+            interval: Duration::from_secs(interval),
+            concurrency: env_number("HEALTH_CHECK_CONCURRENCY", DEFAULT_CONCURRENCY as u64).max(1) as usize,
+            timeout: Duration::from_secs(env_number("HEALTH_CHECK_TIMEOUT_SECS", DEFAULT_TIMEOUT_SECS).max(1)),
+            allow_private: env::var("HEALTH_CHECK_ALLOW_PRIVATE_IPS").is_ok_and(|v| v == "true" || v == "1"),
+        })
+    }
+}
+
+pub(crate) fn env_number(name: &str, default: u64) -> u64 {
+    env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+/// Check the destinations of links with a fallback, forever, once per interval.
+pub async fn run_health_checks(config: HealthCheckConfig, db_pool: Pool, redis_pool: RedisPool) {
+    let client = health_check_client(&config);
+    info!(
+        "Health checks every {}s, {} at a time",
+        config.interval.as_secs(),
+        config.concurrency
+    );
+
+    let mut ticker = tokio::time::interval(config.interval);
+    loop {
+        ticker.tick().await;
+        check_round(&client, &config, &db_pool, &redis_pool)
+            .instrument(info_span!("health_check.round"))
+            .await;
+    }
+}
+
+/// Check every link once.
+async fn check_round(client: &Client, config: &HealthCheckConfig, db_pool: &Pool, redis_pool: &RedisPool) {
+    let db_client = match db_pool.get().await {
+        Ok(db_client) => db_client,
+        Err(e) => {
+            error!("Failed to get DB client for health checks: {:?}", e);
+            return;
+        }
+    };
+    let links = match links_to_check(&db_client).await {
+        Ok(links) => links,
+        Err(e) => {
+            error!("Failed to list links to health check: {:?}", e);
+            return;
+        }
+    };
+    if let Err(e) = prune_health_checks(&db_client, RETENTION_DAYS).await {
+        error!("Failed to prune old health checks: {:?}", e);
+    }
+    drop(db_client);
+
+    stream::iter(links)
+        .for_each_concurrent(config.concurrency, |(domain_id, code, url)| async move {
+            // Templated destinations are checked with their placeholders left empty
+            let check = check_destination(client, config, &render_sample(&url, "")).await;
+            if !check.healthy {
+                warn!("Destination of {} is unhealthy (status {:?}, error {:?})", code, check.status_code, check.error);
+            }
+            record(domain_id, &code, &check, db_pool, redis_pool).await;
+        })
+        .await;
+}
+
+/// Store a check, dropping the cache entry when the link's health changed so redirects switch promptly.
+async fn record(domain_id: Option<i32>, code: &str, check: &HealthCheck, db_pool: &Pool, redis_pool: &RedisPool) {
+    let changed = match db_pool.get().await {
+        Ok(db_client) => record_health_check(&db_client, domain_id, code, check).await,
+        Err(e) => {
+            error!("Failed to get DB client to record health check: {:?}", e);
+            return;
+        }
+    };
+    match changed {
+        Ok(true) => {
+            info!("Health of {} changed to {}", code, if check.healthy { "healthy" } else { "unhealthy" });
+            match redis_pool.get().await {
+                Ok(mut conn) => {
+                    if let Err(e) = invalidate_link(&mut conn, domain_id, code).await {
+                        error!("Failed to invalidate cache for {}: {:?}", code, e);
+                    }
+                }
+                Err(e) => error!("Failed to get Redis connection: {:?}", e),
+            }
+        }
+        Ok(false) => {}
+        Err(e) => error!("Failed to record health check for {}: {:?}", code, e),
+    }
+}
+
+/// The HTTP client used to check destinations, which like the metadata fetcher's only connects to
+/// public addresses, after redirects too, unless the configuration allows private ones.
+pub fn health_check_client(config: &HealthCheckConfig) -> Client {
+    let resolver = PublicResolver { allow_private: config.allow_private };
+    let user_agent = concat!(env!("CARGO_PKG_NAME"), "-health-check/", env!("CARGO_PKG_VERSION"));
+    http_client(config.timeout, resolver, config.allow_private, user_agent)
+}
+
+/// Request a destination with HEAD, falling back to GET for servers that do not support HEAD.
+///
+/// Server errors, 404 and 410 responses, timeouts and connection failures count as unhealthy,
+/// and so do destinations on non-public addresses, which are not requested.
+pub async fn check_destination(client: &Client, config: &HealthCheckConfig, url: &str) -> HealthCheck {
+    let started = Instant::now();
+    if !Url::parse(url).is_ok_and(|url| may_fetch(&url, config.allow_private)) {
+        return HealthCheck {
+            checked_at: Utc::now(),
+            healthy: false,
+            status_code: None,
+            latency_ms: 0,
+            error: Some(BLOCKED.to_string()),
+        };
+    }
+    let mut result = client.request(Method::HEAD, url).send().await;
+    if let Ok(response) = &result {
+        if matches!(response.status(), StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED) {
+            result = client.get(url).send().await;
+        }
+    }
+    let latency_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+
+    match result {
+        Ok(response) => {
+            let status = response.status();
+            HealthCheck {
+                checked_at: Utc::now(),
+                healthy: !(status.is_server_error() || matches!(status, StatusCode::NOT_FOUND | StatusCode::GONE)),
+                status_code: Some(status.as_u16() as i32),
+                latency_ms,
+                error: None,
+            }
+        }
+        Err(e) => HealthCheck {
+            checked_at: Utc::now(),
+            healthy: false,
+            status_code: None,
+            latency_ms,
+            error: Some(if is_blocked(&e) {
+                BLOCKED.to_string()
+            } else if e.is_timeout() {
+                "Timed out".to_string()
+            } else {
+                e.to_string()
+            }),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::db::{configure_db, get_link, insert_shortlink};
+    use crate::config::health::recent_health_checks;
+    use crate::config::redis::configure_redis;
+    use crate::models::NewLink;
+    use crate::routes::create_routes;
+    use crate::utils::short_code::generate_short_code;
+    use crate::utils::stub_server::{serve, StubResponse};
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicU16, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    const FALLBACK_URL: &str = "https://fallback.example/";
+
+    fn config(allow_private: bool) -> HealthCheckConfig {
+        HealthCheckConfig {
+            interval: Duration::from_secs(300),
+            concurrency: 1,
+            timeout: Duration::from_secs(5),
+            allow_private,
+        }
+    }
+
+    /// Check `url` with the client of `config`, as the background checker does.
+    async fn check_with(config: &HealthCheckConfig, url: &str) -> HealthCheck {
+        check_destination(&health_check_client(config), config, url).await
+    }
+
+    /// A destination answering HEAD with `head_status` and GET with 200, logging the methods it receives.
+    async fn destination(head_status: Arc<AtomicU16>, methods: Arc<Mutex<Vec<String>>>) -> SocketAddr {
+        serve(move |request| {
+            methods.lock().unwrap().push(request.method.clone());
+            match request.method.as_str() {
+                "HEAD" => StubResponse::new(head_status.load(Ordering::SeqCst)),
+                _ => StubResponse::html("<html></html>"),
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_get_when_head_is_not_allowed() {
+        let methods = Arc::new(Mutex::new(Vec::new()));
+        let addr = destination(Arc::new(AtomicU16::new(405)), methods.clone()).await;
+
+        let check = check_with(&config(true), &format!("http://{}/", addr)).await;
+        assert!(check.healthy);
+        assert_eq!(check.status_code, Some(200));
+        assert_eq!(*methods.lock().unwrap(), ["HEAD", "GET"]);
+    }
+
+    #[tokio::test]
+    async fn server_errors_and_missing_pages_are_unhealthy() {
+        for status in [500, 503, 404, 410] {
+            let methods = Arc::new(Mutex::new(Vec::new()));
+            let addr = destination(Arc::new(AtomicU16::new(status)), methods.clone()).await;
+
+            let check = check_with(&config(true), &format!("http://{}/", addr)).await;
+            assert!(!check.healthy, "status {}", status);
+            assert_eq!(check.status_code, Some(status as i32));
+            assert_eq!(*methods.lock().unwrap(), ["HEAD"]);
+        }
+    }
+
+    #[tokio::test]
+    async fn unreachable_destinations_are_unhealthy() {
+        // Bound then dropped, so nothing listens on the port
+        let addr = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+
+        let check = check_with(&config(true), &format!("http://{}/", addr)).await;
+        assert!(!check.healthy);
+        assert_eq!(check.status_code, None);
+        assert!(check.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn private_destinations_are_refused() {
+        let methods = Arc::new(Mutex::new(Vec::new()));
+        let addr = destination(Arc::new(AtomicU16::new(200)), methods.clone()).await;
+        let redirect = serve(move |_| StubResponse::redirect(&format!("http://{}/", addr))).await;
+
+        for url in [
+            format!("http://{}/", addr),
+            format!("http://localhost:{}/", addr.port()),
+            "http://169.254.169.254/latest/meta-data/".to_string(),
+        ] {
+            let check = check_with(&config(false), &url).await;
+            assert!(!check.healthy, "{}", url);
+            assert_eq!(check.status_code, None, "{}", url);
+            assert_eq!(check.error.as_deref(), Some(BLOCKED), "{}", url);
+        }
+        // Resolve `localhost` as if it were public, so only the redirect policy stands in the way
+        let client = http_client(Duration::from_secs(5), PublicResolver { allow_private: true }, false, "test");
+        let url = format!("http://localhost:{}/", redirect.port());
+        let check = check_destination(&client, &config(false), &url).await;
+        assert!(!check.healthy);
+        assert_eq!(check.error.as_deref(), Some(BLOCKED));
+        assert!(methods.lock().unwrap().is_empty());
+    }
+
+    /// Where the default domain's redirect for `code` points.
+    async fn redirect_location(code: &str, db_pool: &Pool, redis_pool: &RedisPool) -> String {
+        let response = warp::test::request()
+            .path(&format!("/{}", code))
+            .reply(&create_routes(db_pool.clone(), redis_pool.clone()))
+            .await;
+        assert!(response.status().is_redirection(), "status {}", response.status());
+        response.headers()["location"].to_str().unwrap().to_string()
+    }
+
+    /// Runs against the database and Redis configured by `DATABASE_URL` and `REDIS_URL`; skipped without them.
+    #[tokio::test]
+    async fn redirects_to_the_fallback_while_the_destination_is_unhealthy() {
+        if env::var("DATABASE_URL").is_err() {
+            eprintln!("DATABASE_URL is not set, skipping");
+            return;
+        }
+        let db_pool = configure_db().await;
+        let redis_pool = configure_redis().await;
+        let head_status = Arc::new(AtomicU16::new(200));
+        let methods = Arc::new(Mutex::new(Vec::new()));
+        let addr = destination(head_status.clone(), methods.clone()).await;
+        let original_url = format!("http://{}/page", addr);
+
+        let mut db_client = db_pool.get().await.unwrap();
+        let tx = db_client.transaction().await.unwrap();
+        let link = NewLink { original_url: original_url.clone(), fallback_url: Some(FALLBACK_URL.to_string()), ..Default::default() };
+        let link = insert_shortlink(&tx, &generate_short_code(), &link, None, None).await.unwrap();
+        tx.commit().await.unwrap();
+        let code = link.short_code.clone();
+
+        // Cached before the first check, so a change of health must drop the cache entry
+        assert_eq!(redirect_location(&code, &db_pool, &redis_pool).await, original_url);
+
+        let mut rounds = Vec::new();
+        for status in [200, 500, 405] {
+            head_status.store(status, Ordering::SeqCst);
+            let check = check_with(&config(true), &original_url).await;
+            record(None, &code, &check, &db_pool, &redis_pool).await;
+            let healthy = get_link(&db_client, None, &code).await.unwrap().unwrap().healthy;
+            rounds.push((healthy, redirect_location(&code, &db_pool, &redis_pool).await));
+        }
+        assert_eq!(
+            rounds,
+            [
+                (Some(true), original_url.clone()),
+                (Some(false), FALLBACK_URL.to_string()),
+                (Some(true), original_url.clone()),
+            ]
+        );
+        assert_eq!(*methods.lock().unwrap(), ["HEAD", "HEAD", "HEAD", "GET"]);
+
+        let history = recent_health_checks(&db_client, None, &code, 10).await.unwrap();
+        let history: Vec<_> = history.iter().map(|check| (check.healthy, check.status_code)).collect();
+        assert_eq!(history, [(true, Some(200)), (false, Some(500)), (true, Some(200))]);
+
+        db_client.execute("DELETE FROM health_checks WHERE short_code = $1", &[&code]).await.unwrap();
+        db_client.execute("DELETE FROM shortlink WHERE id = $1", &[&link.id]).await.unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+use crate::models::LinkMetadata;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use url::Url;
+
+/// Longest page title kept, in characters.
+const MAX_TITLE_CHARS: usize = 300;
+/// Longest page description kept, in characters.
+const MAX_DESCRIPTION_CHARS: usize = 1000;
+/// Longest favicon or image URL kept, in bytes.
+const MAX_URL_LEN: usize = 2048;
+
+struct Patterns {
+    head_end: Regex,
+    title: Regex,
+    tag: Regex,
+    attribute: Regex,
+    entity: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        head_end: Regex::new(r"(?i)</head\s*>|<body[\s>]").unwrap(),
+        title: Regex::new(r"(?is)<title[^>]*>(.*?)</title\s*>").unwrap(),
+        tag: Regex::new(r"(?is)<(meta|link)\b([^>]*)>").unwrap(),
+        attribute: Regex::new(r#"(?s)([a-zA-Z_:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap(),
+        entity: Regex::new(r"&(#[0-9]{1,7}|#[xX][0-9a-fA-F]{1,6}|[a-zA-Z]{2,6});").unwrap(),
+    })
+}
+
+/// Read the title, description, favicon and `og:image` from the `<head>` of an HTML page.
+///
+/// Relative favicon and image URLs are resolved against `base`, the URL the page was served from.
+/// Pages without an icon link get the conventional `/favicon.ico` of their origin.
+pub fn parse_metadata(html: &str, base: &Url) -> LinkMetadata {
+    let patterns = patterns();
+    let head = match patterns.head_end.find(html) {
+        Some(end) => &html[..end.start()],
+        None => html,
+    };
+
+    let mut metas: HashMap<String, String> = HashMap::new();
+    let mut icon = None;
+    let mut touch_icon = None;
+    for tag in patterns.tag.captures_iter(head) {
+        let attributes = attributes(&tag[2]);
+        if tag[1].eq_ignore_ascii_case("meta") {
+            let key = attributes.get("property").or_else(|| attributes.get("name"));
+            if let (Some(key), Some(content)) = (key, attributes.get("content")) {
+                metas.entry(key.to_ascii_lowercase()).or_insert_with(|| content.clone());
+            }
+        } else if let (Some(rel), Some(href)) = (attributes.get("rel"), attributes.get("href")) {
+            let mut rel = rel.split_ascii_whitespace().map(str::to_ascii_lowercase);
+            if rel.clone().any(|token| token == "icon") {
+                icon.get_or_insert_with(|| href.clone());
+            } else if rel.any(|token| token == "apple-touch-icon") {
+                touch_icon.get_or_insert_with(|| href.clone());
+            }
+        }
+    }
+
+    let title = patterns
+        .title
+        .captures(head)
+        .map(|title| decode_entities(&title[1]))
+        .or_else(|| metas.get("og:title").map(|title| decode_entities(title)));
+    let description = ["description", "og:description"]
+        .iter()
+        .find_map(|key| metas.get(*key))
+        .map(|description| decode_entities(description));
+    let image = ["og:image", "og:image:url", "twitter:image"].iter().find_map(|key| metas.get(*key));
+    let favicon = icon
+        .or(touch_icon)
+        .map(|href| decode_entities(&href))
+        .or_else(|| Some("/favicon.ico".to_string()));
+
+    LinkMetadata {
+        page_title: clean_text(title, MAX_TITLE_CHARS),
+        page_description: clean_text(description, MAX_DESCRIPTION_CHARS),
+        favicon_url: favicon.and_then(|href| resolve(base, &href)),
+        image_url: image.and_then(|href| resolve(base, &decode_entities(href))),
+    }
+}
+
+/// The attributes of a tag, keyed by lowercase name; the first of repeated attributes wins.
+fn attributes(tag: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    for attribute in patterns().attribute.captures_iter(tag) {
+        let value = attribute.get(2).or(attribute.get(3)).or(attribute.get(4)).map_or("", |v| v.as_str());
+        attributes.entry(attribute[1].to_ascii_lowercase()).or_insert_with(|| value.to_string());
+    }
+    attributes
+}
+
+/// Replace character references such as `&amp;` and `&#39;` with the characters they stand for.
+fn decode_entities(text: &str) -> String {
+    patterns()
+        .entity
+        .replace_all(text, |entity: &regex::Captures| {
+            let name = &entity[1];
+            let decoded = match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                    None => name.strip_prefix('#').and_then(|n| n.parse().ok()).and_then(char::from_u32),
+                },
+            };
+            decoded.map_or_else(|| entity[0].to_string(), String::from)
+        })
+        .into_owned()
+}
+
+/// Collapse whitespace and cut the text to `max_chars`; `None` if nothing is left.
+fn clean_text(text: Option<String>, max_chars: usize) -> Option<String> {
+    let text = text?.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    Some(text.chars().take(max_chars).collect())
+}
+
+/// Resolve a possibly relative URL, keeping only http(s) URLs of reasonable length.
+fn resolve(base: &Url, href: &str) -> Option<String> {
+    let url = base.join(href.trim()).ok()?;
+    (matches!(url.scheme(), "http" | "https") && url.as_str().len() <= MAX_URL_LEN).then(|| url.to_string())
+}
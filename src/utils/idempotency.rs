@@ -0,0 +1,166 @@
+use crate::config::db::DbError;
+use deadpool_redis::redis::{AsyncCommands, ExistenceCheck, RedisResult, SetExpiry, SetOptions};
+use deadpool_redis::Connection;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::net::IpAddr;
+use tracing::{info_span, Instrument};
+use warp::http::header::CONTENT_TYPE;
+use warp::http::HeaderValue;
+use warp::reject::{custom, Rejection};
+use warp::reply::Response;
+
+/// Header clients send to make a request safe to retry.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Header set on responses replayed from an earlier request.
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+/// Default number of seconds a stored response can be replayed.
+const DEFAULT_IDEMPOTENCY_TTL_SECS: u64 = 24 * 60 * 60;
+/// Longest accepted idempotency key.
+const MAX_KEY_LEN: usize = 255;
+
+#[derive(Debug)]
+pub struct InvalidIdempotencyKey;
+
+impl warp::reject::Reject for InvalidIdempotencyKey {}
+
+/// A request with the same key is still being processed.
+#[derive(Debug)]
+pub struct IdempotencyKeyInUse;
+
+impl warp::reject::Reject for IdempotencyKeyInUse {}
+
+/// The key was already used for a request with a different body.
+#[derive(Debug)]
+pub struct IdempotencyKeyReused;
+
+impl warp::reject::Reject for IdempotencyKeyReused {}
+
+/// How long a stored response can be replayed, from `IDEMPOTENCY_TTL_SECS`.
+fn idempotency_ttl() -> u64 {
+    env::var("IDEMPOTENCY_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&secs| secs > 0)
+        .unwrap_or(DEFAULT_IDEMPOTENCY_TTL_SECS)
+}
+
+/// What is stored in Redis under an idempotency key.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+enum StoredRequest {
+    Pending { request_hash: String },
+    Completed { request_hash: String, body: String },
+}
+
+impl StoredRequest {
+    fn request_hash(&self) -> &str {
+        match self {
+            StoredRequest::Pending { request_hash } | StoredRequest::Completed { request_hash, .. } => request_hash,
+        }
+    }
+}
+
+/// An idempotency key claimed for one request, scoped to the API key that sent it, or for
+/// anonymous requests to the client address, so clients never see each other's responses.
+pub struct Idempotency {
+    key: String,
+    request_hash: String,
+}
+
+impl Idempotency {
+    /// Anonymous requests from an unknown address cannot be told apart and are refused.
+    pub fn new(
+        owner_key_id: Option<i32>,
+        client_ip: Option<IpAddr>,
+        key: &str,
+        request: &impl Serialize,
+    ) -> Result<Self, Rejection> {
+        if key.is_empty() || key.len() > MAX_KEY_LEN || !key.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(custom(InvalidIdempotencyKey));
+        }
+        let body = serde_json::to_vec(request).map_err(|_| custom(InvalidIdempotencyKey))?;
+        let owner = match (owner_key_id, client_ip) {
+            (Some(id), _) => id.to_string(),
+            (None, Some(ip)) => format!("anon:{}", ip),
+            (None, None) => return Err(custom(InvalidIdempotencyKey)),
+        };
+        Ok(Idempotency {
+            key: format!("idempotency:{}:{}", owner, key),
+            request_hash: format!("{:x}", Sha256::digest(&body)),
+        })
+    }
+
+    /// Claim the key for this request.
+    ///
+    /// Returns the stored response when the same request already completed, in which case
+    /// it must be replayed instead of processing the request again.
+    pub async fn begin(&self, conn: &mut Connection) -> Result<Option<Response>, Rejection> {
+        let pending = StoredRequest::Pending { request_hash: self.request_hash.clone() };
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(idempotency_ttl()));
+        let claimed: Option<String> = conn
+            .set_options(&self.key, serde_json::to_string(&pending).unwrap(), options)
+            .instrument(info_span!("redis.set_nx", key = %self.key))
+            .await
+            .map_err(redis_error)?;
+        if claimed.is_some() {
+            return Ok(None);
+        }
+
+        let stored: Option<String> = conn
+            .get(&self.key)
+            .instrument(info_span!("redis.get", key = %self.key))
+            .await
+            .map_err(redis_error)?;
+        // A key that vanished in between expired mid-flight; treat it as still in use
+        let stored = stored
+            .and_then(|s| serde_json::from_str::<StoredRequest>(&s).ok())
+            .ok_or_else(|| custom(IdempotencyKeyInUse))?;
+        if stored.request_hash() != self.request_hash {
+            return Err(custom(IdempotencyKeyReused));
+        }
+        match stored {
+            StoredRequest::Pending { .. } => Err(custom(IdempotencyKeyInUse)),
+            StoredRequest::Completed { body, .. } => {
+                info!("Replaying response for {}", self.key);
+                let mut response = Response::new(body.into());
+                let headers = response.headers_mut();
+                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+                Ok(Some(response))
+            }
+        }
+    }
+
+    /// Store the response so retries replay it.
+    pub async fn complete(&self, conn: &mut Connection, body: String) {
+        let completed = StoredRequest::Completed { request_hash: self.request_hash.clone(), body };
+        let result: RedisResult<()> = conn
+            .set_ex(&self.key, serde_json::to_string(&completed).unwrap(), idempotency_ttl())
+            .instrument(info_span!("redis.set_ex", key = %self.key))
+            .await;
+        if let Err(e) = result {
+            error!("Failed to store idempotent response for {}: {:?}", self.key, e);
+        }
+    }
+
+    /// Release the key after a failed request so it can be retried.
+    pub async fn abandon(&self, conn: &mut Connection) {
+        let result: RedisResult<()> = conn
+            .del(&self.key)
+            .instrument(info_span!("redis.del", key = %self.key))
+            .await;
+        if let Err(e) = result {
+            error!("Failed to release idempotency key {}: {:?}", self.key, e);
+        }
+    }
+}
+
+fn redis_error(e: deadpool_redis::redis::RedisError) -> Rejection {
+    error!("Redis error: {:?}", e);
+    custom(DbError::DatabaseError)
+}
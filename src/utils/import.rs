@@ -0,0 +1,373 @@
+use crate::config::db::{import_link, DbError, ImportOutcome};
+use crate::config::domains::list_domains;
+use crate::models::{Actor, AuditAction};
+use crate::utils::audit::{link_change_events, link_event, record};
+use crate::utils::domain::{is_valid_host, normalize_host};
+use crate::utils::short_code::is_valid_short_code;
+use crate::utils::validate::{is_valid_schedule, validate_link};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use thiserror::Error;
+use tokio_postgres::Client;
+
+/// Maximum number of per-record errors reported in an import summary.
+const MAX_REPORTED_ERRORS: usize = 100;
+
+/// File format used to import and export links.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+/// What to do when an imported short code already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Keep the existing link and move on
+    #[default]
+    Skip,
+    /// Replace the existing link with the imported one
+    Overwrite,
+    /// Abort the whole import
+    Fail,
+}
+
+/// A validated link ready to be imported.
+#[derive(Debug, Clone)]
+pub struct ImportRecord {
+    pub line: usize,
+    pub short_code: String,
+    pub original_url: String,
+    pub created_at: Option<NaiveDateTime>,
+    pub active_from: Option<DateTime<Utc>>,
+    pub active_until: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+    /// Host of the branded domain of the link, `None` for the default domain.
+    pub domain: Option<String>,
+}
+
+/// A record that could not be imported.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordError {
+    pub line: usize,
+    pub error: String,
+}
+
+/// Counts of what an import did.
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    pub inserted: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub errors: Vec<RecordError>,
+    /// Domains and short codes whose destination changed, so their cache entries must be dropped.
+    #[serde(skip)]
+    pub updated_codes: Vec<(Option<i32>, String)>,
+}
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Database error")]
+    Database(#[from] DbError),
+    #[error("Short code {code} on line {line} already exists")]
+    Conflict { line: usize, code: String },
+}
+
+/// Columns of a CSV import file; `tags` are separated by `|`.
+#[derive(Debug, Deserialize)]
+struct CsvRecord {
+    code: String,
+    url: String,
+    #[serde(default)]
+    created_at: Option<String>,
+    #[serde(default)]
+    active_from: Option<String>,
+    /// Older exports call this `expires_at`.
+    #[serde(default, alias = "expires_at")]
+    active_until: Option<String>,
+    #[serde(default)]
+    tags: Option<String>,
+    #[serde(default)]
+    domain: Option<String>,
+}
+
+impl From<CsvRecord> for JsonRecord {
+    fn from(record: CsvRecord) -> Self {
+        JsonRecord {
+            code: record.code,
+            url: record.url,
+            created_at: record.created_at,
+            active_from: record.active_from,
+            active_until: record.active_until,
+            tags: record
+                .tags
+                .map(|tags| tags.split('|').map(str::to_string).collect())
+                .unwrap_or_default(),
+            domain: record.domain,
+        }
+    }
+}
+
+/// Fields of a JSONL import line.
+#[derive(Debug, Deserialize)]
+struct JsonRecord {
+    code: String,
+    url: String,
+    #[serde(default)]
+    created_at: Option<String>,
+    #[serde(default)]
+    active_from: Option<String>,
+    /// Older exports call this `expires_at`.
+    #[serde(default, alias = "expires_at")]
+    active_until: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    domain: Option<String>,
+}
+
+/// Read import records one at a time, without loading the whole input.
+pub fn read_records<'a, R: Read + Send + 'a>(
+    reader: R,
+    format: TransferFormat,
+) -> Box<dyn Iterator<Item = Result<ImportRecord, RecordError>> + Send + 'a> {
+    match format {
+        TransferFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+            let headers = match reader.headers() {
+                Ok(headers) => headers.clone(),
+                Err(e) => return Box::new(std::iter::once(Err(RecordError { line: 1, error: e.to_string() }))),
+            };
+            Box::new(reader.into_records().map(move |result| {
+                let record = result.map_err(|e| RecordError {
+                    line: e.position().map_or(0, |p| p.line() as usize),
+                    error: e.to_string(),
+                })?;
+                let line = record.position().map_or(0, |p| p.line() as usize);
+                let record: CsvRecord = record
+                    .deserialize(Some(&headers))
+                    .map_err(|e| RecordError { line, error: e.to_string() })?;
+                build_record(line, record.into())
+            }))
+        }
+        TransferFormat::Jsonl => Box::new(
+            BufReader::new(reader)
+                .lines()
+                .enumerate()
+                .map(|(i, line)| (i + 1, line))
+                .filter(|(_, line)| !matches!(line, Ok(l) if l.trim().is_empty()))
+                .map(|(line, content)| {
+                    let content = content.map_err(|e| RecordError { line, error: e.to_string() })?;
+                    let record: JsonRecord =
+                        serde_json::from_str(&content).map_err(|e| RecordError { line, error: e.to_string() })?;
+                    build_record(line, record)
+                }),
+        ),
+    }
+}
+
+/// Validate the fields of one record.
+fn build_record(line: usize, record: JsonRecord) -> Result<ImportRecord, RecordError> {
+    let fail = |error: String| RecordError { line, error };
+    let JsonRecord {
+        code,
+        url,
+        created_at,
+        active_from,
+        active_until,
+        tags,
+        domain,
+    } = record;
+
+    if !is_valid_short_code(&code) {
+        return Err(fail(format!("Invalid short code {:?}", code)));
+    }
+    let original_url = validate_link(url.clone()).map_err(|_| fail(format!("Invalid link {:?}", url)))?;
+    let created_at = match created_at.filter(|v| !v.is_empty()) {
+        Some(value) => Some(parse_timestamp(&value).ok_or_else(|| fail(format!("Invalid created_at {:?}", value)))?.naive_utc()),
+        None => None,
+    };
+    let active_from = match active_from.filter(|v| !v.is_empty()) {
+        Some(value) => Some(parse_timestamp(&value).ok_or_else(|| fail(format!("Invalid active_from {:?}", value)))?),
+        None => None,
+    };
+    let active_until = match active_until.filter(|v| !v.is_empty()) {
+        Some(value) => Some(parse_timestamp(&value).ok_or_else(|| fail(format!("Invalid active_until {:?}", value)))?),
+        None => None,
+    };
+    if !is_valid_schedule(active_from, active_until) {
+        return Err(fail("active_from must be before active_until".to_string()));
+    }
+    let tags = tags
+        .into_iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    let domain = domain.map(|host| normalize_host(&host)).filter(|host| !host.is_empty());
+    if let Some(host) = domain.as_deref().filter(|host| !is_valid_host(host)) {
+        return Err(fail(format!("Invalid domain {:?}", host)));
+    }
+
+    Ok(ImportRecord {
+        line,
+        short_code: code,
+        original_url,
+        created_at,
+        active_from,
+        active_until,
+        tags,
+        domain,
+    })
+}
+
+/// Parse an RFC 3339 timestamp, or a naive `YYYY-MM-DD HH:MM:SS` one taken as UTC.
+pub fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Utc));
+    }
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|time| time.and_utc())
+}
+
+/// Import records in a single transaction.
+///
+/// Links are added to `workspace_id`, and only links of that workspace are overwritten. Invalid
+/// records, including ones on a domain that does not exist, are counted and reported without
+/// stopping the import; a conflict under `ConflictPolicy::Fail` rolls back everything. Every
+/// inserted or overwritten link is recorded in the audit log as a change made by `actor`.
+pub async fn import_records<I>(
+    client: &mut Client,
+    records: I,
+    workspace_id: Option<i32>,
+    policy: ConflictPolicy,
+    actor: &Actor,
+) -> Result<ImportSummary, ImportError>
+where
+    I: Iterator<Item = Result<ImportRecord, RecordError>>,
+{
+    let domain_ids: HashMap<String, i32> = list_domains(client)
+        .await?
+        .into_iter()
+        .map(|domain| (domain.host, domain.id))
+        .collect();
+    let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+    let mut summary = ImportSummary::default();
+    let mut events = Vec::new();
+
+    for record in records {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                summary.failed += 1;
+                if summary.errors.len() < MAX_REPORTED_ERRORS {
+                    summary.errors.push(e);
+                }
+                continue;
+            }
+        };
+        let domain_id = match record.domain.as_deref() {
+            None => None,
+            Some(host) => match domain_ids.get(host) {
+                Some(&id) => Some(id),
+                None => {
+                    summary.failed += 1;
+                    if summary.errors.len() < MAX_REPORTED_ERRORS {
+                        summary.errors.push(RecordError {
+                            line: record.line,
+                            error: format!("Unknown domain {:?}", host),
+                        });
+                    }
+                    continue;
+                }
+            },
+        };
+        match import_link(&tx, &record, domain_id, workspace_id, policy).await? {
+            ImportOutcome::Inserted(link) => {
+                summary.inserted += 1;
+                events.push(link_event(AuditAction::LinkCreate, None, Some(&link)));
+            }
+            ImportOutcome::Updated { before, after } => {
+                summary.updated += 1;
+                summary.updated_codes.push((domain_id, record.short_code));
+                events.extend(link_change_events(AuditAction::LinkUpdate, &before, &after));
+            }
+            ImportOutcome::Conflict if policy == ConflictPolicy::Fail => {
+                return Err(ImportError::Conflict {
+                    line: record.line,
+                    code: record.short_code,
+                });
+            }
+            ImportOutcome::Conflict => summary.skipped += 1,
+        }
+    }
+
+    record(&tx, actor, &events).await?;
+    tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::db::{configure_db, get_link, insert_shortlink};
+    use crate::models::{LinkLabels, NewLink, PassthroughMode, UtmParams};
+    use crate::utils::audit::cli_actor;
+    use crate::utils::password::hash_password;
+    use crate::utils::short_code::generate_short_code;
+    use std::env;
+
+    /// Runs against the database configured by `DATABASE_URL`; skipped without it.
+    #[tokio::test]
+    async fn overwrite_resets_what_the_record_does_not_carry() {
+        if env::var("DATABASE_URL").is_err() {
+            eprintln!("DATABASE_URL is not set, skipping");
+            return;
+        }
+        let db_pool = configure_db().await;
+        let mut db_client = db_pool.get().await.unwrap();
+        let code = generate_short_code();
+
+        let tx = db_client.transaction().await.unwrap();
+        let link = NewLink {
+            original_url: "https://example.com/old".to_string(),
+            password_hash: Some(hash_password("secret".to_string()).await),
+            single_use: true,
+            interstitial: true,
+            fallback_url: Some("https://fallback.example/".to_string()),
+            passthrough: PassthroughMode::Query,
+            utm: UtmParams { utm_source: Some("newsletter".to_string()), ..Default::default() },
+            labels: LinkLabels { title: Some("Old".to_string()), ..Default::default() },
+            ..Default::default()
+        };
+        insert_shortlink(&tx, &code, &link, None, None).await.unwrap();
+        tx.commit().await.unwrap();
+
+        let jsonl = format!("{{\"code\": \"{}\", \"url\": \"https://example.com/new\"}}\n", code);
+        let records = read_records(jsonl.as_bytes(), TransferFormat::Jsonl);
+        let summary = import_records(&mut db_client, records, None, ConflictPolicy::Overwrite, &cli_actor())
+            .await
+            .unwrap();
+        assert_eq!(summary.updated, 1);
+
+        let link = get_link(&db_client, None, &code).await.unwrap().unwrap();
+        assert_eq!(link.original_url, "https://example.com/new");
+        assert!(!link.password_protected);
+        assert!(!link.single_use);
+        assert!(!link.interstitial);
+        assert_eq!(link.fallback_url, None);
+        assert_eq!(link.passthrough, PassthroughMode::None);
+        assert_eq!(link.utm, UtmParams::default());
+        assert_eq!(link.title, None);
+
+        db_client.execute("DELETE FROM shortlink WHERE id = $1", &[&link.id]).await.unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+/// Placeholder in destinations replaced by the negotiated language.
+pub const LANG_PLACEHOLDER: &str = "{lang}";
+/// Longest language tag accepted, per RFC 5646.
+const MAX_TAG_LEN: usize = 35;
+
+/// Whether `tag` looks like a language tag such as `en`, `pt-BR` or `zh-Hant-TW`.
+pub fn is_valid_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let primary = subtags.next().unwrap_or_default();
+    tag.len() <= MAX_TAG_LEN
+        && (2..=3).contains(&primary.len())
+        && primary.bytes().all(|b| b.is_ascii_alphabetic())
+        && subtags.all(|subtag| (1..=8).contains(&subtag.len()) && subtag.bytes().all(|b| b.is_ascii_alphanumeric()))
+}
+
+/// Parse an `Accept-Language` header into language ranges, most preferred first.
+///
+/// Ranges with `q=0` are dropped; ranges with equal weight keep their order.
+pub fn parse_accept_language(header: &str) -> Vec<(String, f32)> {
+    let mut ranges: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let range = parts.next().filter(|range| !range.is_empty())?;
+            let weight = parts
+                .find_map(|param| param.strip_prefix("q=").or_else(|| param.strip_prefix("Q=")))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok().filter(|q| (0.0..=1.0).contains(q)))?;
+            (weight > 0.0).then(|| (range.to_ascii_lowercase(), weight))
+        })
+        .collect();
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranges
+}
+
+/// Pick the available language that best satisfies the visitor's `Accept-Language` header.
+///
+/// For each range in order of preference, an exact match wins, then a more general available
+/// language (`fr` for `fr-CA`), then a more specific one (`fr-CA` for `fr`). `*` accepts
+/// `default`. Returns `None` when nothing acceptable is available.
+pub fn negotiate_language<'a>(header: Option<&str>, available: &[&'a str], default: Option<&'a str>) -> Option<&'a str> {
+    let ranges = parse_accept_language(header?);
+    ranges.iter().find_map(|(range, _)| {
+        if range == "*" {
+            return default.or_else(|| available.first().copied());
+        }
+        let find = |accept: &dyn Fn(&str) -> bool| available.iter().copied().find(|lang| accept(&lang.to_ascii_lowercase()));
+        find(&|lang| lang == range)
+            .or_else(|| find(&|lang| range.starts_with(lang) && range.as_bytes().get(lang.len()) == Some(&b'-')))
+            .or_else(|| find(&|lang| lang.starts_with(range.as_str()) && lang.as_bytes().get(range.len()) == Some(&b'-')))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_are_ordered_by_weight() {
+        let ranges = parse_accept_language("de;q=0.5, fr-CH, fr;q=0.9, en;Q=0.8, *;q=0.1");
+        let ranges: Vec<&str> = ranges.iter().map(|(range, _)| range.as_str()).collect();
+        assert_eq!(ranges, ["fr-ch", "fr", "en", "de", "*"]);
+        // Equal weights keep the order of the header
+        let ranges = parse_accept_language("nl, it;q=1, es");
+        let ranges: Vec<&str> = ranges.iter().map(|(range, _)| range.as_str()).collect();
+        assert_eq!(ranges, ["nl", "it", "es"]);
+    }
+
+    #[test]
+    fn zero_and_invalid_weights_are_dropped() {
+        let ranges = parse_accept_language("fr;q=0, de;q=0.0, es;q=1.5, it;q=abc, , en;q=0.3");
+        assert_eq!(ranges, [("en".to_string(), 0.3)]);
+        assert_eq!(negotiate_language(Some("fr;q=0, en;q=0.1"), &["fr", "en"], None), Some("en"));
+        assert_eq!(negotiate_language(Some("fr;q=0"), &["fr"], None), None);
+    }
+
+    #[test]
+    fn regions_fall_back_both_ways() {
+        let available = ["fr", "pt-BR", "en-US"];
+        assert_eq!(negotiate_language(Some("fr-CA"), &available, None), Some("fr"));
+        assert_eq!(negotiate_language(Some("pt"), &available, None), Some("pt-BR"));
+        assert_eq!(negotiate_language(Some("EN-us"), &available, None), Some("en-US"));
+        // A prefix that is not a whole subtag does not match
+        assert_eq!(negotiate_language(Some("frr, ptx"), &available, None), None);
+        // Preference beats closeness: an inexact first choice wins over an exact second one
+        assert_eq!(negotiate_language(Some("fr-CA, en-US;q=0.5"), &available, None), Some("fr"));
+        // An exact match wins over a fallback for the same range
+        assert_eq!(negotiate_language(Some("pt-br"), &["pt", "pt-BR"], None), Some("pt-BR"));
+    }
+
+    #[test]
+    fn wildcard_accepts_the_default() {
+        let available = ["de", "fr"];
+        assert_eq!(negotiate_language(Some("ja, *;q=0.5"), &available, Some("fr")), Some("fr"));
+        assert_eq!(negotiate_language(Some("*"), &available, None), Some("de"));
+        assert_eq!(negotiate_language(Some("ja, fr;q=0.2, *;q=0.5"), &available, Some("de")), Some("de"));
+        assert_eq!(negotiate_language(Some("ja"), &available, Some("fr")), None);
+        assert_eq!(negotiate_language(None, &available, Some("fr")), None);
+    }
+
+    #[test]
+    fn language_tags() {
+        for tag in ["en", "pt-BR", "zh-Hant-TW", "gsw", "es-419"] {
+            assert!(is_valid_language_tag(tag), "{}", tag);
+        }
+        for tag in ["", "e", "english", "en_US", "en-", "12", "en-toolongsubtag"] {
+            assert!(!is_valid_language_tag(tag), "{}", tag);
+        }
+    }
+}
@@ -0,0 +1,508 @@
+use crate::config::metadata::{links_missing_metadata, postpone_link_metadata, record_link_metadata};
+use crate::models::LinkMetadata;
+use crate::utils::health_check::env_number;
+use crate::utils::html_meta::parse_metadata;
+use crate::utils::template::render_sample;
+use deadpool_postgres::Pool;
+use futures_util::{stream, StreamExt};
+use log::{error, info};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use reqwest::{redirect, Client, Response};
+use std::env;
+use std::error::Error as _;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{info_span, Instrument};
+use url::{Host, Url};
+
+/// Default seconds between two looks for links without metadata.
+const DEFAULT_INTERVAL_SECS: u64 = 15;
+/// Default number of pages fetched at the same time.
+const DEFAULT_CONCURRENCY: usize = 4;
+/// Default seconds to wait for a page.
+const DEFAULT_TIMEOUT_SECS: u64 = 5;
+/// Default number of bytes of a page read before giving up on finding its `<head>`.
+const DEFAULT_MAX_BODY_BYTES: u64 = 512 * 1024;
+/// Links fetched per round; rounds follow each other without waiting while a backlog remains.
+const BATCH_SIZE: i64 = 100;
+/// Redirects followed before giving up on a page.
+const MAX_REDIRECTS: usize = 5;
+/// Failed attempts at fetching a page that could work later, such as timeouts, before giving up on it.
+const MAX_ATTEMPTS: i32 = 8;
+
+/// Settings of the background metadata fetcher.
+#[derive(Debug, Clone)]
+pub struct MetadataConfig {
+    pub interval: Duration,
+    pub concurrency: usize,
+    pub timeout: Duration,
+    pub max_body_bytes: usize,
+    /// Whether pages on loopback, private and other non-public addresses may be fetched.
+    pub allow_private: bool,
+}
+
+impl MetadataConfig {
+    /// Read `METADATA_FETCH_INTERVAL_SECS`, `METADATA_FETCH_CONCURRENCY`, `METADATA_FETCH_TIMEOUT_SECS`,
+    /// `METADATA_MAX_BODY_BYTES` and `METADATA_ALLOW_PRIVATE_IPS`.
+    ///
+    /// Returns `None` when the interval is set to 0, which disables the fetcher.
+    pub fn from_env() -> Option<Self> {
+        let interval = env_number("METADATA_FETCH_INTERVAL_SECS", DEFAULT_INTERVAL_SECS);
+        if interval == 0 {
+            return None;
+        }
+        Some(MetadataConfig {
+            interval: Duration::from_secs(interval),
+            concurrency: env_number("METADATA_FETCH_CONCURRENCY", DEFAULT_CONCURRENCY as u64).max(1) as usize,
+            timeout: Duration::from_secs(env_number("METADATA_FETCH_TIMEOUT_SECS", DEFAULT_TIMEOUT_SECS).max(1)),
+            max_body_bytes: env_number("METADATA_MAX_BODY_BYTES", DEFAULT_MAX_BODY_BYTES).max(1024) as usize,
+            allow_private: env::var("METADATA_ALLOW_PRIVATE_IPS").is_ok_and(|v| v == "true" || v == "1"),
+        })
+    }
+}
+
+/// Why a destination page could not be read.
+#[derive(Debug, Error)]
+pub enum MetadataError {
+    #[error("Destination is not a public http(s) URL")]
+    Blocked,
+    #[error("Destination answered with status {0}")]
+    Status(u16),
+    #[error("Destination is not an HTML page")]
+    NotHtml,
+    #[error("Timed out")]
+    Timeout,
+    #[error("Request failed: {0}")]
+    Request(String),
+}
+
+impl MetadataError {
+    /// Whether fetching the page again cannot help: it is blocked, not HTML, or refused by a 4xx status.
+    /// Timeouts, connection failures and server errors may pass, so those pages are fetched again later.
+    pub fn is_final(&self) -> bool {
+        match self {
+            MetadataError::Blocked | MetadataError::NotHtml => true,
+            MetadataError::Status(status) => (400..500).contains(status),
+            MetadataError::Timeout | MetadataError::Request(_) => false,
+        }
+    }
+}
+
+/// Whether a request failed because [`PublicResolver`] or the redirect policy of [`http_client`]
+/// refused a non-public address.
+pub(crate) fn is_blocked(e: &reqwest::Error) -> bool {
+    let mut source = e.source();
+    while let Some(cause) = source {
+        if let Some(MetadataError::Blocked) = cause.downcast_ref::<MetadataError>() {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
+impl From<reqwest::Error> for MetadataError {
+    fn from(e: reqwest::Error) -> Self {
+        if is_blocked(&e) {
+            return MetadataError::Blocked;
+        }
+        if e.is_timeout() {
+            return MetadataError::Timeout;
+        }
+        // The causes say why a request failed, such as a connection being refused
+        let mut message = e.to_string();
+        let mut source = e.source();
+        while let Some(cause) = source {
+            message.push_str(": ");
+            message.push_str(&cause.to_string());
+            source = cause.source();
+        }
+        MetadataError::Request(message)
+    }
+}
+
+/// Resolves host names like the system resolver, but only to addresses the fetcher may connect to.
+///
+/// Checking the addresses actually connected to, rather than the URL up front, also covers redirects
+/// and hosts whose DNS answers change between two lookups.
+pub(crate) struct PublicResolver {
+    pub(crate) allow_private: bool,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let allow_private = self.allow_private;
+        Box::pin(async move {
+            let addrs: Vec<_> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| allow_private || is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(Box::new(MetadataError::Blocked) as _);
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// The HTTP client used to fetch destination pages.
+pub fn metadata_client(config: &MetadataConfig) -> Client {
+    let resolver = PublicResolver { allow_private: config.allow_private };
+    let user_agent = concat!(env!("CARGO_PKG_NAME"), "-metadata/", env!("CARGO_PKG_VERSION"));
+    http_client(config.timeout, resolver, config.allow_private, user_agent)
+}
+
+/// An HTTP client resolving host names with `resolver` and following redirects only to
+/// http(s) URLs, and to literal non-public addresses only if `allow_private` is set.
+pub(crate) fn http_client(timeout: Duration, resolver: PublicResolver, allow_private: bool, user_agent: &str) -> Client {
+    Client::builder()
+        .timeout(timeout)
+        .connect_timeout(timeout)
+        .no_proxy()
+        .dns_resolver(Arc::new(resolver))
+        .redirect(redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("Too many redirects")
+            } else if !may_fetch(attempt.url(), allow_private) {
+                attempt.error(MetadataError::Blocked)
+            } else {
+                attempt.follow()
+            }
+        }))
+        .user_agent(user_agent)
+        .build()
+        .expect("Failed to build HTTP client")
+}
+
+/// Fetch the metadata of links that have none, forever, once per interval.
+pub async fn run_metadata_fetcher(config: MetadataConfig, db_pool: Pool) {
+    let client = metadata_client(&config);
+    info!(
+        "Fetching destination metadata every {}s, {} at a time",
+        config.interval.as_secs(),
+        config.concurrency
+    );
+
+    let mut ticker = tokio::time::interval(config.interval);
+    loop {
+        ticker.tick().await;
+        while fetch_round(&client, &config, &db_pool)
+            .instrument(info_span!("metadata.round"))
+            .await
+            == BATCH_SIZE as usize
+        {}
+    }
+}
+
+/// Fetch the metadata of one batch of links. Returns the number of links stored.
+async fn fetch_round(client: &Client, config: &MetadataConfig, db_pool: &Pool) -> usize {
+    let links = match db_pool.get().await {
+        Ok(db_client) => links_missing_metadata(&db_client, BATCH_SIZE).await,
+        Err(e) => {
+            error!("Failed to get DB client for fetching metadata: {:?}", e);
+            return 0;
+        }
+    };
+    let links = match links {
+        Ok(links) => links,
+        Err(e) => {
+            error!("Failed to list links without metadata: {:?}", e);
+            return 0;
+        }
+    };
+
+    stream::iter(links)
+        .map(|(link_id, url)| async move {
+            // Templated destinations are fetched with their placeholders left empty
+            match fetch_metadata(client, config, &render_sample(&url, "")).await {
+                Ok(metadata) => store(link_id, &url, &metadata, db_pool).await,
+                Err(e) if e.is_final() => {
+                    // Stored empty, so the page is not fetched again until the destination changes
+                    info!("No metadata for link {}: {}", link_id, e);
+                    store(link_id, &url, &LinkMetadata::default(), db_pool).await
+                }
+                Err(e) => postpone(link_id, &url, &e, db_pool).await,
+            }
+        })
+        .buffer_unordered(config.concurrency)
+        .filter(|stored| std::future::ready(*stored))
+        .count()
+        .await
+}
+
+async fn store(link_id: i32, url: &str, metadata: &LinkMetadata, db_pool: &Pool) -> bool {
+    let stored = match db_pool.get().await {
+        Ok(db_client) => record_link_metadata(&db_client, link_id, url, metadata).await,
+        Err(e) => {
+            error!("Failed to get DB client to store metadata: {:?}", e);
+            return false;
+        }
+    };
+    stored.unwrap_or_else(|e| {
+        error!("Failed to store metadata of link {}: {:?}", link_id, e);
+        false
+    })
+}
+
+/// Put off fetching a page that failed for a reason that may pass, giving up after `MAX_ATTEMPTS`.
+/// Returns `true` if the link was given up on and stored empty.
+async fn postpone(link_id: i32, url: &str, e: &MetadataError, db_pool: &Pool) -> bool {
+    let attempts = match db_pool.get().await {
+        Ok(db_client) => postpone_link_metadata(&db_client, link_id, url).await,
+        Err(e) => {
+            error!("Failed to get DB client to postpone metadata: {:?}", e);
+            return false;
+        }
+    };
+    match attempts {
+        Ok(Some(attempts)) if attempts >= MAX_ATTEMPTS => {
+            info!("No metadata for link {} after {} attempts: {}", link_id, attempts, e);
+            store(link_id, url, &LinkMetadata::default(), db_pool).await
+        }
+        Ok(Some(attempts)) => {
+            info!("Fetching metadata for link {} failed ({} so far), retrying later: {}", link_id, attempts, e);
+            false
+        }
+        Ok(None) => false,
+        Err(e) => {
+            error!("Failed to postpone metadata of link {}: {:?}", link_id, e);
+            false
+        }
+    }
+}
+
+/// Fetch a page and read its metadata from the first `max_body_bytes` bytes.
+pub async fn fetch_metadata(client: &Client, config: &MetadataConfig, url: &str) -> Result<LinkMetadata, MetadataError> {
+    let url = Url::parse(url).map_err(|_| MetadataError::Blocked)?;
+    if !may_fetch(&url, config.allow_private) {
+        return Err(MetadataError::Blocked);
+    }
+    let mut response = client.get(url).header(ACCEPT, "text/html,application/xhtml+xml").send().await?;
+    if !response.status().is_success() {
+        return Err(MetadataError::Status(response.status().as_u16()));
+    }
+    let is_html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_none_or(|value| value.to_ascii_lowercase().contains("html"));
+    if !is_html {
+        return Err(MetadataError::NotHtml);
+    }
+
+    let base = response.url().clone();
+    let body = read_body(&mut response, config.max_body_bytes).await?;
+    Ok(parse_metadata(&String::from_utf8_lossy(&body), &base))
+}
+
+/// Read at most `max_bytes` of a response body, leaving the rest unread.
+async fn read_body(response: &mut Response, max_bytes: usize) -> Result<Vec<u8>, MetadataError> {
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= max_bytes {
+            body.truncate(max_bytes);
+            break;
+        }
+    }
+    Ok(body)
+}
+
+/// Whether a URL may be requested: http(s) only, and no literal non-public address.
+///
+/// Host names are checked when they are resolved, by [`PublicResolver`].
+pub(crate) fn may_fetch(url: &Url, allow_private: bool) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    match url.host() {
+        Some(Host::Domain(_)) => true,
+        Some(Host::Ipv4(ip)) => allow_private || is_public(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => allow_private || is_public(IpAddr::V6(ip)),
+        None => false,
+    }
+}
+
+/// Whether an address is on the public internet, as opposed to loopback, private, link-local,
+/// shared, documentation, multicast or otherwise reserved ranges.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_v4(ip);
+            }
+            let segments = ip.segments();
+            // NAT64 addresses embed an IPv4 address in their last 32 bits
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                return is_public_v4(Ipv4Addr::from(((segments[6] as u32) << 16) | segments[7] as u32));
+            }
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+        }
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (b & 0xc0) == 64)
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b & 0xfe) == 18)
+        || a >= 240)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::stub_server::{serve, StubResponse};
+    use std::net::Ipv6Addr;
+
+    fn config(max_body_bytes: usize) -> MetadataConfig {
+        MetadataConfig {
+            interval: Duration::from_secs(15),
+            concurrency: 1,
+            timeout: Duration::from_secs(5),
+            max_body_bytes,
+            allow_private: false,
+        }
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn private_and_reserved_ipv4_ranges_are_not_public() {
+        for address in [
+            "10.0.0.1",
+            "10.255.255.255",
+            "100.64.0.1",
+            "100.127.255.254",
+            "169.254.169.254",
+            "127.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "0.0.0.0",
+            "240.0.0.1",
+        ] {
+            assert!(!is_public(ip(address)), "{} should not be public", address);
+        }
+        assert!(is_public(ip("100.128.0.1")));
+    }
+
+    #[test]
+    fn embedded_and_local_ipv6_addresses_are_not_public() {
+        for address in [
+            "::1",
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+            "64:ff9b::7f00:1",
+            "64:ff9b::a9fe:a9fe",
+            "fc00::1",
+            "fdff:ffff::1",
+            "fe80::1",
+        ] {
+            assert!(!is_public(ip(address)), "{} should not be public", address);
+        }
+    }
+
+    #[test]
+    fn public_addresses_are_public() {
+        assert!(is_public(IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34))));
+        assert!(is_public(ip("::ffff:93.184.216.34")));
+        assert!(is_public(ip("64:ff9b::5db8:d822")));
+        assert!(is_public(IpAddr::V6(Ipv6Addr::new(0x2606, 0x2800, 0x220, 1, 0x248, 0x1893, 0x25c8, 0x1946))));
+    }
+
+    #[tokio::test]
+    async fn redirect_to_loopback_is_blocked() {
+        let addr = serve(|request| match request.path.as_str() {
+            "/redirect" => StubResponse::redirect("http://127.0.0.1/admin"),
+            _ => StubResponse::html("<title>Private</title>"),
+        })
+        .await;
+        // Resolve `localhost` as if it were public, so only the redirect policy stands in the way
+        let client = http_client(Duration::from_secs(5), PublicResolver { allow_private: true }, false, "test");
+        let url = format!("http://localhost:{}/redirect", addr.port());
+        let result = fetch_metadata(&client, &config(1024), &url).await;
+        assert!(matches!(result, Err(MetadataError::Blocked)), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn host_resolving_to_loopback_is_blocked() {
+        let addr = serve(|_| StubResponse::html("<title>Private</title>")).await;
+        let client = metadata_client(&config(1024));
+        let url = format!("http://localhost:{}/", addr.port());
+        let result = fetch_metadata(&client, &config(1024), &url).await;
+        assert!(matches!(result, Err(MetadataError::Blocked)), "{:?}", result);
+        assert!(result.unwrap_err().is_final());
+    }
+
+    #[tokio::test]
+    async fn body_is_truncated_at_max_body_bytes() {
+        let addr = serve(|_| StubResponse::html("a".repeat(100_000))).await;
+        let client = http_client(Duration::from_secs(5), PublicResolver { allow_private: true }, true, "test");
+        let mut response = client.get(format!("http://127.0.0.1:{}/", addr.port())).send().await.unwrap();
+        let body = read_body(&mut response, 1024).await.unwrap();
+        assert_eq!(body.len(), 1024);
+    }
+
+    #[tokio::test]
+    async fn page_metadata_is_read() {
+        let addr = serve(|_| {
+            StubResponse::html(
+                r#"<html><head><title>Stub &amp; Co</title>
+                <meta name="description" content="A stub page">
+                <link rel="icon" href="/icon.png">
+                <meta property="og:image" content="img/cover.jpg">
+                </head><body>Hello</body></html>"#,
+            )
+        })
+        .await;
+        let config = MetadataConfig { allow_private: true, ..config(4096) };
+        let client = metadata_client(&config);
+        let base = format!("http://127.0.0.1:{}", addr.port());
+        let metadata = fetch_metadata(&client, &config, &format!("{}/pages/stub", base)).await.unwrap();
+        assert_eq!(metadata.page_title.as_deref(), Some("Stub & Co"));
+        assert_eq!(metadata.page_description.as_deref(), Some("A stub page"));
+        assert_eq!(metadata.favicon_url, Some(format!("{}/icon.png", base)));
+        assert_eq!(metadata.image_url, Some(format!("{}/pages/img/cover.jpg", base)));
+    }
+
+    #[tokio::test]
+    async fn only_final_errors_are_not_retried() {
+        let addr = serve(|request| match request.path.as_str() {
+            "/missing" => StubResponse::new(404),
+            "/broken" => StubResponse::new(503),
+            _ => StubResponse::new(200).header("Content-Type", "application/pdf"),
+        })
+        .await;
+        let config = MetadataConfig { allow_private: true, ..config(1024) };
+        let client = metadata_client(&config);
+        let url = |path: &str| format!("http://127.0.0.1:{}{}", addr.port(), path);
+        let missing = fetch_metadata(&client, &config, &url("/missing")).await;
+        assert!(matches!(missing, Err(MetadataError::Status(404))), "{:?}", missing);
+        assert!(missing.unwrap_err().is_final());
+        let broken = fetch_metadata(&client, &config, &url("/broken")).await;
+        assert!(matches!(broken, Err(MetadataError::Status(503))), "{:?}", broken);
+        assert!(!broken.unwrap_err().is_final());
+        let file = fetch_metadata(&client, &config, &url("/file.pdf")).await;
+        assert!(matches!(file, Err(MetadataError::NotHtml)), "{:?}", file);
+        assert!(!MetadataError::Timeout.is_final());
+    }
+}
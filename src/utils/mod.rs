@@ -1,2 +1,26 @@
+pub mod audit;
+pub mod auth;
+pub mod domain;
+pub mod export;
+pub mod health_check;
+pub mod html_meta;
+pub mod idempotency;
+pub mod import;
+pub mod language;
+pub mod metadata;
+pub mod passthrough;
+pub mod password;
 pub mod rate_limit;
+pub mod request_context;
+pub mod search;
+pub mod short_code;
+#[cfg(test)]
+pub mod stub_server;
+pub mod template;
+pub mod trash;
+pub mod user_agent;
+pub mod utm;
 pub mod validate;
+pub mod variant;
+pub mod visitor;
+pub mod workspace;
@@ -0,0 +1,170 @@
+use crate::models::PassthroughMode;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use url::{form_urlencoded, Url};
+use warp::Filter;
+
+/// What the visited URL adds after the short code.
+#[derive(Debug, Clone, Default)]
+pub struct Forwarded {
+    /// Path after the short code, still percent-encoded and without the leading slash.
+    pub path: String,
+    /// Raw query string, without the `?`.
+    pub query: Option<String>,
+}
+
+/// Extract the path after the short code and the query string of the visited URL.
+pub fn forwarded() -> impl Filter<Extract = (Forwarded,), Error = Infallible> + Clone {
+    warp::path::tail()
+        .and(warp::query::raw().map(Some).or(warp::any().map(|| None)).unify())
+        .map(|tail: warp::path::Tail, query: Option<String>| Forwarded {
+            path: tail.as_str().to_string(),
+            query: query.filter(|query| !query.is_empty()),
+        })
+}
+
+/// Add what the link forwards from the visited URL to its destination.
+///
+/// The path is appended to the destination path, so `/docs` visited as `/{code}/a/b` leads to
+/// `/docs/a/b`; `.` and `..` segments are dropped so the path cannot climb out of the destination.
+/// Query parameters are appended after the destination's own. When both have a parameter of the
+/// same name, the destination's value wins and the visitor's is dropped. The destination's
+/// fragment is kept.
+pub fn pass_through(destination: &str, mode: PassthroughMode, forwarded: &Forwarded) -> String {
+    let segments: Vec<&str> = if mode.forwards_path() {
+        forwarded.path.split('/').filter(|segment| !is_dot_segment(segment)).collect()
+    } else {
+        Vec::new()
+    };
+    let url = match Url::parse(destination) {
+        Ok(mut url) if !segments.is_empty() => {
+            let path = format!("{}/{}", url.path().trim_end_matches('/'), segments.join("/"));
+            url.set_path(&path);
+            url.into()
+        }
+        _ => destination.to_string(),
+    };
+    match forwarded.query.as_deref().filter(|_| mode.forwards_query()) {
+        Some(query) => merge_query(&url, form_urlencoded::parse(query.as_bytes())),
+        None => url,
+    }
+}
+
+/// Append query parameters to a URL, keeping its fragment.
+///
+/// Parameters the URL already has win: incoming ones with the same name are dropped.
+/// The URL is not re-parsed, so placeholders such as `{lang}` are left untouched.
+pub fn merge_query<K, V>(url: &str, params: impl IntoIterator<Item = (K, V)>) -> String
+where
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let (base, fragment) = match url.split_once('#') {
+        Some((base, fragment)) => (base, Some(fragment)),
+        None => (url, None),
+    };
+    let existing_query = base.split_once('?').map(|(_, query)| query);
+    let existing: HashSet<String> = existing_query
+        .map(|query| form_urlencoded::parse(query.as_bytes()).map(|(name, _)| name.into_owned()).collect())
+        .unwrap_or_default();
+    let mut added = form_urlencoded::Serializer::new(String::new());
+    for (name, value) in params {
+        if !existing.contains(name.as_ref()) {
+            added.append_pair(name.as_ref(), value.as_ref());
+        }
+    }
+    let added = added.finish();
+    if added.is_empty() {
+        return url.to_string();
+    }
+    let separator = match existing_query {
+        None => "?",
+        Some(query) if query.is_empty() || query.ends_with('&') => "",
+        Some(_) => "&",
+    };
+    let mut merged = format!("{}{}{}", base, separator, added);
+    if let Some(fragment) = fragment {
+        merged.push('#');
+        merged.push_str(fragment);
+    }
+    merged
+}
+
+/// Empty, `.` and `..` segments, including their percent-encoded spellings.
+fn is_dot_segment(segment: &str) -> bool {
+    let segment = segment.to_ascii_lowercase().replace("%2e", ".");
+    segment.is_empty() || segment == "." || segment == ".."
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UtmParams;
+    use crate::utils::utm::tag_url;
+
+    fn visited(path: &str, query: Option<&str>) -> Forwarded {
+        Forwarded { path: path.to_string(), query: query.map(str::to_string) }
+    }
+
+    #[test]
+    fn destination_params_win_then_utm_then_forwarded() {
+        let utm = UtmParams {
+            utm_source: Some("newsletter".to_string()),
+            utm_medium: Some("email".to_string()),
+            ..Default::default()
+        };
+        let forwarded = visited("", Some("utm_medium=social&utm_campaign=spring&ref=home"));
+        let tagged = tag_url("https://example.com/?utm_source=partner#top", &utm);
+        assert_eq!(
+            pass_through(&tagged, PassthroughMode::Query, &forwarded),
+            "https://example.com/?utm_source=partner&utm_medium=email&utm_campaign=spring&ref=home#top"
+        );
+    }
+
+    #[test]
+    fn duplicate_query_keys() {
+        // Repeated visitor parameters are all forwarded
+        let forwarded = visited("", Some("tag=a&tag=b"));
+        assert_eq!(
+            pass_through("https://example.com/", PassthroughMode::Query, &forwarded),
+            "https://example.com/?tag=a&tag=b"
+        );
+        // ...unless the destination has the parameter, which drops every one of them
+        assert_eq!(
+            pass_through("https://example.com/?tag=x&tag=y", PassthroughMode::Query, &forwarded),
+            "https://example.com/?tag=x&tag=y"
+        );
+    }
+
+    #[test]
+    fn dot_segments_cannot_climb_out_of_the_destination() {
+        for path in ["../admin", "%2e%2e/admin", "%2E%2E/admin", "./admin", "a/../../admin"] {
+            let url = pass_through("https://example.com/docs/", PassthroughMode::Path, &visited(path, None));
+            assert!(url.starts_with("https://example.com/docs/"), "{} -> {}", path, url);
+            assert!(url.ends_with("/admin"), "{} -> {}", path, url);
+            assert!(!url.contains(".."), "{} -> {}", path, url);
+        }
+    }
+
+    #[test]
+    fn encoded_slashes_stay_encoded() {
+        let url = pass_through("https://example.com/docs", PassthroughMode::Path, &visited("a%2Fb/c", None));
+        assert_eq!(url, "https://example.com/docs/a%2Fb/c");
+        let url = pass_through("https://example.com/docs", PassthroughMode::Path, &visited("%2e%2e%2fadmin", None));
+        assert_eq!(url, "https://example.com/docs/%2e%2e%2fadmin");
+    }
+
+    #[test]
+    fn the_mode_decides_what_is_forwarded() {
+        let forwarded = visited("a/b", Some("ref=home"));
+        let cases = [
+            (PassthroughMode::None, "https://example.com/docs"),
+            (PassthroughMode::Path, "https://example.com/docs/a/b"),
+            (PassthroughMode::Query, "https://example.com/docs?ref=home"),
+            (PassthroughMode::Both, "https://example.com/docs/a/b?ref=home"),
+        ];
+        for (mode, expected) in cases {
+            assert_eq!(pass_through("https://example.com/docs", mode, &forwarded), expected, "{:?}", mode);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as base64_url;
+use base64::Engine as _;
+use chrono::Utc;
+use deadpool_redis::redis::{self, RedisResult};
+use deadpool_redis::Connection;
+use hmac::{Hmac, Mac};
+use log::warn;
+use sha2::Sha256;
+use std::env;
+use std::sync::OnceLock;
+use tracing::{info_span, Instrument};
+
+/// Cookie remembering that a visitor entered the password of a link.
+pub const UNLOCK_COOKIE: &str = "link_unlock";
+/// Default number of seconds an unlocked link stays unlocked.
+const DEFAULT_UNLOCK_TTL_SECS: i64 = 60 * 60;
+/// Default number of failed attempts allowed per link within the attempt window.
+const DEFAULT_MAX_FAILED_ATTEMPTS: u32 = 5;
+/// Seconds after which failed attempts are forgotten.
+const FAILED_ATTEMPT_WINDOW_SECS: i64 = 15 * 60;
+
+/// Hash a link password with Argon2.
+pub async fn hash_password(password: String) -> String {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .expect("Argon2 hashing with default parameters")
+            .to_string()
+    })
+    .await
+    .expect("Password hashing task panicked")
+}
+
+/// Check a password against a stored Argon2 hash.
+pub async fn verify_password(password: String, password_hash: String) -> bool {
+    tokio::task::spawn_blocking(move || {
+        PasswordHash::new(&password_hash)
+            .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+            .unwrap_or(false)
+    })
+    .await
+    .unwrap_or(false)
+}
+
+/// Key used to sign unlock cookies, from `LINK_COOKIE_SECRET`.
+///
+/// Without it a random key is used, so unlocked links lock again when the server restarts.
+fn cookie_secret() -> &'static [u8] {
+    static SECRET: OnceLock<Vec<u8>> = OnceLock::new();
+    SECRET.get_or_init(|| match env::var("LINK_COOKIE_SECRET") {
+        Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+        _ => {
+            warn!("LINK_COOKIE_SECRET is not set, using a random key for unlock cookies");
+            let mut secret = vec![0u8; 32];
+            OsRng.fill_bytes(&mut secret);
+            secret
+        }
+    })
+}
+
+/// How long an unlocked link stays unlocked, from `LINK_UNLOCK_TTL_SECS`.
+fn unlock_ttl() -> i64 {
+    env::var("LINK_UNLOCK_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&secs| secs > 0)
+        .unwrap_or(DEFAULT_UNLOCK_TTL_SECS)
+}
+
+/// Failed attempts allowed per link before further ones are refused, from `LINK_MAX_FAILED_ATTEMPTS`.
+fn max_failed_attempts() -> u32 {
+    env::var("LINK_MAX_FAILED_ATTEMPTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_FAILED_ATTEMPTS)
+}
+
+/// Signature binding the cookie to the link and its current password, so changing
+/// the password locks the link again.
+fn sign(short_code: &str, password_hash: &str, expires: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(cookie_secret()).expect("HMAC accepts any key length");
+    mac.update(format!("{}\n{}\n{}", short_code, password_hash, expires).as_bytes());
+    mac
+}
+
+/// `Set-Cookie` value unlocking a link for the configured time.
+pub fn unlock_cookie(short_code: &str, password_hash: &str) -> String {
+    let ttl = unlock_ttl();
+    let expires = Utc::now().timestamp() + ttl;
+    let signature = sign(short_code, password_hash, expires).finalize().into_bytes();
+    let secure = env::var("BASE_URL").is_ok_and(|url| url.starts_with("https://"));
+    format!(
+        "{}={}.{}; Path=/{}; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        UNLOCK_COOKIE,
+        expires,
+        base64_url.encode(signature),
+        short_code,
+        ttl,
+        if secure { "; Secure" } else { "" }
+    )
+}
+
+/// Whether the unlock cookie sent by the visitor is valid for this link.
+pub fn is_unlocked(short_code: &str, password_hash: &str, cookie: Option<&str>) -> bool {
+    let Some((expires, signature)) = cookie.and_then(|value| value.split_once('.')) else {
+        return false;
+    };
+    let (Ok(expires), Ok(signature)) = (expires.parse::<i64>(), base64_url.decode(signature)) else {
+        return false;
+    };
+    expires > Utc::now().timestamp()
+        && sign(short_code, password_hash, expires).verify_slice(&signature).is_ok()
+}
+
+fn failed_attempts_key(domain_id: Option<i32>, short_code: &str) -> String {
+    match domain_id {
+        Some(domain_id) => format!("unlock_failures:{}:{}", domain_id, short_code),
+        None => format!("unlock_failures:{}", short_code),
+    }
+}
+
+/// Count a password attempt on a link, starting the attempt window on the first one.
+///
+/// Run as one script so the count and its expiry are set together, even if the connection drops in between.
+const CLAIM_ATTEMPT_SCRIPT: &str = r"
+local attempts = redis.call('INCR', KEYS[1])
+if redis.call('TTL', KEYS[1]) < 0 then redis.call('EXPIRE', KEYS[1], ARGV[1]) end
+return attempts";
+/// Give back an attempt, unless its window has already ended and the count with it.
+const RELEASE_ATTEMPT_SCRIPT: &str = r"
+if redis.call('EXISTS', KEYS[1]) == 1 then return redis.call('DECR', KEYS[1]) end
+return 0";
+
+/// Count a password attempt on a link before checking the password. Returns `false` if the link
+/// refuses further attempts for now, and the password must not be checked.
+///
+/// Counting before checking, rather than after a wrong password, keeps parallel guesses from all
+/// getting past the limit. A correct password gives its attempt back with [`release_password_attempt`],
+/// so only wrong ones add up; they are forgotten after the attempt window.
+pub async fn claim_password_attempt(conn: &mut Connection, domain_id: Option<i32>, short_code: &str) -> RedisResult<bool> {
+    let key = failed_attempts_key(domain_id, short_code);
+    let attempts: u32 = redis::cmd("EVAL")
+        .arg(CLAIM_ATTEMPT_SCRIPT)
+        .arg(1)
+        .arg(&key)
+        .arg(FAILED_ATTEMPT_WINDOW_SECS)
+        .query_async(conn)
+        .instrument(info_span!("redis.claim_attempt", key = %key))
+        .await?;
+    Ok(attempts <= max_failed_attempts())
+}
+
+/// Give back the attempt counted by [`claim_password_attempt`] for a correct password.
+pub async fn release_password_attempt(conn: &mut Connection, domain_id: Option<i32>, short_code: &str) -> RedisResult<()> {
+    let key = failed_attempts_key(domain_id, short_code);
+    redis::cmd("EVAL")
+        .arg(RELEASE_ATTEMPT_SCRIPT)
+        .arg(1)
+        .arg(&key)
+        .query_async::<()>(conn)
+        .instrument(info_span!("redis.release_attempt", key = %key))
+        .await
+}
@@ -7,6 +7,7 @@ use warp::filters::BoxedFilter;
 use warp::reject::Rejection;
 use warp::Filter;
 use std::env;
+use crate::utils::request_context::client_addr;
 
 #[derive(Debug)]
 pub struct TooManyRequests;
@@ -50,7 +51,7 @@ pub async fn rate_limit(
 pub fn with_ip_rate_limit(
     rate_limiter: Arc<Mutex<HashMap<IpAddr, (u32, Instant)>>>,
 ) -> BoxedFilter<()> {
-    warp::addr::remote()
+    client_addr()
         .and_then(move |addr: Option<SocketAddr>| {
             let rate_limiter = rate_limiter.clone();
             async move {
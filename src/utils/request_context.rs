@@ -0,0 +1,74 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+use warp::Filter;
+
+/// Header used to carry the request ID in both directions.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-supplied request ID that is accepted as-is.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+/// Per-request state shared between the server and the handlers.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    pub request_id: String,
+    short_code: Arc<Mutex<Option<String>>>,
+}
+
+impl RequestContext {
+    pub fn new(request_id: String) -> Self {
+        RequestContext {
+            request_id,
+            short_code: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// The short code recorded by the handler, if any.
+    pub fn short_code(&self) -> Option<String> {
+        self.short_code.lock().unwrap().clone()
+    }
+}
+
+/// Address of the connected client, attached to each request by the server.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientAddr(pub SocketAddr);
+
+/// Use the client-supplied request ID when it is sane, otherwise generate a new one.
+pub fn request_id_or_new(supplied: Option<&str>) -> String {
+    match supplied {
+        Some(id)
+            if !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) =>
+        {
+            id.to_string()
+        }
+        _ => Uuid::new_v4().to_string(),
+    }
+}
+
+/// Run `fut` with `context` as the current request context.
+pub async fn scope<F: Future>(context: RequestContext, fut: F) -> F::Output {
+    REQUEST_CONTEXT.scope(context, fut).await
+}
+
+/// The ID of the request currently being handled, if any.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_CONTEXT.try_with(|ctx| ctx.request_id.clone()).ok()
+}
+
+/// Record the short code the current request is about, for the access log.
+pub fn record_short_code(code: &str) {
+    let _ = REQUEST_CONTEXT.try_with(|ctx| *ctx.short_code.lock().unwrap() = Some(code.to_string()));
+}
+
+/// Extract the address of the connected client.
+pub fn client_addr() -> impl Filter<Extract = (Option<SocketAddr>,), Error = std::convert::Infallible> + Clone {
+    warp::ext::optional::<ClientAddr>().map(|addr: Option<ClientAddr>| addr.map(|a| a.0))
+}
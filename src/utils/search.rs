@@ -0,0 +1,119 @@
+use crate::config::db::LinkCursor;
+use crate::models::{Link, LinkPage, ListedLink};
+use crate::utils::short_code::short_url;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as base64_url;
+use base64::Engine as _;
+
+/// Number of links listed when no limit is given.
+pub const DEFAULT_LINK_LIMIT: i64 = 50;
+/// Most links listed at once.
+const MAX_LINK_LIMIT: i64 = 500;
+
+#[derive(Debug)]
+pub struct InvalidCursor;
+
+impl warp::reject::Reject for InvalidCursor {}
+
+/// The number of links to list for a requested limit.
+pub fn link_limit(requested: Option<i64>) -> i64 {
+    requested.unwrap_or(DEFAULT_LINK_LIMIT).clamp(1, MAX_LINK_LIMIT)
+}
+
+/// The tags of a comma-separated tag filter.
+pub fn parse_tags(tags: Option<&str>) -> Vec<String> {
+    tags.into_iter()
+        .flat_map(|tags| tags.split(','))
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The position of the last link of the previous page, from its opaque cursor.
+///
+/// Cursors of a search carry the link's relevance, so they only continue a search, and other cursors only a listing.
+pub fn decode_cursor(cursor: &str, searching: bool) -> Result<LinkCursor, InvalidCursor> {
+    let cursor = base64_url.decode(cursor).ok().and_then(|bytes| String::from_utf8(bytes).ok()).ok_or(InvalidCursor)?;
+    let position = match cursor.strip_prefix("rank:").and_then(|ranked| ranked.split_once(':')) {
+        Some((rank, id)) if searching => rank.parse().ok().zip(id.parse().ok()).map(|(rank, id)| LinkCursor {
+            rank: Some(rank),
+            id,
+        }),
+        None if !searching => {
+            let id = cursor.strip_prefix("link:").and_then(|id| id.parse().ok());
+            id.map(|id| LinkCursor { rank: None, id })
+        }
+        _ => None,
+    };
+    position.ok_or(InvalidCursor)
+}
+
+fn encode_cursor(cursor: LinkCursor) -> String {
+    match cursor.rank {
+        Some(rank) => base64_url.encode(format!("rank:{}:{}", rank, cursor.id)),
+        None => base64_url.encode(format!("link:{}", cursor.id)),
+    }
+}
+
+/// A page of links out of up to `limit + 1` links; the extra link only tells that another page follows.
+pub fn link_page(mut links: Vec<(Link, LinkCursor)>, limit: i64) -> LinkPage {
+    let more = links.len() as i64 > limit;
+    links.truncate(limit as usize);
+    let next_cursor = links.last().filter(|_| more).map(|&(_, cursor)| encode_cursor(cursor));
+    LinkPage {
+        links: links
+            .into_iter()
+            .map(|(link, _)| ListedLink {
+                short_url: short_url(link.domain.as_deref(), &link.short_code),
+                link,
+            })
+            .collect(),
+        next_cursor,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_cursors_survive_the_round_trip() {
+        for rank in [0.0, 0.1, 0.0607927, 1e-8, f32::MAX] {
+            let cursor = LinkCursor { rank: Some(rank), id: 42 };
+            assert_eq!(decode_cursor(&encode_cursor(cursor), true).unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn listing_cursors_survive_the_round_trip() {
+        for id in [1, 42, i32::MAX] {
+            let cursor = LinkCursor { rank: None, id };
+            assert_eq!(decode_cursor(&encode_cursor(cursor), false).unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn cursors_only_continue_what_they_came_from() {
+        let search = encode_cursor(LinkCursor { rank: Some(0.5), id: 7 });
+        let listing = encode_cursor(LinkCursor { rank: None, id: 7 });
+        assert!(decode_cursor(&search, false).is_err());
+        assert!(decode_cursor(&listing, true).is_err());
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        let cursors = [
+            "".to_string(),
+            "not base64!".to_string(),
+            base64_url.encode("link:"),
+            base64_url.encode("link:abc"),
+            base64_url.encode("rank:0.5"),
+            base64_url.encode("rank:high:7"),
+            base64_url.encode("7"),
+        ];
+        for cursor in &cursors {
+            assert!(decode_cursor(cursor, false).is_err(), "{}", cursor);
+            assert!(decode_cursor(cursor, true).is_err(), "{}", cursor);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use std::env;
+use uuid::Uuid;
+
+/// Generate a new random short code.
+pub fn generate_short_code() -> String {
+    Uuid::new_v4().to_string()[..8].to_string()
+}
+
+/// Build the public short URL of a short code, on its branded domain or on `BASE_URL`.
+pub fn short_url(domain: Option<&str>, short_code: &str) -> String {
+    let base_url = env::var("BASE_URL").expect("BASE_URL is not set in .env");
+    match domain {
+        Some(host) => {
+            let scheme = base_url.split_once("://").map_or("https", |(scheme, _)| scheme);
+            format!("{}://{}/{}", scheme, host, short_code)
+        }
+        None => format!("{}/{}", base_url, short_code),
+    }
+}
+
+/// First path segments taken by other routes, which cannot be used as short codes.
+const RESERVED_CODES: &[&str] = &["api", "shorten"];
+
+/// Whether a short code can be stored and resolved: 1-64 letters, digits, `-` or `_`.
+pub fn is_valid_short_code(code: &str) -> bool {
+    !code.is_empty()
+        && code.len() <= 64
+        && code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && !RESERVED_CODES.contains(&code)
+}
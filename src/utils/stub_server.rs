@@ -0,0 +1,98 @@
+//! A minimal HTTP server for tests of code that fetches other sites.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// A request received by the stub server.
+pub struct StubRequest {
+    pub method: String,
+    pub path: String,
+}
+
+/// A response of the stub server.
+pub struct StubResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl StubResponse {
+    pub fn new(status: u16) -> Self {
+        StubResponse { status, headers: Vec::new(), body: Vec::new() }
+    }
+
+    pub fn html(body: impl Into<String>) -> Self {
+        StubResponse::new(200).header("Content-Type", "text/html; charset=utf-8").body(body.into())
+    }
+
+    pub fn redirect(location: &str) -> Self {
+        StubResponse::new(302).header("Location", location)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+/// Serve requests on a random local port with `handler` until the test ends, one request per connection.
+///
+/// Returns the address listened on.
+pub async fn serve<F>(handler: F) -> SocketAddr
+where
+    F: Fn(StubRequest) -> StubResponse + Send + Sync + 'static,
+{
+    serve_async(move |request| std::future::ready(handler(request))).await
+}
+
+/// Like [`serve`], for handlers that need to wait, such as to simulate a slow site.
+pub async fn serve_async<F, Fut>(handler: F) -> SocketAddr
+where
+    F: Fn(StubRequest) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = StubResponse> + Send,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("Failed to bind stub server");
+    let addr = listener.local_addr().unwrap();
+    let handler = std::sync::Arc::new(handler);
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    match socket.read(&mut buf).await {
+                        Ok(0) | Err(_) => return,
+                        Ok(n) => request.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let head = String::from_utf8_lossy(&request).into_owned();
+                let mut line = head.lines().next().unwrap_or_default().split(' ');
+                let method = line.next().unwrap_or_default().to_string();
+                let path = line.next().unwrap_or_default().to_string();
+                let is_head = method == "HEAD";
+                let response = handler(StubRequest { method, path }).await;
+
+                let mut raw = format!("HTTP/1.1 {} Stub\r\nConnection: close\r\n", response.status);
+                for (name, value) in &response.headers {
+                    raw.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                raw.push_str(&format!("Content-Length: {}\r\n\r\n", response.body.len()));
+                let mut raw = raw.into_bytes();
+                if !is_head {
+                    raw.extend_from_slice(&response.body);
+                }
+                let _ = socket.write_all(&raw).await;
+                let _ = socket.shutdown().await;
+            });
+        }
+    });
+    addr
+}
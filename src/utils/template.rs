@@ -0,0 +1,151 @@
+use crate::utils::passthrough::Forwarded;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use url::form_urlencoded;
+
+/// Bytes escaped in substituted values: everything except RFC 3986 unreserved characters.
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// A placeholder in a templated destination.
+enum Placeholder<'a> {
+    /// `{1}`, `{2}`, ...: a path segment after the short code, counted from 1.
+    Segment(usize),
+    /// `{path}`: the whole path after the short code.
+    Path,
+    /// `{query.name}`: a query parameter of the visited URL.
+    Query(&'a str),
+}
+
+fn placeholder(name: &str) -> Option<Placeholder<'_>> {
+    if name == "path" {
+        return Some(Placeholder::Path);
+    }
+    if let Some(param) = name.strip_prefix("query.") {
+        return (!param.is_empty()).then_some(Placeholder::Query(param));
+    }
+    name.parse().ok().filter(|&index| index > 0).map(Placeholder::Segment)
+}
+
+/// Replace every placeholder with `value(placeholder)`, leaving other braces such as `{lang}` alone.
+fn substitute<'a>(template: &'a str, mut value: impl FnMut(Placeholder<'a>) -> String) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let inner = &rest[start + 1..];
+        match inner.find('}').and_then(|end| Some((end, placeholder(&inner[..end])?))) {
+            Some((end, found)) => {
+                rendered.push_str(&value(found));
+                rest = &inner[end + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = inner;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Whether a destination has placeholders filled from the visited URL.
+pub fn is_template(url: &str) -> bool {
+    let mut found = false;
+    substitute(url, |_| {
+        found = true;
+        String::new()
+    });
+    found
+}
+
+/// Fill the placeholders of a destination from the visited URL.
+///
+/// Values are percent-decoded, then percent-encoded again so they cannot add path segments,
+/// query parameters or a fragment; only `{path}` keeps its `/` separators. `.` and `..` segments
+/// render as nothing, and so do placeholders with no value.
+pub fn render_template(template: &str, forwarded: &Forwarded) -> String {
+    let segments: Vec<String> = forwarded
+        .path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| match percent_decode_str(segment).decode_utf8_lossy().as_ref() {
+            "." | ".." => String::new(),
+            segment => encode(segment),
+        })
+        .collect();
+    substitute(template, |found| match found {
+        Placeholder::Segment(index) => segments.get(index - 1).cloned().unwrap_or_default(),
+        Placeholder::Path => segments
+            .iter()
+            .filter(|segment| !segment.is_empty())
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("/"),
+        Placeholder::Query(name) => forwarded
+            .query
+            .as_deref()
+            .and_then(|query| form_urlencoded::parse(query.as_bytes()).find(|(param, _)| param == name))
+            .map(|(_, value)| encode(&value))
+            .unwrap_or_default(),
+    })
+}
+
+/// Fill every placeholder of a destination with `sample`, to check what it can render to.
+pub fn render_sample(template: &str, sample: &str) -> String {
+    substitute(template, |_| encode(sample))
+}
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, COMPONENT).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visited(path: &str, query: Option<&str>) -> Forwarded {
+        Forwarded { path: path.to_string(), query: query.map(str::to_string) }
+    }
+
+    #[test]
+    fn values_are_percent_encoded() {
+        let forwarded = visited("caf%C3%A9%20menu/a%2Fb", Some("id=1%262&next=%23top&q=a+b"));
+        assert_eq!(render_template("https://example.com/{1}", &forwarded), "https://example.com/caf%C3%A9%20menu");
+        // An encoded `/` stays inside its segment instead of adding one
+        assert_eq!(render_template("https://example.com/{2}", &forwarded), "https://example.com/a%2Fb");
+        assert_eq!(render_template("https://example.com/{path}", &forwarded), "https://example.com/caf%C3%A9%20menu/a%2Fb");
+        assert_eq!(
+            render_template("https://example.com/?id={query.id}&next={query.next}&q={query.q}", &forwarded),
+            "https://example.com/?id=1%262&next=%23top&q=a%20b"
+        );
+    }
+
+    #[test]
+    fn dot_segments_render_as_nothing() {
+        for path in ["../admin", "%2e%2e/admin", "%2E%2E/admin", "./admin"] {
+            let forwarded = visited(path, None);
+            assert_eq!(render_template("https://example.com/docs/{1}/{2}", &forwarded), "https://example.com/docs//admin");
+            assert_eq!(render_template("https://example.com/docs/{path}", &forwarded), "https://example.com/docs/admin");
+        }
+    }
+
+    #[test]
+    fn missing_values_render_as_nothing() {
+        let forwarded = visited("a", Some("id=1&id=2"));
+        assert_eq!(render_template("https://example.com/{1}/{2}", &forwarded), "https://example.com/a/");
+        assert_eq!(render_template("https://example.com/?ref={query.ref}", &forwarded), "https://example.com/?ref=");
+        // The first of duplicate query parameters is used
+        assert_eq!(render_template("https://example.com/{query.id}", &forwarded), "https://example.com/1");
+    }
+
+    #[test]
+    fn only_known_placeholders_are_templates() {
+        assert!(is_template("https://example.com/{1}"));
+        assert!(is_template("https://example.com/{path}"));
+        assert!(is_template("https://example.com/?id={query.id}"));
+        let urls = ["https://example.com/{lang}/", "https://example.com/{0}", "https://example.com/{query.}", "https://example.com/{"];
+        for url in urls {
+            assert!(!is_template(url), "{}", url);
+            assert_eq!(render_template(url, &visited("a", Some("b=c"))), url);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use crate::config::db::{purge_deleted_links, trash_retention_days, DbError};
+use crate::config::redis::invalidate_link;
+use crate::models::{Actor, AuditAction, Link, TrashedLink};
+use crate::utils::audit::{link_event, record, system_actor};
+use crate::utils::short_code::short_url;
+use deadpool_postgres::Pool;
+use deadpool_redis::Pool as RedisPool;
+use log::{error, info};
+use std::time::Duration;
+use tokio_postgres::Client;
+use tracing::{info_span, Instrument};
+
+/// Time between two purges of the trash.
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Purge the trash forever, once per interval.
+pub async fn run_trash_purge(db_pool: Pool, redis_pool: RedisPool) {
+    info!("Purging links deleted more than {} days ago every {}s", trash_retention_days(), PURGE_INTERVAL.as_secs());
+    let mut ticker = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let mut client = match db_pool.get().await {
+            Ok(client) => client,
+            Err(e) => {
+                error!("Failed to get DB client for purging the trash: {:?}", e);
+                continue;
+            }
+        };
+        if let Err(e) = purge_trash(&mut client, &redis_pool, &system_actor())
+            .instrument(info_span!("trash.purge"))
+            .await
+        {
+            error!("Failed to purge the trash: {:?}", e);
+        }
+    }
+}
+
+/// Delete for good the links that outstayed `TRASH_RETENTION_DAYS` in the trash, releasing their short codes.
+///
+/// A purged code may be taken by a new link, so its cached destination is dropped too.
+pub async fn purge_trash(client: &mut Client, redis_pool: &RedisPool, actor: &Actor) -> Result<Vec<Link>, DbError> {
+    let tx = client.transaction().await.map_err(|_| DbError::DatabaseError)?;
+    let purged = purge_deleted_links(&tx, trash_retention_days()).await?;
+    if purged.is_empty() {
+        return Ok(purged);
+    }
+    let events: Vec<_> = purged.iter().map(|link| link_event(AuditAction::LinkPurge, Some(link), None)).collect();
+    record(&tx, actor, &events).await?;
+    tx.commit().await.map_err(|_| DbError::DatabaseError)?;
+    match redis_pool.get().await {
+        Ok(mut conn) => {
+            for link in &purged {
+                if let Err(e) = invalidate_link(&mut conn, link.domain_id, &link.short_code).await {
+                    error!("Failed to invalidate cached link {}: {:?}", link.short_code, e);
+                }
+            }
+        }
+        Err(e) => error!("Failed to get Redis connection: {:?}", e),
+    }
+    info!("Purged {} links from the trash", purged.len());
+    Ok(purged)
+}
+
+/// A link in the trash, with when it is due to be purged.
+pub fn trashed(link: Link) -> TrashedLink {
+    let purge_at = link
+        .deleted_at
+        .map(|deleted_at| deleted_at + chrono::Duration::days(trash_retention_days().into()));
+    TrashedLink {
+        short_url: short_url(link.domain.as_deref(), &link.short_code),
+        link,
+        purge_at,
+    }
+}
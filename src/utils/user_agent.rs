@@ -0,0 +1,61 @@
+use crate::models::targeting::{Browser, DeviceClass, Os, UserAgent};
+
+/// Fragments found in the `User-Agent` of crawlers and link preview fetchers.
+const BOT_MARKERS: [&str; 6] = ["bot", "crawler", "spider", "slurp", "facebookexternalhit", "preview"];
+
+/// Parse the parts of a `User-Agent` header that targeting rules can match on.
+///
+/// This recognises the common browsers and platforms only; anything else is left
+/// as `None` and matches no rule naming that field. A missing header counts as a desktop.
+pub fn parse_user_agent(header: Option<&str>) -> UserAgent {
+    let Some(header) = header else {
+        return UserAgent { os: None, device: DeviceClass::Desktop, browser: None };
+    };
+    let lower = header.to_ascii_lowercase();
+    let has = |marker: &str| header.contains(marker);
+
+    let os = if has("iPhone") || has("iPad") || has("iPod") {
+        Some(Os::Ios)
+    } else if has("Android") {
+        Some(Os::Android)
+    } else if has("CrOS") {
+        Some(Os::Chromeos)
+    } else if has("Windows") {
+        Some(Os::Windows)
+    } else if has("Macintosh") || has("Mac OS X") {
+        Some(Os::Macos)
+    } else if has("Linux") {
+        Some(Os::Linux)
+    } else {
+        None
+    };
+
+    let device = if BOT_MARKERS.iter().any(|marker| lower.contains(marker)) {
+        DeviceClass::Bot
+    } else if has("iPad") || has("Tablet") || (os == Some(Os::Android) && !has("Mobile")) {
+        DeviceClass::Tablet
+    } else if has("iPhone") || has("iPod") || has("Mobi") {
+        DeviceClass::Mobile
+    } else {
+        DeviceClass::Desktop
+    };
+
+    // Most browsers also claim to be Chrome and Safari, so the specific ones are checked first
+    let browser = if has("Edg/") || has("EdgA/") || has("EdgiOS/") {
+        Some(Browser::Edge)
+    } else if has("OPR/") || has("Opera") {
+        Some(Browser::Opera)
+    } else if has("SamsungBrowser/") {
+        Some(Browser::Samsung)
+    } else if has("Firefox/") || has("FxiOS/") {
+        Some(Browser::Firefox)
+    } else if has("Chrome/") || has("CriOS/") {
+        Some(Browser::Chrome)
+    } else if has("Safari/") {
+        Some(Browser::Safari)
+    } else {
+        None
+    };
+
+    UserAgent { os, device, browser }
+}
@@ -0,0 +1,25 @@
+use crate::models::UtmParams;
+use crate::utils::passthrough::merge_query;
+use std::env;
+
+/// When a link's UTM parameters are added to its destinations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UtmMode {
+    /// Merged into the destination on every redirect, so the stored URLs stay clean.
+    Redirect,
+    /// Written into the stored destination URLs when the link is created.
+    Create,
+}
+
+/// When to add UTM parameters, from `UTM_MODE` (`redirect` or `create`, default `redirect`).
+pub fn utm_mode() -> UtmMode {
+    match env::var("UTM_MODE").as_deref() {
+        Ok("create") => UtmMode::Create,
+        _ => UtmMode::Redirect,
+    }
+}
+
+/// Add UTM parameters to a destination; parameters already in the URL are kept as they are.
+pub fn tag_url(url: &str, utm: &UtmParams) -> String {
+    merge_query(url, utm.pairs())
+}
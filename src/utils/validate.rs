@@ -1,25 +1,372 @@
+use crate::config::campaigns::get_campaign;
+use crate::config::db::DbError;
+use crate::config::domains::get_domain;
+use crate::models::{LanguageRule, LinkLabels, NewLink, ShortenRequest, TargetingRule, UtmParams, Variant};
+use crate::utils::domain::normalize_host;
+use crate::utils::language::{is_valid_language_tag, LANG_PLACEHOLDER};
+use crate::utils::password::hash_password;
+use crate::utils::template::{is_template, render_sample};
+use crate::utils::utm::{tag_url, utm_mode, UtmMode};
+use chrono::{DateTime, Utc};
 use regex::Regex;
+use std::collections::HashSet;
+use thiserror::Error;
+use tokio_postgres::Client;
+use url::Url;
 use warp::http::StatusCode;
 use warp::reject::{custom, Rejection};
 use warp::reply::Reply;
 
+/// Most targeting rules a single link may have.
+const MAX_TARGETING_RULES: usize = 20;
+/// Most language rules a single link may have.
+const MAX_LANGUAGE_RULES: usize = 50;
+/// Most variants a single link may have.
+const MAX_VARIANTS: usize = 20;
+/// Longest variant name, which is stored in a cookie and with each click.
+const MAX_VARIANT_NAME_LEN: usize = 32;
+/// Largest weight of a single variant.
+const MAX_VARIANT_WEIGHT: u32 = 10_000;
+/// Longest value of a single UTM parameter.
+const MAX_UTM_VALUE_LEN: usize = 200;
+/// Longest campaign preset name.
+const MAX_CAMPAIGN_NAME_LEN: usize = 64;
+/// Most tags a single link may have.
+const MAX_TAGS: usize = 20;
+/// Longest tag.
+const MAX_TAG_LEN: usize = 50;
+/// Longest link title.
+const MAX_TITLE_LEN: usize = 200;
+/// Longest link notes.
+const MAX_NOTES_LEN: usize = 2000;
+/// Longest folder path.
+const MAX_FOLDER_LEN: usize = 100;
+
 #[derive(Debug)]
 pub struct InvalidLink;
 
 impl warp::reject::Reject for InvalidLink {}
 
 /// Validate the link to prevent exploitation
+///
+/// Query strings are allowed, but every `%` must start a valid percent-encoded byte.
 pub fn validate_link(link: String) -> Result<String, Rejection> {
     let url_regex = Regex::new(r"^(https?|ftp)://[^\s/$.?#].[^\s]*$").unwrap();
-    let banned_chars = vec![" ", "?", "\"", "&", "%", "javascript:", "data:"];
+    let banned_chars = [" ", "\"", "javascript:", "data:"];
 
-    if banned_chars.iter().any(|c| link.contains(c)) || !url_regex.is_match(&link) {
+    if banned_chars.iter().any(|c| link.contains(c))
+        || !has_valid_percent_encoding(&link)
+        || !url_regex.is_match(&link)
+    {
         return Err(custom(InvalidLink));
     }
 
     Ok(link)
 }
 
+/// Whether every `%` is followed by two hex digits.
+fn has_valid_percent_encoding(link: &str) -> bool {
+    let bytes = link.as_bytes();
+    bytes.iter().enumerate().filter(|(_, &b)| b == b'%').all(|(i, _)| {
+        bytes.get(i + 1..i + 3).is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+    })
+}
+
+/// Why a requested link cannot be created.
+#[derive(Debug, Error)]
+pub enum LinkError {
+    #[error("Invalid link provided")]
+    InvalidLink,
+    #[error("Invalid {0} provided")]
+    InvalidFallback(&'static str),
+    #[error("Password must not be empty")]
+    EmptyPassword,
+    #[error("active_from must be before active_until")]
+    InvalidSchedule,
+    #[error("Invalid targeting rule: {0}")]
+    InvalidTargetingRule(&'static str),
+    #[error("Invalid language rule: {0}")]
+    InvalidLanguageRule(&'static str),
+    #[error("Invalid variant: {0}")]
+    InvalidVariant(&'static str),
+    #[error("Invalid destination template: {0}")]
+    InvalidTemplate(&'static str),
+    #[error("Invalid UTM parameters: {0}")]
+    InvalidUtm(&'static str),
+    #[error("Invalid campaign name")]
+    InvalidCampaign,
+    #[error("Invalid labels: {0}")]
+    InvalidLabels(&'static str),
+    #[error("Unknown campaign {0:?}")]
+    UnknownCampaign(String),
+    #[error("Unknown domain {0:?}")]
+    UnknownDomain(String),
+    #[error("Database error")]
+    Database(#[from] DbError),
+}
+
+impl warp::reject::Reject for LinkError {}
+
+/// Validate a shorten request and turn it into a link ready to be stored.
+///
+/// UTM parameters not given explicitly are taken from the named campaign preset of the link's workspace. With
+/// `UTM_MODE=create` they are also written into every destination the visitor may be routed to.
+/// A requested domain must have been added beforehand.
+pub async fn prepare_link(request: ShortenRequest, workspace_id: Option<i32>, client: &Client) -> Result<NewLink, LinkError> {
+    let domain = match &request.domain {
+        Some(host) => Some(
+            get_domain(client, &normalize_host(host))
+                .await?
+                .ok_or_else(|| LinkError::UnknownDomain(host.clone()))?,
+        ),
+        None => None,
+    };
+    let mut original_url = validate_link(request.url).map_err(|_| LinkError::InvalidLink)?;
+    let upcoming_url = validate_fallback(request.upcoming_url, "upcoming_url")?;
+    let ended_url = validate_fallback(request.ended_url, "ended_url")?;
+    let mut fallback_url = validate_fallback(request.fallback_url, "fallback_url")?;
+    let mut targeting_rules = validate_targeting_rules(request.targeting_rules)?;
+    let mut language_rules = validate_language_rules(request.language_rules)?;
+    let mut variants = validate_variants(request.variants)?;
+    let utm = match &request.campaign {
+        Some(name) => {
+            let campaign = get_campaign(client, workspace_id, name).await?.ok_or_else(|| LinkError::UnknownCampaign(name.clone()))?;
+            request.utm.or(&campaign.utm)
+        }
+        None => request.utm,
+    };
+    let utm = validate_utm(utm)?;
+    let labels = validate_labels(LinkLabels {
+        tags: request.tags,
+        title: request.title,
+        notes: request.notes,
+        folder: request.folder,
+    })?;
+    let default_language = match request.default_language {
+        Some(lang) if !is_valid_language_tag(&lang) => return Err(LinkError::InvalidLanguageRule("invalid default_language")),
+        lang => lang,
+    };
+    let destinations: Vec<&str> = std::iter::once(original_url.as_str())
+        .chain(fallback_url.as_deref())
+        .chain(targeting_rules.iter().map(|rule| rule.url.as_str()))
+        .chain(language_rules.iter().filter_map(|rule| rule.url.as_deref()))
+        .chain(variants.iter().map(|variant| variant.url.as_str()))
+        .collect();
+    if destinations.iter().any(|url| url.contains(LANG_PLACEHOLDER)) && default_language.is_none() {
+        return Err(LinkError::InvalidLanguageRule("destinations with {lang} need a default_language"));
+    }
+    destinations.iter().try_for_each(|url| validate_template(url))?;
+    if upcoming_url.iter().chain(&ended_url).any(|url| is_template(url)) {
+        return Err(LinkError::InvalidTemplate("upcoming_url and ended_url cannot have placeholders"));
+    }
+    if !is_valid_schedule(request.active_from, request.active_until) {
+        return Err(LinkError::InvalidSchedule);
+    }
+    let password_hash = match request.password {
+        Some(password) if password.is_empty() => return Err(LinkError::EmptyPassword),
+        Some(password) => Some(hash_password(password).await),
+        None => None,
+    };
+    if !utm.is_empty() && utm_mode() == UtmMode::Create {
+        let tag = |url: &mut String| *url = tag_url(url, &utm);
+        tag(&mut original_url);
+        fallback_url.iter_mut().for_each(tag);
+        targeting_rules.iter_mut().for_each(|rule| tag(&mut rule.url));
+        language_rules.iter_mut().filter_map(|rule| rule.url.as_mut()).for_each(tag);
+        variants.iter_mut().for_each(|variant| tag(&mut variant.url));
+    }
+
+    Ok(NewLink {
+        domain,
+        original_url,
+        password_hash,
+        single_use: request.single_use,
+        interstitial: request.interstitial,
+        active_from: request.active_from,
+        active_until: request.active_until,
+        upcoming_url,
+        ended_url,
+        fallback_url,
+        targeting_rules,
+        language_rules,
+        default_language,
+        variants,
+        passthrough: request.passthrough,
+        utm,
+        campaign: request.campaign,
+        labels,
+    })
+}
+
+/// Check and tidy up the labels of a link.
+///
+/// Blank titles, notes and folders are dropped, and duplicate tags are removed. Tags cannot contain
+/// commas, which separate them when filtering, and folders lose leading and trailing slashes.
+pub fn validate_labels(labels: LinkLabels) -> Result<LinkLabels, LinkError> {
+    if labels.tags.len() > MAX_TAGS {
+        return Err(LinkError::InvalidLabels("too many tags"));
+    }
+    let mut tags: Vec<String> = Vec::with_capacity(labels.tags.len());
+    for tag in labels.tags {
+        let tag = tag.trim();
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_LEN || tag.contains(',') {
+            return Err(LinkError::InvalidLabels("tags must be 1 to 50 characters without commas"));
+        }
+        if !tags.iter().any(|existing| existing == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    let text = |value: Option<String>, max_len: usize, error: &'static str| {
+        match value.as_deref().map(str::trim).filter(|value| !value.is_empty()) {
+            Some(value) if value.chars().count() > max_len => Err(LinkError::InvalidLabels(error)),
+            value => Ok(value.map(str::to_string)),
+        }
+    };
+    let folder = labels.folder.map(|folder| folder.trim().trim_matches('/').to_string());
+    Ok(LinkLabels {
+        tags,
+        title: text(labels.title, MAX_TITLE_LEN, "title is too long")?,
+        notes: text(labels.notes, MAX_NOTES_LEN, "notes are too long")?,
+        folder: text(folder, MAX_FOLDER_LEN, "folder is too long")?,
+    })
+}
+
+fn validate_fallback(url: Option<String>, field: &'static str) -> Result<Option<String>, LinkError> {
+    url.map(|url| validate_link(url).map_err(|_| LinkError::InvalidFallback(field)))
+        .transpose()
+}
+
+/// Every rule needs a condition and a valid URL, and a link has at most `MAX_TARGETING_RULES` of them.
+fn validate_targeting_rules(rules: Vec<TargetingRule>) -> Result<Vec<TargetingRule>, LinkError> {
+    if rules.len() > MAX_TARGETING_RULES {
+        return Err(LinkError::InvalidTargetingRule("too many rules"));
+    }
+    rules
+        .into_iter()
+        .map(|rule| {
+            if !rule.has_conditions() {
+                return Err(LinkError::InvalidTargetingRule("a rule needs at least one condition"));
+            }
+            let country = rule
+                .country
+                .map(|country| {
+                    if country.len() == 2 && country.bytes().all(|b| b.is_ascii_alphabetic()) {
+                        Ok(country.to_ascii_uppercase())
+                    } else {
+                        Err(LinkError::InvalidTargetingRule("country must be a two-letter ISO code"))
+                    }
+                })
+                .transpose()?;
+            let url = validate_link(rule.url).map_err(|_| LinkError::InvalidTargetingRule("invalid url"))?;
+            Ok(TargetingRule { country, url, ..rule })
+        })
+        .collect()
+}
+
+/// Every rule needs a distinct language tag, and a valid URL when it has one.
+fn validate_language_rules(rules: Vec<LanguageRule>) -> Result<Vec<LanguageRule>, LinkError> {
+    if rules.len() > MAX_LANGUAGE_RULES {
+        return Err(LinkError::InvalidLanguageRule("too many rules"));
+    }
+    let mut seen = HashSet::new();
+    rules
+        .into_iter()
+        .map(|rule| {
+            if !is_valid_language_tag(&rule.lang) {
+                return Err(LinkError::InvalidLanguageRule("invalid language tag"));
+            }
+            if !seen.insert(rule.lang.to_ascii_lowercase()) {
+                return Err(LinkError::InvalidLanguageRule("duplicate language"));
+            }
+            let url = rule
+                .url
+                .map(|url| validate_link(url).map_err(|_| LinkError::InvalidLanguageRule("invalid url")))
+                .transpose()?;
+            Ok(LanguageRule { url, ..rule })
+        })
+        .collect()
+}
+
+/// Variants need distinct, cookie-safe names, valid URLs and bounded weights.
+fn validate_variants(variants: Vec<Variant>) -> Result<Vec<Variant>, LinkError> {
+    if variants.len() > MAX_VARIANTS {
+        return Err(LinkError::InvalidVariant("too many variants"));
+    }
+    let mut seen = HashSet::new();
+    variants
+        .into_iter()
+        .map(|variant| {
+            let valid_name = (1..=MAX_VARIANT_NAME_LEN).contains(&variant.name.len())
+                && variant.name.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_'));
+            if !valid_name {
+                return Err(LinkError::InvalidVariant("names must be letters, digits, - or _"));
+            }
+            if !seen.insert(variant.name.clone()) {
+                return Err(LinkError::InvalidVariant("duplicate name"));
+            }
+            if variant.weight > MAX_VARIANT_WEIGHT {
+                return Err(LinkError::InvalidVariant("weight too large"));
+            }
+            let url = validate_link(variant.url).map_err(|_| LinkError::InvalidVariant("invalid url"))?;
+            Ok(Variant { url, ..variant })
+        })
+        .collect()
+}
+
+/// A templated destination must render to an allowed URL, and placeholders must not be able to
+/// change its scheme, host or port.
+fn validate_template(url: &str) -> Result<(), LinkError> {
+    if !is_template(url) {
+        return Ok(());
+    }
+    let origin = |sample: &str| {
+        let rendered = validate_link(render_sample(url, sample))
+            .map_err(|_| LinkError::InvalidTemplate("the rendered URL is not allowed"))?;
+        let parsed = Url::parse(&rendered).map_err(|_| LinkError::InvalidTemplate("the rendered URL is not allowed"))?;
+        Ok::<_, LinkError>((parsed.scheme().to_string(), parsed.host_str().map(str::to_string), parsed.port()))
+    };
+    if origin("a")? != origin("b")? {
+        return Err(LinkError::InvalidTemplate("placeholders cannot change the scheme, host or port"));
+    }
+    Ok(())
+}
+
+/// UTM values must be non-empty, printable and reasonably short.
+pub fn validate_utm(utm: UtmParams) -> Result<UtmParams, LinkError> {
+    let valid = utm
+        .pairs()
+        .all(|(_, value)| !value.is_empty() && value.len() <= MAX_UTM_VALUE_LEN && !value.chars().any(char::is_control));
+    if !valid {
+        return Err(LinkError::InvalidUtm("values must be 1 to 200 printable characters"));
+    }
+    Ok(utm)
+}
+
+/// Campaign preset names are letters, digits, `-`, `_` and `.`.
+pub fn validate_campaign_name(name: &str) -> Result<(), LinkError> {
+    let valid = (1..=MAX_CAMPAIGN_NAME_LEN).contains(&name.len())
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+    if !valid {
+        return Err(LinkError::InvalidCampaign);
+    }
+    Ok(())
+}
+
+/// An activation window must open before it closes.
+pub fn is_valid_schedule(active_from: Option<DateTime<Utc>>, active_until: Option<DateTime<Utc>>) -> bool {
+    match (active_from, active_until) {
+        (Some(from), Some(until)) => from < until,
+        _ => true,
+    }
+}
+
+/// Normalize a validated link so equivalent spellings compare equal.
+///
+/// Lowercases the scheme and host, drops default ports and fills in an empty path.
+pub fn normalize_url(link: &str) -> String {
+    Url::parse(link).map(String::from).unwrap_or_else(|_| link.to_string())
+}
+
 /// Error handler
 pub async fn error_handler(err: Rejection) -> Result<impl Reply, std::convert::Infallible> {
     if err.find::<InvalidLink>().is_some() {
@@ -32,6 +379,11 @@ pub async fn error_handler(err: Rejection) -> Result<impl Reply, std::convert::I
             "Too many requests, slow down!",
             StatusCode::TOO_MANY_REQUESTS,
         ))
+    } else if err.find::<crate::utils::auth::Unauthorized>().is_some() {
+        Ok(warp::reply::with_status(
+            "Missing or invalid API key",
+            StatusCode::UNAUTHORIZED,
+        ))
     } else if err.find::<crate::config::db::DbError>().is_some() {
         Ok(warp::reply::with_status(
             "Database error",
@@ -0,0 +1,121 @@
+use crate::models::variant::Variant;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use std::env;
+
+/// Cookie remembering which variant of a link a visitor was assigned.
+pub const VARIANT_COOKIE: &str = "link_variant";
+/// Default number of seconds a visitor keeps their variant.
+const DEFAULT_VARIANT_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// How long a visitor keeps their variant, from `VARIANT_COOKIE_TTL_SECS`.
+fn variant_ttl() -> i64 {
+    env::var("VARIANT_COOKIE_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&secs| secs > 0)
+        .unwrap_or(DEFAULT_VARIANT_TTL_SECS)
+}
+
+/// Pick a variant at random, in proportion to the weights.
+///
+/// Returns `None` when every weight is zero.
+pub fn pick_variant(variants: &[Variant]) -> Option<&Variant> {
+    let total: u64 = variants.iter().map(|variant| u64::from(variant.weight)).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut point = OsRng.next_u64() % total;
+    variants.iter().find(|variant| {
+        let weight = u64::from(variant.weight);
+        if point < weight {
+            return true;
+        }
+        point -= weight;
+        false
+    })
+}
+
+/// `Set-Cookie` value keeping a visitor on the same variant of a link.
+pub fn variant_cookie(short_code: &str, variant: &str) -> String {
+    let secure = env::var("BASE_URL").is_ok_and(|url| url.starts_with("https://"));
+    format!(
+        "{}={}; Path=/{}; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        VARIANT_COOKIE,
+        variant,
+        short_code,
+        variant_ttl(),
+        if secure { "; Secure" } else { "" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Destination;
+    use crate::utils::visitor::Visitor;
+    use std::collections::HashMap;
+
+    fn variant(name: &str, weight: u32) -> Variant {
+        Variant { name: name.to_string(), url: format!("https://example.com/{}", name), weight }
+    }
+
+    fn destination(variants: &[Variant]) -> Destination {
+        serde_json::from_value(serde_json::json!({
+            "original_url": "https://example.com/",
+            "password_protected": false,
+            "variants": variants,
+        }))
+        .unwrap()
+    }
+
+    fn picks(variants: &[Variant], draws: usize) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for _ in 0..draws {
+            *counts.entry(pick_variant(variants).unwrap().name.clone()).or_default() += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn zero_weights_are_never_picked() {
+        assert_eq!(pick_variant(&[]), None);
+        assert_eq!(pick_variant(&[variant("a", 0), variant("b", 0)]), None);
+        let counts = picks(&[variant("a", 0), variant("b", 1), variant("c", 0)], 200);
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts["b"], 200);
+    }
+
+    #[test]
+    fn picks_follow_the_weights() {
+        let counts = picks(&[variant("a", 90), variant("b", 10)], 10_000);
+        // Far outside what chance allows for a 90/10 split over 10,000 draws
+        assert!((8_500..=9_500).contains(&counts["a"]), "{:?}", counts);
+        assert!((500..=1_500).contains(&counts["b"]), "{:?}", counts);
+    }
+
+    #[test]
+    fn a_sticky_variant_is_kept() {
+        let link = destination(&[variant("a", 1), variant("b", 99)]);
+        let visitor = Visitor { variant: Some("a".to_string()), ..Default::default() };
+        for _ in 0..50 {
+            let (url, routing) = link.route(&visitor);
+            assert_eq!(url, "https://example.com/a");
+            assert_eq!(routing.variant.as_deref(), Some("a"));
+        }
+    }
+
+    #[test]
+    fn a_removed_sticky_variant_is_replaced() {
+        let link = destination(&[variant("b", 1), variant("c", 0)]);
+        let visitor = Visitor { variant: Some("a".to_string()), ..Default::default() };
+        let (url, routing) = link.route(&visitor);
+        assert_eq!(url, "https://example.com/b");
+        // The new assignment differs from the cookie, so the handler sets it again
+        assert_eq!(routing.variant.as_deref(), Some("b"));
+
+        // Without any variant left the visitor goes to the destination
+        let (url, routing) = destination(&[]).route(&visitor);
+        assert_eq!(url, "https://example.com/");
+        assert_eq!(routing.variant, None);
+    }
+}
@@ -0,0 +1,33 @@
+use crate::config::geoip::geo_location;
+use crate::models::targeting::GeoLocation;
+use crate::utils::request_context::client_addr;
+use crate::utils::variant::VARIANT_COOKIE;
+use warp::{Filter, Rejection};
+
+/// What is known about the visitor following a short link.
+#[derive(Debug, Clone, Default)]
+pub struct Visitor {
+    pub user_agent: Option<String>,
+    pub referer: Option<String>,
+    pub accept_language: Option<String>,
+    /// Variant assigned on an earlier visit, from the sticky cookie.
+    pub variant: Option<String>,
+    /// Location of the client address, when a GeoIP database is configured.
+    pub location: GeoLocation,
+}
+
+/// Extract the visitor details from the request headers and client address.
+pub fn visitor() -> impl Filter<Extract = (Visitor,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("user-agent")
+        .and(warp::header::optional::<String>("referer"))
+        .and(warp::header::optional::<String>("accept-language"))
+        .and(warp::cookie::optional::<String>(VARIANT_COOKIE))
+        .and(client_addr())
+        .map(|user_agent, referer, accept_language, variant, addr: Option<std::net::SocketAddr>| Visitor {
+            user_agent,
+            referer,
+            accept_language,
+            variant,
+            location: addr.map(|addr| geo_location(addr.ip())).unwrap_or_default(),
+        })
+}
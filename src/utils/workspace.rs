@@ -0,0 +1,88 @@
+use crate::config::db::DbError;
+use std::env;
+use thiserror::Error;
+use warp::http::StatusCode;
+
+/// Default number of days an invite can be accepted.
+const DEFAULT_INVITE_TTL_DAYS: i32 = 7;
+/// Longest workspace name accepted.
+const MAX_WORKSPACE_NAME_LEN: usize = 64;
+/// Longest email address accepted.
+const MAX_EMAIL_LEN: usize = 254;
+
+/// Why a workspace request cannot be carried out.
+#[derive(Debug, Error)]
+pub enum WorkspaceError {
+    #[error("Workspace names must be 1 to {MAX_WORKSPACE_NAME_LEN} printable characters")]
+    InvalidName,
+    #[error("A workspace with this name already exists")]
+    NameTaken,
+    #[error("Invalid email address")]
+    InvalidEmail,
+    #[error("API keys need at least one scope")]
+    NoScopes,
+    #[error("Member not found")]
+    MemberNotFound,
+    #[error("API key not found")]
+    KeyNotFound,
+    #[error("A workspace must keep at least one owner")]
+    LastOwner,
+    #[error("This invite is unknown, expired or already used")]
+    InvalidInvite,
+    #[error("Database error")]
+    Database(#[from] DbError),
+}
+
+impl WorkspaceError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            WorkspaceError::MemberNotFound | WorkspaceError::KeyNotFound | WorkspaceError::InvalidInvite => {
+                StatusCode::NOT_FOUND
+            }
+            WorkspaceError::NameTaken | WorkspaceError::LastOwner => StatusCode::CONFLICT,
+            WorkspaceError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl warp::reject::Reject for WorkspaceError {}
+
+/// Check a workspace name and return it trimmed.
+pub fn validate_workspace_name(name: &str) -> Result<String, WorkspaceError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_WORKSPACE_NAME_LEN || name.chars().any(char::is_control) {
+        return Err(WorkspaceError::InvalidName);
+    }
+    Ok(name.to_string())
+}
+
+/// Check an email address and return it trimmed and lowercased.
+pub fn validate_email(email: &str) -> Result<String, WorkspaceError> {
+    let email = email.trim().to_lowercase();
+    let valid = email.len() <= MAX_EMAIL_LEN
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+        && email
+            .split_once('@')
+            .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.') && !domain.contains('@'));
+    if valid {
+        Ok(email)
+    } else {
+        Err(WorkspaceError::InvalidEmail)
+    }
+}
+
+/// Number of days an invite can be accepted, from `INVITE_TTL_DAYS`.
+pub fn invite_ttl_days() -> i32 {
+    env::var("INVITE_TTL_DAYS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(DEFAULT_INVITE_TTL_DAYS)
+}
+
+/// The link an invitee uses to join.
+pub fn invite_url(token: &str) -> String {
+    let base_url = env::var("BASE_URL").expect("BASE_URL is not set in .env");
+    format!("{}/api/invites/{}", base_url, token)
+}
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use warp::Rejection;
+use warp::Reply;
+
+/// Page shown before a scheduled link becomes active.
+pub async fn link_upcoming(active_from: DateTime<Utc>) -> Result<impl Reply, Rejection> {
+    let message = format!(
+        "This link becomes available on {} UTC.",
+        active_from.format("%B %-d, %Y at %H:%M")
+    );
+    Ok(warp::reply::html(page("Not Yet Available", &message)))
+}
+
+/// Page shown after a scheduled link has ended.
+pub async fn link_ended() -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::html(page("Link Ended", "This link is no longer available.")))
+}
+
+/// Page shown for a link its owner has deleted.
+pub async fn link_removed() -> Result<impl Reply, Rejection> {
+    Ok(warp::reply::html(page("Link Removed", "This link has been removed by its owner.")))
+}
+
+fn page(title: &str, message: &str) -> String {
+    format!(
+        r#"
+        <!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta charset="UTF-8">
+            <meta name="viewport" content="width=device-width, initial-scale=1.0">
+            <meta name="robots" content="noindex">
+            <title>{title}</title>
+            <link href="https://cdnjs.cloudflare.com/ajax/libs/tailwindcss/2.2.19/tailwind.min.css" rel="stylesheet">
+        </head>
+        <body class="bg-gray-50 min-h-screen flex items-center justify-center">
+            <div class="text-center">
+                <h1 class="text-6xl font-bold text-gray-800 mb-4">{title}</h1>
+                <p class="text-gray-600 mb-8">{message}</p>
+                <a href="/" class="bg-blue-600 hover:bg-blue-700 text-white font-medium py-3 px-6 rounded-lg transition-all duration-200">Go to Home</a>
+            </div>
+        </body>
+        </html>
+    "#
+    )
+}
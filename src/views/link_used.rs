@@ -0,0 +1,26 @@
+use warp::Rejection;
+use warp::Reply;
+
+/// Page shown when a single-use link has already been followed.
+pub async fn link_used() -> Result<impl Reply, Rejection> {
+    let html = r#"
+        <!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta charset="UTF-8">
+            <meta name="viewport" content="width=device-width, initial-scale=1.0">
+            <meta name="robots" content="noindex">
+            <title>Link Already Used</title>
+            <link href="https://cdnjs.cloudflare.com/ajax/libs/tailwindcss/2.2.19/tailwind.min.css" rel="stylesheet">
+        </head>
+        <body class="bg-gray-50 min-h-screen flex items-center justify-center">
+            <div class="text-center">
+                <h1 class="text-6xl font-bold text-gray-800 mb-4">Link Already Used</h1>
+                <p class="text-gray-600 mb-8">This link could only be opened once, and it has already been used.</p>
+                <a href="/" class="bg-blue-600 hover:bg-blue-700 text-white font-medium py-3 px-6 rounded-lg transition-all duration-200">Go to Home</a>
+            </div>
+        </body>
+        </html>
+    "#;
+    Ok(warp::reply::html(html))
+}
@@ -1,2 +1,6 @@
 pub mod index;
-pub mod not_found;
\ No newline at end of file
+pub mod link_unavailable;
+pub mod link_used;
+pub mod not_found;
+pub mod password;
+pub mod preview;
@@ -0,0 +1,38 @@
+use warp::Rejection;
+use warp::Reply;
+
+/// Password prompt shown instead of redirecting to a protected link.
+///
+/// The form posts back to the visited URL, so a forwarded path and query string survive unlocking.
+pub async fn password_prompt(error: Option<&str>) -> Result<impl Reply, Rejection> {
+    let error = error
+        .map(|message| format!(r#"<p class="text-red-600 mb-4">{}</p>"#, message))
+        .unwrap_or_default();
+    let html = format!(
+        r#"
+        <!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta charset="UTF-8">
+            <meta name="viewport" content="width=device-width, initial-scale=1.0">
+            <meta name="robots" content="noindex">
+            <title>Password Required</title>
+            <link href="https://cdnjs.cloudflare.com/ajax/libs/tailwindcss/2.2.19/tailwind.min.css" rel="stylesheet">
+        </head>
+        <body class="bg-gray-50 min-h-screen flex items-center justify-center">
+            <div class="text-center w-full max-w-sm px-6">
+                <h1 class="text-4xl font-bold text-gray-800 mb-4">Password Required</h1>
+                <p class="text-gray-600 mb-8">This link is protected. Enter the password to continue.</p>
+                {error}
+                <form method="post">
+                    <input type="password" name="password" required autofocus
+                        class="w-full border border-gray-300 rounded-lg py-3 px-4 mb-4 focus:outline-none focus:border-blue-600">
+                    <button type="submit" class="w-full bg-blue-600 hover:bg-blue-700 text-white font-medium py-3 px-6 rounded-lg transition-all duration-200">Continue</button>
+                </form>
+            </div>
+        </body>
+        </html>
+    "#
+    );
+    Ok(warp::reply::html(html))
+}
@@ -0,0 +1,100 @@
+use chrono::NaiveDateTime;
+use warp::Rejection;
+use warp::Reply;
+
+/// How the preview page's Continue button goes on to the link.
+pub enum Continue<'a> {
+    /// Post to this URL, which redirects without showing the preview again; an empty URL posts back to the visited one.
+    Post(&'a str),
+    /// Open this URL, such as the password prompt of a protected link.
+    Get(&'a str),
+}
+
+/// What the preview page shows about a link.
+pub struct LinkPreview<'a> {
+    pub short_url: &'a str,
+    /// Where the link leads; `None` when it is kept secret, as for password-protected links.
+    pub destination: Option<&'a str>,
+    /// Title of the destination page.
+    pub title: Option<&'a str>,
+    pub created_at: Option<NaiveDateTime>,
+    /// QR code of the short URL, as a `data:` URL.
+    pub qr_code: &'a str,
+    pub continue_to: Continue<'a>,
+}
+
+/// Page showing where a short link leads before following it.
+pub async fn link_preview(preview: LinkPreview<'_>) -> Result<impl Reply, Rejection> {
+    let destination = match preview.destination {
+        Some(url) => format!(
+            r#"<p class="text-gray-600 mb-2">This link leads to</p>
+                <p class="font-mono text-sm bg-white border border-gray-200 rounded-lg p-3 mb-4 break-all">{}</p>"#,
+            escape_html(url)
+        ),
+        None => r#"<p class="text-gray-600 mb-4">This link is password protected; its destination is shown after unlocking it.</p>"#
+            .to_string(),
+    };
+    let title = preview
+        .title
+        .map(|title| format!(r#"<p class="text-lg text-gray-800 mb-4">{}</p>"#, escape_html(title)))
+        .unwrap_or_default();
+    let created = preview
+        .created_at
+        .map(|created_at| {
+            format!(r#"<p class="text-sm text-gray-500 mb-6">Created on {}</p>"#, created_at.format("%B %-d, %Y"))
+        })
+        .unwrap_or_default();
+    let button_class = "inline-block w-full bg-blue-600 hover:bg-blue-700 text-white font-medium py-3 px-6 rounded-lg transition-all duration-200";
+    let button = match preview.continue_to {
+        Continue::Post(action) => format!(
+            r#"<form method="post" action="{}"><button type="submit" class="{}">Continue</button></form>"#,
+            escape_html(action),
+            button_class
+        ),
+        Continue::Get(url) => format!(r#"<a href="{}" class="{}">Continue</a>"#, escape_html(url), button_class),
+    };
+    let short_url = escape_html(preview.short_url);
+    let html = format!(
+        r#"
+        <!DOCTYPE html>
+        <html lang="en">
+        <head>
+            <meta charset="UTF-8">
+            <meta name="viewport" content="width=device-width, initial-scale=1.0">
+            <meta name="robots" content="noindex">
+            <title>Link Preview</title>
+            <link href="https://cdnjs.cloudflare.com/ajax/libs/tailwindcss/2.2.19/tailwind.min.css" rel="stylesheet">
+        </head>
+        <body class="bg-gray-50 min-h-screen flex items-center justify-center">
+            <div class="text-center w-full max-w-md px-6">
+                <h1 class="text-4xl font-bold text-gray-800 mb-2">Link Preview</h1>
+                <p class="text-gray-500 mb-6">{short_url}</p>
+                {destination}
+                {title}
+                {created}
+                <img src="{qr_code}" alt="QR code of {short_url}" class="mx-auto mb-6 w-40 h-40">
+                {button}
+            </div>
+        </body>
+        </html>
+    "#,
+        qr_code = preview.qr_code,
+    );
+    Ok(warp::reply::html(html))
+}
+
+/// Escape text for use in HTML content and quoted attributes.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}